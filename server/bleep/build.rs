@@ -26,6 +26,7 @@ fn set_index_version() {
         "src/semantic/schema.rs",
         "src/semantic/chunk.rs",
         "src/indexes/schema.rs",
+        "src/db/symbol_table.rs",
         "src/intelligence/scope_resolution.rs",
         "../languages.yml",
     ];
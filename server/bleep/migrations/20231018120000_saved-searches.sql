@@ -0,0 +1,21 @@
+CREATE TABLE saved_searches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    query TEXT NOT NULL,
+    last_run_at INTEGER,
+
+    -- JSON serialized fields
+    last_diff TEXT NOT NULL
+);
+
+-- Matches of saved searches are stored one per row, so that a run only writes what changed.
+CREATE TABLE saved_search_matches (
+    saved_search_id INTEGER NOT NULL,
+    repo_ref TEXT NOT NULL,
+    relative_path TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    text TEXT NOT NULL
+);
+
+CREATE INDEX saved_search_matches_search ON saved_search_matches (saved_search_id, repo_ref);
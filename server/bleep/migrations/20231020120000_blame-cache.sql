@@ -0,0 +1,12 @@
+-- blame depends on the path and history of a blob, not only on its contents
+CREATE TABLE blame_cache (
+    repo_ref TEXT NOT NULL,
+    blob_hash TEXT NOT NULL,
+    path TEXT NOT NULL,
+    commit_id TEXT NOT NULL,
+
+    -- JSON serialized fields
+    blame TEXT NOT NULL,
+
+    PRIMARY KEY (repo_ref, blob_hash, path, commit_id)
+);
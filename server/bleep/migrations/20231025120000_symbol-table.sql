@@ -0,0 +1,15 @@
+CREATE TABLE symbol_table (
+    repo_ref TEXT NOT NULL,
+    cache_key TEXT NOT NULL,
+    relative_path TEXT NOT NULL,
+    lang TEXT NOT NULL,
+    branches TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+
+    -- JSON serialized fields
+    text_range TEXT NOT NULL
+);
+
+CREATE INDEX symbol_table_name ON symbol_table (repo_ref, name);
+CREATE INDEX symbol_table_cache_key ON symbol_table (repo_ref, cache_key);
@@ -0,0 +1,28 @@
+CREATE TABLE precise_occurrences (
+    repo_ref TEXT NOT NULL,
+    -- empty for indexes that apply to any branch
+    branch TEXT NOT NULL,
+    -- the directory of the repo the index was produced for
+    root TEXT NOT NULL,
+    -- `upload` for indexes uploaded through the API, `repo` for indexes committed to the repo
+    source TEXT NOT NULL,
+    relative_path TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    is_global BOOLEAN NOT NULL,
+    is_definition BOOLEAN NOT NULL,
+    start_line INTEGER NOT NULL,
+    start_column INTEGER NOT NULL,
+    end_line INTEGER NOT NULL,
+    end_column INTEGER NOT NULL,
+    -- the unit that columns are counted in: `utf-8`, `utf-16` or `utf-32`
+    encoding TEXT NOT NULL
+);
+
+CREATE INDEX precise_occurrences_path ON precise_occurrences (repo_ref, branch, relative_path);
+CREATE INDEX precise_occurrences_symbol ON precise_occurrences (symbol);
+
+-- the blob that the index committed to each repo was last ingested from
+CREATE TABLE precise_repo_indexes (
+    repo_ref TEXT NOT NULL PRIMARY KEY,
+    blob_id TEXT NOT NULL
+);
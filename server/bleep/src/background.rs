@@ -3,6 +3,7 @@ use tokio::sync::Semaphore;
 use tracing::{debug, info};
 
 use crate::{
+    db::SavedSearchDiff,
     repo::{BranchFilter, RepoRef, SyncStatus},
     Application, Configuration,
 };
@@ -23,11 +24,11 @@ type ProgressStream = tokio::sync::broadcast::Sender<Progress>;
 #[derive(serde::Serialize, Clone)]
 pub struct Progress {
     #[serde(rename = "ref")]
-    reporef: RepoRef,
+    pub(crate) reporef: RepoRef,
     #[serde(rename = "b")]
     branch_filter: Option<BranchFilter>,
     #[serde(rename = "ev")]
-    event: ProgressEvent,
+    pub(crate) event: ProgressEvent,
 }
 
 #[derive(serde::Serialize, Clone)]
@@ -35,6 +36,12 @@ pub struct Progress {
 pub enum ProgressEvent {
     IndexPercent(u8),
     StatusChange(SyncStatus),
+    /// A saved search has new or removed matches after the repository was synced.
+    SavedSearch {
+        id: i64,
+        added: usize,
+        removed: usize,
+    },
 }
 
 type Task = Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
@@ -181,6 +188,18 @@ impl SyncQueue {
         self.progress.subscribe()
     }
 
+    pub(crate) fn notify_saved_search(&self, reporef: RepoRef, id: i64, diff: &SavedSearchDiff) {
+        _ = self.progress.send(Progress {
+            reporef,
+            branch_filter: None,
+            event: ProgressEvent::SavedSearch {
+                id,
+                added: diff.added.len(),
+                removed: diff.removed.len(),
+            },
+        });
+    }
+
     pub(crate) async fn read_queue(&self) -> Vec<QueuedRepoStatus> {
         let mut output = vec![];
         self.active
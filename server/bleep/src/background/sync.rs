@@ -1,16 +1,21 @@
 use either::Either;
 use tokio::sync::OwnedSemaphorePermit;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
     cache::FileCache,
+    db::{BlameCache, PreciseIndex, SymbolTable},
     indexes,
+    intelligence::precise,
     remotes::RemoteError,
-    repo::{Backend, RepoError, RepoMetadata, RepoRef, Repository, SyncStatus},
+    repo::{iterator, Backend, RepoError, RepoMetadata, RepoRef, Repository, SyncStatus},
     Application,
 };
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use super::control::SyncPipes;
 
@@ -108,7 +113,7 @@ impl SyncHandle {
                     let disk_path = app
                         .config
                         .source
-                        .repo_path_for_name(&name.replace('/', "_"));
+                        .repo_path_for_name(&name.replace(['/', ':'], "_"));
 
                     Repository {
                         disk_path,
@@ -118,6 +123,9 @@ impl SyncHandle {
                         last_commit_unix_secs: 0,
                         most_common_lang: None,
                         branch_filter: None,
+                        index_settings: Default::default(),
+                        last_indexed_commit: None,
+                        last_history_commit: None,
                     }
                 }
             });
@@ -230,6 +238,8 @@ impl SyncHandle {
         match indexed {
             Ok(_) => {
                 writers.commit().await.map_err(SyncError::Tantivy)?;
+                self.load_precise_index(&repo).await;
+                self.evict_blame(&repo).await;
                 indexed.map_err(SyncError::Indexing)
             }
             Err(_) if self.pipes.is_removed() => self.delete_repo(&repo, writers).await,
@@ -245,6 +255,64 @@ impl SyncHandle {
         }
     }
 
+    /// Ingest a SCIP index or LSIF dump committed at the root of the repository, as the default
+    /// index of every branch. Failing to do so doesn't fail the sync.
+    async fn load_precise_index(&self, repo: &Repository) {
+        if let Err(err) = self.try_load_precise_index(repo).await {
+            warn!(?err, %self.reporef, "failed to load precise index");
+        }
+    }
+
+    /// Unchanged indexes aren't ingested again, and the occurrences of deleted ones are removed.
+    async fn try_load_precise_index(&self, repo: &Repository) -> anyhow::Result<()> {
+        let index = PreciseIndex::new(&self.app.sql);
+        let repo_ref = self.reporef.to_string();
+
+        let (reporef, dir) = (self.reporef.clone(), repo.disk_path.clone());
+        let found = tokio::task::spawn_blocking(move || committed_precise_index(&reporef, &dir));
+        let Some((blob_id, data)) = found.await?? else {
+            return index.delete_committed(&repo_ref).await;
+        };
+
+        if index.committed_blob(&repo_ref).await?.as_deref() == Some(blob_id.as_str()) {
+            debug!(%self.reporef, "precise index unchanged");
+            return Ok(());
+        }
+
+        let occurrences = tokio::task::spawn_blocking(move || precise::parse(&data, "")).await??;
+        index
+            .replace_committed(&repo_ref, &blob_id, occurrences)
+            .await
+    }
+
+    /// Drop cached blame that was computed from commits no reference points to anymore. Failing
+    /// to do so doesn't fail the sync.
+    async fn evict_blame(&self, repo: &Repository) {
+        if let Err(err) = self.try_evict_blame(repo).await {
+            warn!(?err, %self.reporef, "failed to evict cached blame");
+        }
+    }
+
+    async fn try_evict_blame(&self, repo: &Repository) -> anyhow::Result<()> {
+        let cache = BlameCache::new(&self.app.sql);
+        let repo_ref = self.reporef.to_string();
+
+        // repositories that were never blamed may not be git repositories at all
+        let cached = cache.commits(&repo_ref).await?;
+        if cached.is_empty() {
+            return Ok(());
+        }
+
+        let dir = repo.disk_path.clone();
+        let current = tokio::task::spawn_blocking(move || iterator::ref_commits(&dir)).await??;
+        let outdated = cached
+            .into_iter()
+            .filter(|commit| !current.contains(commit))
+            .collect::<Vec<_>>();
+
+        cache.delete_commits(&repo_ref, &outdated).await
+    }
+
     async fn delete_repo(
         &self,
         repo: &Repository,
@@ -319,6 +387,21 @@ impl SyncHandle {
             .await
             .map_err(SyncError::Sql)?;
 
+        PreciseIndex::new(sql)
+            .delete_repo(&self.reporef.to_string())
+            .await
+            .map_err(SyncError::Sql)?;
+
+        BlameCache::new(sql)
+            .delete_repo(&self.reporef.to_string())
+            .await
+            .map_err(SyncError::Sql)?;
+
+        SymbolTable::new(sql)
+            .delete_repo(&self.reporef.to_string())
+            .await
+            .map_err(SyncError::Sql)?;
+
         if !self.reporef.is_local() {
             tokio::fs::remove_dir_all(&repo.disk_path)
                 .await
@@ -380,3 +463,33 @@ impl SyncHandle {
         }
     }
 }
+
+/// The ID and contents of the SCIP index or LSIF dump at the root of the repository at `dir`.
+///
+/// Git repositories are read at their indexed `HEAD`, like the rest of the index, so that
+/// uncommitted changes aren't ingested. Other directories are read from disk, and identified by
+/// the hash of the contents.
+fn committed_precise_index(
+    reporef: &RepoRef,
+    dir: &Path,
+) -> anyhow::Result<Option<(String, Vec<u8>)>> {
+    let is_git = gix::open(dir).is_ok();
+
+    for name in precise::INDEX_FILE_NAMES {
+        let found = if is_git {
+            iterator::indexed_blob(reporef, dir, name)?
+        } else {
+            match std::fs::read(dir.join(name)) {
+                Ok(data) => Some((blake3::hash(&data).to_string(), data)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+
+    Ok(None)
+}
@@ -118,6 +118,7 @@ impl<'a> FileCache<'a> {
         let mut tx = self.db.begin().await?;
         self.delete_files(&mut tx).await?;
         self.delete_chunks(&mut tx).await?;
+        self.delete_symbols(&mut tx).await?;
         tx.commit().await?;
 
         Ok(())
@@ -149,6 +150,20 @@ impl<'a> FileCache<'a> {
         Ok(())
     }
 
+    /// The symbol table mirrors the cache keys of files, so it's cleared along with them.
+    async fn delete_symbols(&self, tx: &mut sqlx::Transaction<'_, Sqlite>) -> anyhow::Result<()> {
+        let repo_str = self.reporef.to_string();
+        sqlx::query! {
+            "DELETE FROM symbol_table \
+                 WHERE repo_ref = ?",
+            repo_str
+        }
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn chunks_for_file(&self, key: &'a str) -> ChunkCache<'a> {
         ChunkCache::for_file(self.db, self.reporef, key).await
     }
@@ -163,7 +178,7 @@ pub struct ChunkCache<'a> {
     reporef: &'a RepoRef,
     file_cache_key: &'a str,
     cache: scc::HashMap<String, FreshValue<String>>,
-    update: scc::HashMap<(Vec<String>, String), Vec<String>>,
+    update: scc::HashMap<(Vec<String>, u64, String), Vec<String>>,
     new: RwLock<Vec<PointStruct>>,
     new_sql: RwLock<Vec<(String, String)>>,
 }
@@ -205,7 +220,15 @@ impl<'a> ChunkCache<'a> {
         payload: Payload,
     ) -> anyhow::Result<()> {
         let id = self.cache_key(data);
-        let branches_hash = blake3::hash(payload.branches.join("\n").as_ref()).to_string();
+
+        // The last commit time is hashed alongside branches, so that both are refreshed together on
+        // chunks that didn't change.
+        let branches_hash = {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(payload.branches.join("\n").as_ref());
+            hasher.update(&payload.last_commit_unix_secs.to_le_bytes());
+            hasher.finalize().to_string()
+        };
 
         match self.cache.entry(id) {
             scc::hash_map::Entry::Occupied(mut existing) => {
@@ -213,7 +236,11 @@ impl<'a> ChunkCache<'a> {
                 trace!(?key, "found; not upserting new");
                 if existing.get().value != branches_hash {
                     self.update
-                        .entry((payload.branches, branches_hash.clone()))
+                        .entry((
+                            payload.branches,
+                            payload.last_commit_unix_secs,
+                            branches_hash.clone(),
+                        ))
                         .or_insert_with(Vec::new)
                         .get_mut()
                         .push(existing.key().to_owned());
@@ -343,7 +370,7 @@ impl<'a> ChunkCache<'a> {
     }
 
     /// Update points where the list of branches in which they're
-    /// searchable has changed.
+    /// searchable, or the time of the last commit, has changed.
     async fn commit_branch_updates(
         &self,
         tx: &mut sqlx::Transaction<'_, Sqlite>,
@@ -354,7 +381,7 @@ impl<'a> ChunkCache<'a> {
 
         let mut next = self.update.first_occupied_entry();
         while let Some(entry) = next {
-            let (branches_list, branches_hash) = entry.key();
+            let (branches_list, last_commit_unix_secs, branches_hash) = entry.key();
             let points = entry.get();
             update_size += points.len();
 
@@ -377,7 +404,14 @@ impl<'a> ChunkCache<'a> {
                 .into();
 
             let payload = qdrant_client::client::Payload::new_from_hashmap(
-                [("branches".to_string(), branches_list.to_owned().into())].into(),
+                [
+                    ("branches".to_string(), branches_list.to_owned().into()),
+                    (
+                        "last_commit_unix_secs".to_string(),
+                        (*last_commit_unix_secs as i64).into(),
+                    ),
+                ]
+                .into(),
             );
 
             qdrant_updates.push(async move {
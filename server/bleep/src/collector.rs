@@ -1,5 +1,11 @@
 mod bytes_filter;
+mod facet;
+mod fields_filter;
 mod frequency;
+mod paged;
 
 pub use bytes_filter::BytesFilterCollector;
+pub use facet::{extension, lines, top_level_dir, FacetCollector};
+pub use fields_filter::FieldsFilterCollector;
 pub use frequency::FrequencyCollector;
+pub use paged::PagedCollector;
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    fastfield::BytesFastFieldReader,
+    schema::Field,
+    Score, SegmentReader,
+};
+
+/// Like [`super::FrequencyCollector`], but counts the facets extracted from a field's value,
+/// rather than the value itself. A document is counted once for every facet it has.
+pub struct FacetCollector {
+    field: Field,
+    facets: fn(&[u8]) -> Vec<&[u8]>,
+}
+
+impl FacetCollector {
+    pub fn new(field: Field, facets: fn(&[u8]) -> Vec<&[u8]>) -> Self {
+        Self { field, facets }
+    }
+}
+
+impl Collector for FacetCollector {
+    type Fruit = HashMap<Vec<u8>, usize>;
+
+    type Child = FacetSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: u32,
+        segment_reader: &SegmentReader,
+    ) -> tantivy::Result<FacetSegmentCollector> {
+        let reader = segment_reader.fast_fields().bytes(self.field)?;
+        Ok(FacetSegmentCollector {
+            reader,
+            facets: self.facets,
+            freqs: HashMap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        // this collector does not care about score.
+        false
+    }
+
+    fn merge_fruits(&self, segments: Vec<Self::Fruit>) -> tantivy::Result<Self::Fruit> {
+        let mut freqs = HashMap::new();
+        for segment in segments.into_iter() {
+            for (k, v) in segment {
+                *freqs.entry(k).or_default() += v;
+            }
+        }
+        Ok(freqs)
+    }
+}
+
+pub struct FacetSegmentCollector {
+    reader: BytesFastFieldReader,
+    facets: fn(&[u8]) -> Vec<&[u8]>,
+    freqs: HashMap<Vec<u8>, usize>,
+}
+
+impl SegmentCollector for FacetSegmentCollector {
+    type Fruit = HashMap<Vec<u8>, usize>;
+
+    fn collect(&mut self, doc: u32, _score: Score) {
+        for facet in (self.facets)(self.reader.get_bytes(doc)) {
+            match self.freqs.get_mut(facet) {
+                Some(count) => *count += 1,
+                None => {
+                    self.freqs.insert(facet.to_owned(), 1);
+                }
+            }
+        }
+    }
+
+    fn harvest(self) -> <Self as SegmentCollector>::Fruit {
+        self.freqs
+    }
+}
+
+/// The first component of a relative path, if it is inside a directory.
+///
+/// Directories themselves, which end with a `/`, are counted under their top-level directory.
+pub fn top_level_dir(path: &[u8]) -> Vec<&[u8]> {
+    match path.iter().position(|&b| b == b'/') {
+        Some(i) if i > 0 => vec![&path[..i]],
+        _ => vec![],
+    }
+}
+
+/// The extension of a relative path, without the leading `.`.
+///
+/// Directories and dotfiles without a further extension, such as `.gitignore`, have none.
+pub fn extension(path: &[u8]) -> Vec<&[u8]> {
+    let name = match path.iter().rposition(|&b| b == b'/') {
+        Some(i) => &path[i + 1..],
+        None => path,
+    };
+
+    match name.iter().rposition(|&b| b == b'.') {
+        Some(i) if i > 0 && i + 1 < name.len() => vec![&name[i + 1..]],
+        _ => vec![],
+    }
+}
+
+/// Every entry of a newline-separated list, such as the branches of a file.
+pub fn lines(value: &[u8]) -> Vec<&[u8]> {
+    value
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn path_facets() {
+        assert_eq!(top_level_dir(b"src/lib.rs"), [b"src"]);
+        assert_eq!(top_level_dir(b"server/bleep/"), [b"server"]);
+        assert!(top_level_dir(b"README.md").is_empty());
+
+        assert_eq!(extension(b"src/lib.rs"), [b"rs"]);
+        assert_eq!(extension(b"archive.tar.gz"), [b"gz"]);
+        assert!(extension(b"server/bleep/").is_empty());
+        assert!(extension(b"client/.gitignore").is_empty());
+        assert!(extension(b"Makefile").is_empty());
+
+        assert_eq!(
+            lines(b"main\norigin/HEAD\n"),
+            [&b"main"[..], b"origin/HEAD"]
+        );
+    }
+}
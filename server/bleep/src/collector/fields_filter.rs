@@ -0,0 +1,125 @@
+// a version of BytesFilterCollector that checks several byte fast fields of a doc at once
+
+use smallvec::SmallVec;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::fastfield::BytesFastFieldReader;
+use tantivy::schema::Field;
+use tantivy::{Score, SegmentReader, TantivyError};
+
+pub struct FieldsFilterCollector<TCollector, TPredicate>
+where
+    TPredicate: 'static + Clone,
+{
+    fields: Vec<Field>,
+    collector: TCollector,
+    predicate: TPredicate,
+}
+
+impl<TCollector, TPredicate> FieldsFilterCollector<TCollector, TPredicate>
+where
+    TCollector: Collector + Send + Sync,
+    TPredicate: Fn(&[&[u8]]) -> bool + Send + Sync + Clone,
+{
+    /// Create a new FieldsFilterCollector.
+    ///
+    /// The predicate is given the values of `fields`, in the same order.
+    pub fn new(
+        fields: Vec<Field>,
+        predicate: TPredicate,
+        collector: TCollector,
+    ) -> FieldsFilterCollector<TCollector, TPredicate> {
+        FieldsFilterCollector {
+            fields,
+            predicate,
+            collector,
+        }
+    }
+}
+
+impl<TCollector, TPredicate> Collector for FieldsFilterCollector<TCollector, TPredicate>
+where
+    TCollector: Collector + Send + Sync,
+    TPredicate: 'static + Fn(&[&[u8]]) -> bool + Send + Sync + Clone,
+{
+    type Fruit = TCollector::Fruit;
+
+    type Child = FieldsFilterSegmentCollector<TCollector::Child, TPredicate>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: u32,
+        segment_reader: &SegmentReader,
+    ) -> tantivy::Result<FieldsFilterSegmentCollector<TCollector::Child, TPredicate>> {
+        let schema = segment_reader.schema();
+        let fast_field_readers = self
+            .fields
+            .iter()
+            .map(|&field| {
+                let field_entry = schema.get_field_entry(field);
+                if !field_entry.is_fast() {
+                    return Err(TantivyError::SchemaError(format!(
+                        "Field {:?} is not a fast field.",
+                        field_entry.name()
+                    )));
+                }
+
+                segment_reader.fast_fields().bytes(field)
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+
+        let segment_collector = self
+            .collector
+            .for_segment(segment_local_id, segment_reader)?;
+
+        Ok(FieldsFilterSegmentCollector {
+            fast_field_readers,
+            segment_collector,
+            predicate: self.predicate.clone(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.collector.requires_scoring()
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<TCollector::Child as SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<TCollector::Fruit> {
+        self.collector.merge_fruits(segment_fruits)
+    }
+}
+
+pub struct FieldsFilterSegmentCollector<TSegmentCollector, TPredicate>
+where
+    TPredicate: 'static,
+{
+    fast_field_readers: Vec<BytesFastFieldReader>,
+    segment_collector: TSegmentCollector,
+    predicate: TPredicate,
+}
+
+impl<TSegmentCollector, TPredicate> SegmentCollector
+    for FieldsFilterSegmentCollector<TSegmentCollector, TPredicate>
+where
+    TSegmentCollector: SegmentCollector,
+    TPredicate: 'static + Fn(&[&[u8]]) -> bool + Send + Sync,
+{
+    type Fruit = TSegmentCollector::Fruit;
+
+    fn collect(&mut self, doc: u32, score: Score) {
+        let values = self
+            .fast_field_readers
+            .iter()
+            .map(|reader| reader.get_bytes(doc))
+            .collect::<SmallVec<[&[u8]; 6]>>();
+
+        if (self.predicate)(&values) {
+            self.segment_collector.collect(doc, score)
+        }
+    }
+
+    fn harvest(self) -> <TSegmentCollector as SegmentCollector>::Fruit {
+        self.segment_collector.harvest()
+    }
+}
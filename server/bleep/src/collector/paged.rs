@@ -0,0 +1,117 @@
+// a top-docs collector that can also collect every matching document, such as for exports
+
+use tantivy::collector::{Collector, DocSetCollector, SegmentCollector};
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+type DocSetSegmentCollector = <DocSetCollector as Collector>::Child;
+type DocSetSegmentFruit = <DocSetSegmentCollector as SegmentCollector>::Fruit;
+
+/// Collects either a ranked page of documents, with `Top`, or every matching document in index
+/// order, with `All`.
+///
+/// Collecting every document visits each match once, whereas a top-docs collector with an offset
+/// ranks all the documents before the page it returns.
+pub enum PagedCollector<TCollector> {
+    Top(TCollector),
+    All,
+}
+
+impl<TCollector, TScore> Collector for PagedCollector<TCollector>
+where
+    TCollector: Collector<Fruit = Vec<(TScore, DocAddress)>>,
+    TScore: Default + Send + Sync + 'static,
+{
+    type Fruit = Vec<(TScore, DocAddress)>;
+
+    type Child = PagedSegmentCollector<TCollector::Child>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment_reader: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(match self {
+            Self::Top(collector) => {
+                PagedSegmentCollector::Top(collector.for_segment(segment_local_id, segment_reader)?)
+            }
+            Self::All => PagedSegmentCollector::All(
+                DocSetCollector.for_segment(segment_local_id, segment_reader)?,
+            ),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        match self {
+            Self::Top(collector) => collector.requires_scoring(),
+            Self::All => false,
+        }
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<PagedFruit<<TCollector::Child as SegmentCollector>::Fruit>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        match self {
+            Self::Top(collector) => collector.merge_fruits(
+                segment_fruits
+                    .into_iter()
+                    .filter_map(|fruit| match fruit {
+                        PagedFruit::Top(fruit) => Some(fruit),
+                        PagedFruit::All(_) => None,
+                    })
+                    .collect(),
+            ),
+            Self::All => {
+                let docs = segment_fruits
+                    .into_iter()
+                    .filter_map(|fruit| match fruit {
+                        PagedFruit::All(fruit) => Some(fruit),
+                        PagedFruit::Top(_) => None,
+                    })
+                    .collect();
+
+                let mut docs = DocSetCollector
+                    .merge_fruits(docs)?
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                docs.sort();
+
+                Ok(docs
+                    .into_iter()
+                    .map(|addr| (TScore::default(), addr))
+                    .collect())
+            }
+        }
+    }
+}
+
+pub enum PagedSegmentCollector<TSegmentCollector> {
+    Top(TSegmentCollector),
+    All(DocSetSegmentCollector),
+}
+
+pub enum PagedFruit<TFruit> {
+    Top(TFruit),
+    All(DocSetSegmentFruit),
+}
+
+impl<TSegmentCollector> SegmentCollector for PagedSegmentCollector<TSegmentCollector>
+where
+    TSegmentCollector: SegmentCollector,
+{
+    type Fruit = PagedFruit<TSegmentCollector::Fruit>;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        match self {
+            Self::Top(collector) => collector.collect(doc, score),
+            Self::All(collector) => collector.collect(doc, score),
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        match self {
+            Self::Top(collector) => PagedFruit::Top(collector.harvest()),
+            Self::All(collector) => PagedFruit::All(collector.harvest()),
+        }
+    }
+}
@@ -6,9 +6,21 @@ use tracing::{debug, warn};
 
 use crate::Configuration;
 
+mod blame_cache;
+pub use blame_cache::BlameCache;
+
+mod precise_index;
+pub use precise_index::{IndexSource, OccurrenceAt, PreciseIndex, SymbolOccurrence};
+
 mod query_log;
 pub use query_log::QueryLog;
 
+mod saved_searches;
+pub use saved_searches::{SavedMatch, SavedSearch, SavedSearchDiff, SavedSearches};
+
+mod symbol_table;
+pub use symbol_table::{symbol_sites, FileSites, SiteKind, SiteMatch, SymbolSite, SymbolTable};
+
 pub type SqlDb = Arc<SqlitePool>;
 
 pub async fn init(config: &Configuration) -> Result<SqlitePool> {
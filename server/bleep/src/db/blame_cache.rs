@@ -0,0 +1,103 @@
+use crate::repo::blame::BlameRange;
+
+/// Blame annotations of files, by the hash of their blob, their path and the commit that blame
+/// was computed from.
+///
+/// Blame follows the history of a path, so the same blob is annotated differently at another
+/// path, or when reached through another history.
+pub struct BlameCache<'a> {
+    db: &'a super::SqlitePool,
+}
+
+impl<'a> BlameCache<'a> {
+    pub fn new(db: &'a super::SqlitePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn get(
+        &self,
+        repo_ref: &str,
+        blob_hash: &str,
+        path: &str,
+        commit_id: &str,
+    ) -> anyhow::Result<Option<Vec<BlameRange>>> {
+        let row = sqlx::query!(
+            "SELECT blame FROM blame_cache \
+             WHERE repo_ref = ? AND blob_hash = ? AND path = ? AND commit_id = ?",
+            repo_ref,
+            blob_hash,
+            path,
+            commit_id,
+        )
+        .fetch_optional(self.db)
+        .await?;
+
+        row.map(|r| serde_json::from_str(&r.blame))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    pub async fn insert(
+        &self,
+        repo_ref: &str,
+        blob_hash: &str,
+        path: &str,
+        commit_id: &str,
+        blame: &[BlameRange],
+    ) -> anyhow::Result<()> {
+        let blame = serde_json::to_string(blame)?;
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO blame_cache (repo_ref, blob_hash, path, commit_id, blame) \
+             VALUES (?, ?, ?, ?, ?)",
+            repo_ref,
+            blob_hash,
+            path,
+            commit_id,
+            blame,
+        )
+        .execute(self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The commits that annotations of a repository were computed from.
+    pub async fn commits(&self, repo_ref: &str) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query!(
+            "SELECT DISTINCT commit_id FROM blame_cache WHERE repo_ref = ?",
+            repo_ref,
+        )
+        .fetch_all(self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.commit_id).collect())
+    }
+
+    /// Remove the annotations of a repository computed from any of `commits`.
+    pub async fn delete_commits(&self, repo_ref: &str, commits: &[String]) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        for commit_id in commits {
+            sqlx::query!(
+                "DELETE FROM blame_cache WHERE repo_ref = ? AND commit_id = ?",
+                repo_ref,
+                commit_id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_repo(&self, repo_ref: &str) -> anyhow::Result<()> {
+        sqlx::query!("DELETE FROM blame_cache WHERE repo_ref = ?", repo_ref)
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use sqlx::{Sqlite, Transaction};
+
+use crate::intelligence::precise::{LineRange, PositionEncoding, PreciseOccurrence};
+
+/// Where an index came from. Indexes from different sources are stored side by side, so that
+/// ingesting one never replaces the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexSource {
+    /// Uploaded through the API
+    Upload,
+    /// Committed at the root of the repository, and ingested on sync
+    Repo,
+}
+
+impl IndexSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Repo => "repo",
+        }
+    }
+
+    fn parse(source: &str) -> Option<Self> {
+        match source {
+            "upload" => Some(Self::Upload),
+            "repo" => Some(Self::Repo),
+            _ => None,
+        }
+    }
+}
+
+/// The index that occurrences in a branch of a repository are looked up in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedBranch {
+    pub branch: String,
+    pub source: IndexSource,
+}
+
+/// An occurrence of a symbol, found by the symbol.
+#[derive(Debug)]
+pub struct SymbolOccurrence {
+    pub repo_ref: String,
+    pub branch: String,
+    pub source: IndexSource,
+    pub relative_path: String,
+    pub definition: bool,
+    pub range: LineRange,
+}
+
+/// The symbol of an occurrence, found by its position.
+#[derive(Debug)]
+pub struct OccurrenceAt {
+    pub symbol: String,
+    pub global: bool,
+    pub definition: bool,
+}
+
+/// Occurrences of symbols ingested from SCIP indexes and LSIF dumps.
+///
+/// Each repository may have an index per branch, and per directory that an indexer was run in.
+/// Indexes stored without a branch apply to every branch that doesn't have its own. An index
+/// committed to the repository applies to every branch too, unless one was uploaded.
+pub struct PreciseIndex<'a> {
+    db: &'a super::SqlitePool,
+}
+
+impl<'a> PreciseIndex<'a> {
+    pub fn new(db: &'a super::SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Replace the occurrences from the uploaded index of the `root` directory of a repository.
+    pub async fn replace(
+        &self,
+        repo_ref: &str,
+        branch: &str,
+        root: &str,
+        occurrences: Vec<PreciseOccurrence>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+        let source = IndexSource::Upload;
+        replace_occurrences(&mut tx, repo_ref, branch, root, source, occurrences).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The ID of the blob that the index committed to a repository was last ingested from.
+    pub async fn committed_blob(&self, repo_ref: &str) -> anyhow::Result<Option<String>> {
+        let row = sqlx::query!(
+            "SELECT blob_id FROM precise_repo_indexes WHERE repo_ref = ?",
+            repo_ref
+        )
+        .fetch_optional(self.db)
+        .await?;
+
+        Ok(row.map(|r| r.blob_id))
+    }
+
+    /// Replace the occurrences from the index committed to a repository, read from `blob_id`.
+    pub async fn replace_committed(
+        &self,
+        repo_ref: &str,
+        blob_id: &str,
+        occurrences: Vec<PreciseOccurrence>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+        let source = IndexSource::Repo;
+        replace_occurrences(&mut tx, repo_ref, "", "", source, occurrences).await?;
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO precise_repo_indexes (repo_ref, blob_id) VALUES (?, ?)",
+            repo_ref,
+            blob_id,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Remove the occurrences from the index committed to a repository, once it's been deleted.
+    pub async fn delete_committed(&self, repo_ref: &str) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+        let source = IndexSource::Repo;
+        replace_occurrences(&mut tx, repo_ref, "", "", source, vec![]).await?;
+
+        sqlx::query!(
+            "DELETE FROM precise_repo_indexes WHERE repo_ref = ?",
+            repo_ref
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_repo(&self, repo_ref: &str) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM precise_occurrences WHERE repo_ref = ?",
+            repo_ref
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM precise_repo_indexes WHERE repo_ref = ?",
+            repo_ref
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The index used for `branch` of a repository: its own if it has one, or the default index
+    /// otherwise, where uploaded indexes take precedence over committed ones. `None` if there is
+    /// no such index.
+    pub async fn indexed_branch(
+        &self,
+        repo_ref: &str,
+        branch: &str,
+    ) -> anyhow::Result<Option<IndexedBranch>> {
+        let row = sqlx::query!(
+            "SELECT branch, source FROM precise_occurrences \
+             WHERE repo_ref = ? AND branch IN (?, '') \
+             ORDER BY branch DESC, source = 'upload' DESC LIMIT 1",
+            repo_ref,
+            branch,
+        )
+        .fetch_optional(self.db)
+        .await?;
+
+        Ok(row.and_then(|r| {
+            Some(IndexedBranch {
+                branch: r.branch,
+                source: IndexSource::parse(&r.source)?,
+            })
+        }))
+    }
+
+    /// The innermost occurrence at the byte `offset` of a file with the given `content`.
+    pub async fn at(
+        &self,
+        repo_ref: &str,
+        index: &IndexedBranch,
+        relative_path: &str,
+        content: &str,
+        offset: usize,
+    ) -> anyhow::Result<Option<OccurrenceAt>> {
+        let Some(line) = content
+            .get(..offset)
+            .map(|before| before.matches('\n').count())
+        else {
+            return Ok(None);
+        };
+
+        let line = line as i64;
+        let source = index.source.as_str();
+        let rows = sqlx::query!(
+            "SELECT symbol, is_global, is_definition, \
+             start_line, start_column, end_line, end_column, encoding \
+             FROM precise_occurrences \
+             WHERE repo_ref = ? AND branch = ? AND source = ? AND relative_path = ? \
+             AND start_line <= ? AND end_line >= ?",
+            repo_ref,
+            index.branch,
+            source,
+            relative_path,
+            line,
+            line,
+        )
+        .fetch_all(self.db)
+        .await?;
+
+        let innermost = rows
+            .into_iter()
+            .filter_map(|r| {
+                let range = LineRange {
+                    start_line: r.start_line as usize,
+                    start_column: r.start_column as usize,
+                    end_line: r.end_line as usize,
+                    end_column: r.end_column as usize,
+                    encoding: PositionEncoding::parse(&r.encoding)?,
+                };
+                let occurrence = OccurrenceAt {
+                    symbol: r.symbol,
+                    global: r.is_global,
+                    definition: r.is_definition,
+                };
+                Some((range.to_byte_range(content)?, occurrence))
+            })
+            .filter(|(range, _)| range.contains(&offset))
+            .min_by_key(|(range, _)| range.len());
+
+        Ok(innermost.map(|(_, occurrence)| occurrence))
+    }
+
+    /// Every occurrence of `symbol`, in any repository and branch.
+    pub async fn by_symbol(&self, symbol: &str) -> anyhow::Result<Vec<SymbolOccurrence>> {
+        let rows = sqlx::query!(
+            "SELECT repo_ref, branch, source, relative_path, is_definition, \
+             start_line, start_column, end_line, end_column, encoding \
+             FROM precise_occurrences WHERE symbol = ?",
+            symbol,
+        )
+        .fetch_all(self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| {
+                Some(SymbolOccurrence {
+                    repo_ref: r.repo_ref,
+                    branch: r.branch,
+                    source: IndexSource::parse(&r.source)?,
+                    relative_path: r.relative_path,
+                    definition: r.is_definition,
+                    range: LineRange {
+                        start_line: r.start_line as usize,
+                        start_column: r.start_column as usize,
+                        end_line: r.end_line as usize,
+                        end_column: r.end_column as usize,
+                        encoding: PositionEncoding::parse(&r.encoding)?,
+                    },
+                })
+            })
+            .collect())
+    }
+}
+
+/// Replace the occurrences of an index, identified by its repository, branch, root directory and
+/// source.
+async fn replace_occurrences(
+    tx: &mut Transaction<'_, Sqlite>,
+    repo_ref: &str,
+    branch: &str,
+    root: &str,
+    source: IndexSource,
+    occurrences: Vec<PreciseOccurrence>,
+) -> anyhow::Result<()> {
+    let source = source.as_str();
+
+    sqlx::query!(
+        "DELETE FROM precise_occurrences \
+         WHERE repo_ref = ? AND branch = ? AND root = ? AND source = ?",
+        repo_ref,
+        branch,
+        root,
+        source,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for occurrence in occurrences {
+        let LineRange {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            encoding,
+        } = occurrence.range;
+        let (start_line, start_column) = (start_line as i64, start_column as i64);
+        let (end_line, end_column) = (end_line as i64, end_column as i64);
+        let encoding = encoding.as_str();
+
+        sqlx::query!(
+            "INSERT INTO precise_occurrences \
+             (repo_ref, branch, root, source, relative_path, symbol, is_global, is_definition, \
+             start_line, start_column, end_line, end_column, encoding) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            repo_ref,
+            branch,
+            root,
+            source,
+            occurrence.relative_path,
+            occurrence.symbol,
+            occurrence.global,
+            occurrence.definition,
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            encoding,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
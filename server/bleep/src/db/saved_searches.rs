@@ -0,0 +1,367 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub created_at: i64,
+    pub name: String,
+    pub query: String,
+    pub last_run_at: Option<i64>,
+}
+
+/// A single highlighted line matched by a saved search.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SavedMatch {
+    pub repo_ref: String,
+    pub relative_path: String,
+    /// Zero-based line number of the match.
+    pub line: usize,
+    /// The text of the matched line, with surrounding whitespace trimmed.
+    pub text: String,
+}
+
+impl SavedMatch {
+    /// Matches are compared without their line number, so that edits elsewhere in a file don't
+    /// show up as changes.
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.repo_ref, &self.relative_path, &self.text)
+    }
+}
+
+/// Matches that appeared or disappeared between two runs of a saved search.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SavedSearchDiff {
+    pub added: Vec<SavedMatch>,
+    pub removed: Vec<SavedMatch>,
+}
+
+impl SavedSearchDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// How the matches of a run line up with those of the previous run, by index in each list.
+#[derive(Debug, PartialEq, Eq)]
+struct Pairing {
+    /// Matches found by both runs, which may have moved to another line
+    kept: Vec<(usize, usize)>,
+    removed: Vec<usize>,
+    added: Vec<usize>,
+}
+
+impl Pairing {
+    /// Pair up the `old` and `new` matches of the same line, in order, counting repeated lines
+    /// separately.
+    fn new(old: &[SavedMatch], new: &[SavedMatch]) -> Self {
+        let mut unpaired = HashMap::<_, VecDeque<usize>>::new();
+        for (i, m) in old.iter().enumerate() {
+            unpaired.entry(m.key()).or_default().push_back(i);
+        }
+
+        let mut kept = vec![];
+        let mut added = vec![];
+        for (j, m) in new.iter().enumerate() {
+            match unpaired.get_mut(&m.key()).and_then(VecDeque::pop_front) {
+                Some(i) => kept.push((i, j)),
+                None => added.push(j),
+            }
+        }
+
+        let mut removed = unpaired.into_values().flatten().collect::<Vec<_>>();
+        removed.sort();
+
+        Self {
+            kept,
+            removed,
+            added,
+        }
+    }
+
+    fn diff(&self, old: &[SavedMatch], new: &[SavedMatch]) -> SavedSearchDiff {
+        SavedSearchDiff {
+            added: self.added.iter().map(|&j| new[j].clone()).collect(),
+            removed: self.removed.iter().map(|&i| old[i].clone()).collect(),
+        }
+    }
+}
+
+pub struct SavedSearches<'a> {
+    db: &'a super::SqlitePool,
+}
+
+impl<'a> SavedSearches<'a> {
+    pub fn new(db: &'a super::SqlitePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<SavedSearch>> {
+        Ok(sqlx::query_as! {
+            SavedSearch,
+            "SELECT id, created_at, name, query, last_run_at \
+             FROM saved_searches \
+             ORDER BY created_at DESC",
+        }
+        .fetch_all(self.db)
+        .await?)
+    }
+
+    pub async fn get(&self, id: i64) -> anyhow::Result<Option<SavedSearch>> {
+        Ok(sqlx::query_as! {
+            SavedSearch,
+            "SELECT id, created_at, name, query, last_run_at \
+             FROM saved_searches \
+             WHERE id = ?",
+            id,
+        }
+        .fetch_optional(self.db)
+        .await?)
+    }
+
+    /// Save a new search, returning its ID.
+    pub async fn insert(&self, name: &str, query: &str) -> anyhow::Result<i64> {
+        let result = sqlx::query! {
+            "INSERT INTO saved_searches (name, query, last_diff, created_at) \
+             VALUES (?, ?, '{}', strftime('%s', 'now'))",
+            name,
+            query,
+        }
+        .execute(self.db)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Change a saved search, forgetting previous matches. Returns `false` if it doesn't exist.
+    pub async fn update(&self, id: i64, name: &str, query: &str) -> anyhow::Result<bool> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query! {
+            "UPDATE saved_searches \
+             SET name = ?, query = ?, last_diff = '{}', last_run_at = NULL \
+             WHERE id = ?",
+            name,
+            query,
+            id,
+        }
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM saved_search_matches WHERE saved_search_id = ?",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns `false` if the saved search doesn't exist.
+    pub async fn delete(&self, id: i64) -> anyhow::Result<bool> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query!("DELETE FROM saved_searches WHERE id = ?", id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM saved_search_matches WHERE saved_search_id = ?",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Changes in matches found by the latest run of a saved search that changed anything.
+    pub async fn last_diff(&self, id: i64) -> anyhow::Result<Option<SavedSearchDiff>> {
+        let row = sqlx::query!("SELECT last_diff FROM saved_searches WHERE id = ?", id)
+            .fetch_optional(self.db)
+            .await?;
+
+        row.map(|r| serde_json::from_str(&r.last_diff))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Store the matches found by a run of a saved search, in `repo_ref` or in every repository,
+    /// and return how they changed since the previous run.
+    ///
+    /// Matches found again keep their row, with the line they are now on. Runs that find no
+    /// changes only update the time of the last run, so that the last diff stays available until
+    /// there is a new one.
+    pub async fn record_run(
+        &self,
+        id: i64,
+        repo_ref: Option<&str>,
+        matches: &[SavedMatch],
+    ) -> anyhow::Result<SavedSearchDiff> {
+        let mut tx = self.db.begin().await?;
+
+        let rows = sqlx::query! {
+            "SELECT rowid, repo_ref, relative_path, line, text \
+             FROM saved_search_matches \
+             WHERE saved_search_id = ? AND (? IS NULL OR repo_ref = ?) \
+             ORDER BY repo_ref, relative_path, line",
+            id,
+            repo_ref,
+            repo_ref,
+        }
+        .fetch_all(&mut tx)
+        .await?;
+
+        let (rowids, previous): (Vec<_>, Vec<_>) = rows
+            .into_iter()
+            .map(|row| {
+                let m = SavedMatch {
+                    repo_ref: row.repo_ref,
+                    relative_path: row.relative_path,
+                    line: row.line as usize,
+                    text: row.text,
+                };
+
+                (row.rowid, m)
+            })
+            .unzip();
+
+        let pairing = Pairing::new(&previous, matches);
+
+        for &i in &pairing.removed {
+            sqlx::query!(
+                "DELETE FROM saved_search_matches WHERE rowid = ?",
+                rowids[i]
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for &(i, j) in &pairing.kept {
+            if previous[i].line == matches[j].line {
+                continue;
+            }
+
+            let line = matches[j].line as i64;
+            sqlx::query!(
+                "UPDATE saved_search_matches SET line = ? WHERE rowid = ?",
+                line,
+                rowids[i],
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for &j in &pairing.added {
+            let m = &matches[j];
+            let line = m.line as i64;
+            sqlx::query! {
+                "INSERT INTO saved_search_matches \
+                 (saved_search_id, repo_ref, relative_path, line, text) \
+                 VALUES (?, ?, ?, ?, ?)",
+                id,
+                m.repo_ref,
+                m.relative_path,
+                line,
+                m.text,
+            }
+            .execute(&mut tx)
+            .await?;
+        }
+
+        let diff = pairing.diff(&previous, matches);
+        let last_diff = if diff.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&diff)?)
+        };
+
+        sqlx::query! {
+            "UPDATE saved_searches \
+             SET last_diff = COALESCE(?, last_diff), last_run_at = strftime('%s', 'now') \
+             WHERE id = ?",
+            last_diff,
+            id,
+        }
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn diff(old: &[SavedMatch], new: &[SavedMatch]) -> SavedSearchDiff {
+        Pairing::new(old, new).diff(old, new)
+    }
+
+    fn m(relative_path: &str, line: usize, text: &str) -> SavedMatch {
+        SavedMatch {
+            repo_ref: "github.com/bloopai/bloop".into(),
+            relative_path: relative_path.into(),
+            line,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn diff_ignores_moved_lines() {
+        let old = [
+            m("a.rs", 1, "foo()"),
+            m("a.rs", 5, "foo()"),
+            m("b.rs", 2, "foo()"),
+        ];
+        let new = [
+            m("a.rs", 3, "foo()"),
+            m("a.rs", 7, "foo()"),
+            m("c.rs", 1, "foo()"),
+        ];
+
+        assert_eq!(
+            diff(&old, &new),
+            SavedSearchDiff {
+                added: vec![m("c.rs", 1, "foo()")],
+                removed: vec![m("b.rs", 2, "foo()")],
+            }
+        );
+    }
+
+    #[test]
+    fn diff_counts_repeated_lines() {
+        let old = [m("a.rs", 1, "foo()")];
+        let new = [m("a.rs", 1, "foo()"), m("a.rs", 2, "foo()")];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.added, vec![m("a.rs", 2, "foo()")]);
+        assert!(changes.removed.is_empty());
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn moved_lines_are_kept() {
+        let old = [m("a.rs", 1, "foo()"), m("b.rs", 2, "foo()")];
+        let new = [m("a.rs", 3, "foo()"), m("c.rs", 1, "foo()")];
+
+        assert_eq!(
+            Pairing::new(&old, &new),
+            Pairing {
+                kept: vec![(0, 0)],
+                removed: vec![1],
+                added: vec![1],
+            }
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    intelligence::{NodeKind, ScopeGraph},
+    text_range::TextRange,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteKind {
+    Definition,
+    Import,
+    /// A reference to a top-level definition or import of the same file
+    Reference,
+}
+
+impl SiteKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Import => "import",
+            Self::Reference => "reference",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "definition" => Some(Self::Definition),
+            "import" => Some(Self::Import),
+            "reference" => Some(Self::Reference),
+            _ => None,
+        }
+    }
+}
+
+/// An occurrence of a top-level symbol of a file. References are listed under the name of the
+/// definition or import they resolve to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolSite {
+    pub name: String,
+    pub kind: SiteKind,
+    pub range: TextRange,
+}
+
+/// The sites of the top-level symbols in a file, along with the references to them.
+pub fn symbol_sites(scope_graph: &ScopeGraph, src: &[u8]) -> Vec<SymbolSite> {
+    let mut sites = vec![];
+
+    for idx in scope_graph.graph.node_indices() {
+        if !scope_graph.is_top_level(idx) {
+            continue;
+        }
+
+        let (name, kind) = match scope_graph.get_node(idx) {
+            Some(NodeKind::Def(def)) => (def.name(src), SiteKind::Definition),
+            Some(NodeKind::Import(import)) => (import.name(src), SiteKind::Import),
+            _ => continue,
+        };
+
+        let name = String::from_utf8_lossy(name).into_owned();
+        sites.extend(scope_graph.references(idx).map(|ref_idx| SymbolSite {
+            name: name.clone(),
+            kind: SiteKind::Reference,
+            range: scope_graph.graph[ref_idx].range(),
+        }));
+
+        sites.push(SymbolSite {
+            name,
+            kind,
+            range: scope_graph.graph[idx].range(),
+        });
+    }
+
+    sites
+}
+
+/// The symbol sites of a file, as it was indexed.
+#[derive(Debug)]
+pub struct FileSites {
+    /// Cache key of the file's document in the index
+    pub cache_key: String,
+    pub relative_path: String,
+    pub lang: String,
+    /// Newline separated list of branches the file is in
+    pub branches: String,
+    pub sites: Vec<SymbolSite>,
+}
+
+/// A site of a symbol found by name.
+#[derive(Debug)]
+pub struct SiteMatch {
+    pub cache_key: String,
+    pub relative_path: String,
+    pub kind: SiteKind,
+    pub range: TextRange,
+}
+
+/// Top-level symbols of every indexed file, by name.
+///
+/// This lets repo-wide code navigation look up the files that define or refer to a name, rather
+/// than scanning the scope graph of every file in a repo. Rows belong to the cache key of a
+/// file's document, and are replaced along with it.
+pub struct SymbolTable<'a> {
+    db: &'a super::SqlitePool,
+}
+
+impl<'a> SymbolTable<'a> {
+    pub fn new(db: &'a super::SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Remove the sites of the documents with the `removed` cache keys, and store the sites of
+    /// newly indexed documents.
+    pub async fn update(
+        &self,
+        repo_ref: &str,
+        removed: &[String],
+        added: Vec<FileSites>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        let added_keys = added.iter().map(|file| &file.cache_key);
+        for cache_key in removed.iter().chain(added_keys) {
+            sqlx::query!(
+                "DELETE FROM symbol_table WHERE repo_ref = ? AND cache_key = ?",
+                repo_ref,
+                cache_key,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for file in added {
+            for site in file.sites {
+                let kind = site.kind.as_str();
+                let range = serde_json::to_string(&site.range)?;
+
+                sqlx::query!(
+                    "INSERT INTO symbol_table \
+                     (repo_ref, cache_key, relative_path, lang, branches, name, kind, text_range) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                    repo_ref,
+                    file.cache_key,
+                    file.relative_path,
+                    file.lang,
+                    file.branches,
+                    site.name,
+                    kind,
+                    range,
+                )
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Find the sites of symbols called `name` in files of one of `langs`, optionally in a
+    /// `branch`.
+    pub async fn lookup(
+        &self,
+        repo_ref: &str,
+        name: &str,
+        langs: &[&str],
+        branch: Option<&str>,
+    ) -> anyhow::Result<Vec<SiteMatch>> {
+        let rows = sqlx::query!(
+            "SELECT cache_key, relative_path, lang, branches, kind, text_range \
+             FROM symbol_table WHERE repo_ref = ? AND name = ?",
+            repo_ref,
+            name,
+        )
+        .fetch_all(self.db)
+        .await?;
+
+        let mut matches = vec![];
+        for row in rows {
+            let in_lang = langs.iter().any(|l| l.eq_ignore_ascii_case(&row.lang));
+            let in_branch = branch
+                .map(|branch| row.branches.lines().any(|b| b == branch))
+                .unwrap_or(true);
+
+            let Some(kind) = SiteKind::parse(&row.kind) else {
+                continue;
+            };
+
+            if in_lang && in_branch {
+                matches.push(SiteMatch {
+                    cache_key: row.cache_key,
+                    relative_path: row.relative_path,
+                    kind,
+                    range: serde_json::from_str(&row.text_range)?,
+                });
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Remove the sites of every file in a repo, such as when the repo is deleted.
+    pub async fn delete_repo(&self, repo_ref: &str) -> anyhow::Result<()> {
+        sqlx::query!("DELETE FROM symbol_table WHERE repo_ref = ?", repo_ref)
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -19,6 +19,14 @@ pub(crate) enum Feature {
     /// Use GitHub App permission system scoped to a single
     /// installation. Cloud instances use this.
     GithubInstallation = 1 << 4,
+
+    /// Allow logging in to GitLab and Gitea with user-supplied access tokens. Like
+    /// `GithubDeviceFlow`, this is meant for installations that manage their own remotes.
+    ProviderLogin = 1 << 5,
+
+    /// Allow cloning repositories from any git URL. Hosts on private or link-local networks are
+    /// only allowed along with `AnyPathScan`.
+    GitRemotes = 1 << 6,
 }
 
 #[rustfmt::skip]
@@ -33,6 +41,8 @@ enum EnvironmentInner {
     /// Safe API that's suitable for public use
     Server =
 	GithubDeviceFlow as u64
+	| ProviderLogin as u64
+	| GitRemotes as u64
 	| SafePathScan as u64,
 
     /// Use a GitHub App installation to manage repositories and user access.
@@ -59,7 +69,9 @@ enum EnvironmentInner {
     /// Enables scanning arbitrary user-specified locations through a Web-endpoint.
     InsecureLocal =
 	AnyPathScan as u64
-	| GithubDeviceFlow as u64,
+	| GithubDeviceFlow as u64
+	| ProviderLogin as u64
+	| GitRemotes as u64,
 }
 
 #[derive(Debug, Clone)]
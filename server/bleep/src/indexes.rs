@@ -5,17 +5,20 @@ use async_trait::async_trait;
 use smallvec::SmallVec;
 use tantivy::{
     collector::{Collector, MultiFruit},
+    query::EnableScoring,
     schema::Schema,
     tokenizer::NgramTokenizer,
-    DocAddress, Document, IndexReader, IndexWriter, Score,
+    DocAddress, Document, IndexReader, IndexWriter, Searcher, SegmentOrdinal,
 };
 use tokio::sync::RwLock;
 
+pub mod commit;
 pub mod file;
 pub mod reader;
 pub mod repo;
 mod schema;
 
+pub use commit::Commit;
 pub use file::File;
 pub use repo::Repo;
 use tracing::debug;
@@ -68,7 +71,7 @@ impl<'a> GlobalWriteHandle<'a> {
         sync_handle: &SyncHandle,
         repo: &Repository,
     ) -> Result<Arc<RepoMetadata>, RepoError> {
-        let metadata = repo.get_repo_metadata().await;
+        let metadata = repo.get_repo_metadata(&sync_handle.reporef).await;
 
         futures::future::join_all(self.handles.iter().map(|handle| {
             handle.index(&sync_handle.reporef, repo, &metadata, sync_handle.pipes())
@@ -84,6 +87,7 @@ impl<'a> GlobalWriteHandle<'a> {
 pub struct Indexes {
     pub repo: Indexer<Repo>,
     pub file: Indexer<File>,
+    pub commit: Indexer<Commit>,
     write_mutex: tokio::sync::Mutex<()>,
 }
 
@@ -100,11 +104,19 @@ impl Indexes {
             std::fs::remove_dir_all(config.index_path("repo"))?;
             std::fs::remove_dir_all(config.index_path("content"))?;
 
+            // added after the other indexes, so older installs may not have it
+            let commit_path = config.index_path("commit");
+            if commit_path.as_ref().exists() {
+                std::fs::remove_dir_all(commit_path)?;
+            }
+
             let mut refs = vec![];
             // knocking out our current file caches will force re-indexing qdrant
             repo_pool.for_each(|reporef, repo| {
                 refs.push(reporef.to_owned());
                 repo.last_index_unix_secs = 0;
+                repo.last_indexed_commit = None;
+                repo.last_history_commit = None;
             });
 
             for reporef in refs {
@@ -126,6 +138,12 @@ impl Indexes {
                 config.buffer_size,
                 config.max_threads,
             )?,
+            commit: Indexer::create(
+                Commit::new(),
+                config.index_path("commit").as_ref(),
+                config.repo_buffer_size,
+                config.max_threads,
+            )?,
             write_mutex: Default::default(),
         })
     }
@@ -137,7 +155,11 @@ impl Indexes {
         debug!(id, "lock acquired");
 
         Ok(GlobalWriteHandle {
-            handles: vec![self.repo.write_handle()?, self.file.write_handle()?],
+            handles: vec![
+                self.repo.write_handle()?,
+                self.file.write_handle()?,
+                self.commit.write_handle()?,
+            ],
             _write_lock,
         })
     }
@@ -278,27 +300,42 @@ impl<T: Indexable> Indexer<T> {
         Ok(instance)
     }
 
-    pub async fn query<'a, R, I, C>(
+    /// The index, as currently visible to searches.
+    pub async fn searcher(&self) -> Searcher {
+        self.reader.read().await.searcher()
+    }
+
+    /// Search the index, as seen by `scope` if specified, or as currently visible otherwise.
+    pub async fn query<'a, R, I, C, S>(
         &'a self,
         queries: I,
         doc_reader: &'a R,
         collector: C,
+        scope: Option<&SearchScope>,
     ) -> Result<SearchResults<'_, R::Document>>
     where
         I: Iterator<Item = &'a Query<'a>> + Send,
-        C: Collector<Fruit = (Vec<(Score, DocAddress)>, MultiFruit)>,
+        C: Collector<Fruit = (Vec<(S, DocAddress)>, MultiFruit)>,
         R: DocumentRead<Schema = T>,
+        S: Send + 'static,
     {
-        let searcher = self.reader.read().await.searcher();
+        let (searcher, segment) = match scope {
+            Some(scope) => (scope.searcher.clone(), scope.segment),
+            None => (self.searcher().await, None),
+        };
         let queries = queries
             .filter(|q| doc_reader.query_matches(q))
             .collect::<SmallVec<[_; 2]>>();
         let compiled_query =
             doc_reader.compile(&self.source, queries.iter().copied(), &self.index)?;
 
-        let (top_k, metadata) = searcher
-            .search(&compiled_query, &collector)
-            .context("failed to execute search query")?;
+        let (top_k, metadata) = match segment {
+            Some(segment) => {
+                search_segment(&searcher, compiled_query.as_ref(), &collector, segment)
+            }
+            None => searcher.search(&compiled_query, &collector),
+        }
+        .context("failed to execute search query")?;
 
         let iter = top_k.into_iter().map(move |(_score, addr)| {
             let doc = searcher.doc(addr).unwrap();
@@ -312,6 +349,50 @@ impl<T: Indexable> Indexer<T> {
     }
 }
 
+/// A view of an index shared by several searches, such as those of a stream of results, along
+/// with the segment that a search covers, if it covers only one.
+///
+/// Segment ordinals are only meaningful within the searcher they were taken from.
+#[derive(Clone)]
+pub struct SearchScope {
+    pub searcher: Searcher,
+    pub segment: Option<SegmentOrdinal>,
+}
+
+impl std::fmt::Debug for SearchScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchScope")
+            .field("segment", &self.segment)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Run a search over a single segment of the index.
+///
+/// Collecting over a segment that doesn't exist yields an empty result.
+fn search_segment<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn tantivy::query::Query,
+    collector: &C,
+    segment: SegmentOrdinal,
+) -> tantivy::Result<C::Fruit> {
+    let scoring = if collector.requires_scoring() {
+        EnableScoring::Enabled(searcher)
+    } else {
+        EnableScoring::Disabled(searcher.schema())
+    };
+
+    let weight = query.weight(scoring)?;
+    let fruits = searcher
+        .segment_readers()
+        .get(segment as usize)
+        .map(|reader| collector.collect_segment(weight.as_ref(), segment, reader))
+        .into_iter()
+        .collect::<tantivy::Result<Vec<_>>>()?;
+
+    collector.merge_fruits(fruits)
+}
+
 pub struct SearchResults<'a, T> {
     pub docs: Box<dyn Iterator<Item = T> + Sync + Send + 'a>,
     pub metadata: MultiFruit,
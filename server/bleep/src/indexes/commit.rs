@@ -0,0 +1,98 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use tantivy::{schema::Schema, Document, IndexWriter, Term};
+use tracing::info;
+
+pub use super::schema::Commit;
+use super::Indexable;
+use crate::{
+    background::SyncPipes,
+    repo::{iterator::commit_history, RepoMetadata, RepoRef, Repository},
+};
+
+/// The most commits indexed for a single repository in one sync, newest first.
+const MAX_COMMITS: usize = 50_000;
+
+impl Default for Commit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Indexable for Commit {
+    async fn index_repository(
+        &self,
+        repo_ref: &RepoRef,
+        repo: &Repository,
+        metadata: &RepoMetadata,
+        writer: &IndexWriter,
+        pipes: &SyncPipes,
+    ) -> Result<()> {
+        // Not a git repository
+        let Some(head) = &metadata.head_commit else {
+            self.delete_by_repo(writer, repo);
+            return Ok(());
+        };
+
+        if repo.last_history_commit.as_ref() == Some(head) {
+            info!(?repo.disk_path, "commit history unchanged");
+            return Ok(());
+        }
+
+        let since = repo.last_history_commit.as_deref();
+        let history = commit_history(&repo.disk_path, head, since, MAX_COMMITS);
+
+        // Unless only new commits were walked, the history is indexed whole, so drop what we had
+        // for this repository.
+        if !history.incremental {
+            self.delete_by_repo(writer, repo);
+        }
+
+        let repo_name = repo_ref.indexed_name();
+        let count = history.commits.len();
+
+        for commit in history.commits {
+            if pipes.is_cancelled() {
+                bail!("cancelled");
+            }
+
+            let mut doc = Document::new();
+            doc.add_text(self.repo_disk_path, repo.disk_path.to_string_lossy());
+            doc.add_text(self.repo_ref, repo_ref.to_string());
+            doc.add_text(self.repo_name, &repo_name);
+            doc.add_bytes(self.raw_repo_name, repo_name.as_bytes());
+            doc.add_text(self.commit_id, commit.id);
+            doc.add_bytes(self.raw_message, commit.message.as_bytes());
+            doc.add_text(self.message, commit.message);
+            doc.add_text(self.author, commit.author);
+            doc.add_u64(self.unix_seconds, commit.unix_secs);
+            doc.add_bytes(self.raw_paths, commit.paths.join("\n").as_bytes());
+            for path in commit.paths {
+                doc.add_text(self.paths, path);
+            }
+
+            writer.add_document(doc)?;
+        }
+
+        info!(
+            ?repo.disk_path,
+            count,
+            history.incremental,
+            "finished indexing commit history"
+        );
+
+        Ok(())
+    }
+
+    fn delete_by_repo(&self, writer: &IndexWriter, repo: &Repository) {
+        writer.delete_term(Term::from_field_text(
+            self.repo_disk_path,
+            &repo.disk_path.to_string_lossy(),
+        ));
+    }
+
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+}
@@ -3,19 +3,20 @@ use std::{
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
 use anyhow::{bail, Result};
 use async_trait::async_trait;
-use rayon::prelude::*;
+use gix::diff::blob::{self, intern::InternedInput, Algorithm, UnifiedDiffBuilder};
 use scc::hash_map::Entry;
+use serde::Serialize;
 use tantivy::{
-    collector::TopDocs,
+    collector::{DocSetCollector, TopDocs},
     doc,
-    query::{BooleanQuery, Query, QueryParser, TermQuery},
-    schema::{IndexRecordOption, Schema, Term},
+    query::{AllQuery, BooleanQuery, Query, QueryParser, TermQuery},
+    schema::{IndexRecordOption, Schema, Term, Value},
     IndexWriter,
 };
 use tokenizers as _;
@@ -34,10 +35,15 @@ use super::{
 use crate::{
     background::SyncPipes,
     cache::{FileCache, FileCacheSnapshot},
+    collector::BytesFilterCollector,
+    db::{self, FileSites, SymbolTable},
     intelligence::TreeSitterFile,
-    query::compiler::{case_permutations, trigrams},
+    query::{
+        compiler::{case_permutations, trigrams},
+        ranking::SymbolScorer,
+    },
     repo::{iterator::*, RepoMetadata, RepoRef, Repository},
-    symbol::SymbolLocations,
+    symbol::{FuzzySymbolMatcher, SymbolLocations, SymbolMatch},
 };
 
 struct Workload<'a> {
@@ -47,6 +53,7 @@ struct Workload<'a> {
     repo_metadata: &'a RepoMetadata,
     file_cache: &'a FileCache<'a>,
     cache_snapshot: &'a FileCacheSnapshot,
+    symbol_sites: &'a Mutex<Vec<FileSites>>,
     dir_entry: RepoDirEntry,
 }
 
@@ -64,6 +71,7 @@ impl Indexable for File {
         let cache_snapshot = file_cache.retrieve().await;
         let repo_name = reporef.indexed_name();
         let processed = &AtomicU64::new(0);
+        let symbol_sites = &Mutex::new(vec![]);
 
         let file_worker = |count: usize| {
             let cache_snapshot = cache_snapshot.clone();
@@ -79,6 +87,7 @@ impl Indexable for File {
                     repo_name: &repo_name,
                     file_cache: &file_cache,
                     cache_snapshot: &cache_snapshot,
+                    symbol_sites,
                     repo_metadata,
                     dir_entry,
                 };
@@ -91,19 +100,52 @@ impl Indexable for File {
         };
 
         let start = std::time::Instant::now();
+        let files = FileFilter::new(&repo.disk_path, &repo.index_settings)?;
+
+        // Only walk the paths that changed since the last index, if the
+        // cache still holds the entries of that index
+        let changes = match (&repo.last_indexed_commit, &repo_metadata.head) {
+            (Some(since), Some(head)) if !cache_snapshot.is_empty() => {
+                GitWalker::open_changes(&repo.disk_path, since, head, &files).unwrap_or_else(
+                    |err| {
+                        warn!(%err, "failed to diff against the last index; walking all files");
+                        None
+                    },
+                )
+            }
+            _ => None,
+        };
+
+        // Otherwise, if we could determine the time of the last commit,
+        // proceed with a Git Walker, or else use a FS walker
+        if let Some((walker, outdated)) = changes {
+            let outdated = self.cache_keys_at(writer, reporef, outdated)?;
+            info!(
+                ?repo.disk_path,
+                changed = walker.len(),
+                "reindexing paths changed since the last index"
+            );
+
+            // entries of unchanged paths are kept, the rest are removed below unless
+            // they're walked again
+            cache_snapshot.retain(|k, v| {
+                v.fresh = !outdated.contains(k);
+                true
+            });
 
-        // If we could determine the time of the last commit, proceed
-        // with a Git Walker, otherwise use a FS walker
-        if repo_metadata.last_commit_unix_secs.is_some() {
+            let count = walker.len();
+            walker.for_each(pipes, file_worker(count));
+        } else if repo_metadata.last_commit_unix_secs.is_some() {
             let walker = GitWalker::open_repository(
                 reporef,
                 &repo.disk_path,
                 repo.branch_filter.as_ref().map(Into::into),
+                files,
             )?;
             let count = walker.len();
             walker.for_each(pipes, file_worker(count));
         } else {
-            let walker = FileWalker::index_directory(&repo.disk_path);
+            let walker = FileWalker::index_directory(&repo.disk_path, files);
             let count = walker.len();
             walker.for_each(pipes, file_worker(count));
         };
@@ -126,6 +168,14 @@ impl Indexable for File {
             v.fresh
         });
 
+        SymbolTable::new(&self.sql)
+            .update(
+                &reporef.to_string(),
+                &qdrant_remove_list,
+                std::mem::take(&mut *symbol_sites.lock().unwrap()),
+            )
+            .await?;
+
         // batch-delete points from qdrant index
         if !qdrant_remove_list.is_empty() {
             if let Some(semantic) = &self.semantic {
@@ -271,6 +321,107 @@ impl Indexer<File> {
             .take(limit)
     }
 
+    /// Find symbol definitions across all files, or those of a single repository, whose names
+    /// fuzzily match `query_str`. Results are ordered from best to worst match.
+    ///
+    /// Files with symbols that contain the query verbatim are found through the trigram index, and
+    /// scored first. Only when they have fewer than `limit` matches are the symbols of every file
+    /// scored, to find abbreviations and typos.
+    pub async fn fuzzy_symbol_match(
+        &self,
+        query_str: &str,
+        repo_ref: Option<&RepoRef>,
+        limit: usize,
+    ) -> Result<Vec<SymbolMatch>> {
+        let reader = self.reader.read().await;
+        let searcher = reader.searcher();
+        let matcher = Arc::new(FuzzySymbolMatcher::new(query_str));
+
+        let scope: Box<dyn Query> = match repo_ref {
+            Some(repo_ref) => Box::new(TermQuery::new(
+                Term::from_field_text(self.source.repo_ref, &repo_ref.to_string()),
+                IndexRecordOption::Basic,
+            )),
+            None => Box::new(AllQuery),
+        };
+
+        let verbatim = trigrams(query_str)
+            .map(|token| {
+                let terms = case_permutations(&token)
+                    .map(|token| Term::from_field_text(self.source.symbols, &token))
+                    .map(|term| TermQuery::new(term, IndexRecordOption::Basic))
+                    .map(|q| Box::new(q) as Box<dyn Query>)
+                    .collect();
+
+                Box::new(BooleanQuery::union(terms)) as Box<dyn Query>
+            })
+            .chain(std::iter::once(scope.box_clone()))
+            .collect::<Vec<_>>();
+
+        let matches = self.score_symbols(
+            &searcher,
+            &BooleanQuery::intersection(verbatim),
+            &matcher,
+            limit,
+        )?;
+
+        if matches.len() >= limit {
+            return Ok(matches);
+        }
+
+        self.score_symbols(&searcher, &*scope, &matcher, limit)
+    }
+
+    /// The best `limit` symbols of the files matched by `query`.
+    fn score_symbols(
+        &self,
+        searcher: &tantivy::Searcher,
+        query: &dyn Query,
+        matcher: &Arc<FuzzySymbolMatcher>,
+        limit: usize,
+    ) -> Result<Vec<SymbolMatch>> {
+        // a file is scored by its best matching symbol, so the best `limit` symbols are always
+        // found within the best `limit` files
+        let collector = TopDocs::with_limit(limit).custom_score(SymbolScorer {
+            schema: self.source.clone(),
+            matcher: Arc::clone(matcher),
+        });
+
+        let mut matches = vec![];
+        for (score, addr) in searcher.search(query, &collector)? {
+            // files without a single matching symbol are scored 0, and come last
+            if score == 0 {
+                break;
+            }
+
+            let doc = ContentReader.read_document(&self.source, searcher.doc(addr)?);
+            for symbol in doc.symbol_locations.list() {
+                let range = symbol.range.start.byte..symbol.range.end.byte;
+                let Some(name) = doc.content.get(range) else {
+                    continue;
+                };
+
+                if let Some(score) = matcher.score(name) {
+                    matches.push(SymbolMatch {
+                        name: name.to_owned(),
+                        kind: symbol.kind,
+                        range: symbol.range,
+                        repo_name: doc.repo_name.clone(),
+                        repo_ref: doc.repo_ref.clone(),
+                        relative_path: doc.relative_path.clone(),
+                        lang: doc.lang.clone(),
+                        score,
+                    });
+                }
+            }
+        }
+
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        matches.truncate(limit);
+
+        Ok(matches)
+    }
+
     pub async fn by_path(
         &self,
         repo_ref: &RepoRef,
@@ -339,74 +490,271 @@ impl Indexer<File> {
         }
     }
 
-    // Produce all files in a repo
-    //
-    // TODO: Look at this again when:
-    //  - directory retrieval is ready
-    //  - unified referencing is ready
-    pub async fn by_repo<S: AsRef<str>>(
+    /// Find the documents of a repo with the given cache keys, by their key.
+    pub async fn by_cache_keys(
         &self,
         repo_ref: &RepoRef,
-        langs: impl Iterator<Item = S>,
-        branch: Option<&str>,
-    ) -> Vec<ContentDocument> {
+        keys: impl Iterator<Item = &str>,
+    ) -> Result<HashMap<String, ContentDocument>> {
         let reader = self.reader.read().await;
         let searcher = reader.searcher();
 
-        let mut query = vec![];
+        let keys = keys
+            .map(|key| {
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.source.unique_hash, key),
+                    IndexRecordOption::Basic,
+                )) as Box<dyn Query>
+            })
+            .collect::<Vec<_>>();
 
-        // repo query
-        query.push(Box::new(TermQuery::new(
-            Term::from_field_text(self.source.repo_ref, &repo_ref.to_string()),
-            IndexRecordOption::Basic,
-        )) as Box<dyn Query>);
+        let query = BooleanQuery::intersection(vec![
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.source.repo_ref, &repo_ref.to_string()),
+                IndexRecordOption::Basic,
+            )),
+            Box::new(BooleanQuery::union(keys)),
+        ]);
+
+        let mut docs = HashMap::new();
+        for addr in searcher.search(&query, &DocSetCollector)? {
+            let doc = searcher.doc(addr)?;
+            let key = doc
+                .get_first(self.source.unique_hash)
+                .and_then(Value::as_text)
+                .map(ToOwned::to_owned);
+
+            if let Some(key) = key {
+                docs.insert(key, ContentReader.read_document(&self.source, doc));
+            }
+        }
 
-        let branch_term = branch
-            .map(|b| {
-                trigrams(b)
-                    .map(|token| Term::from_field_text(self.source.branches, token.as_str()))
-                    .map(|term| TermQuery::new(term, IndexRecordOption::Basic))
-                    .map(Box::new)
-                    .map(|q| q as Box<dyn Query>)
-                    .collect::<Vec<_>>()
-            })
-            .map(BooleanQuery::intersection);
-        if let Some(b) = branch_term {
-            query.push(Box::new(b) as Box<dyn Query>);
-        };
+        Ok(docs)
+    }
 
-        query.push({
-            let queries = langs
-                .map(|lang| {
-                    Box::new(TermQuery::new(
-                        Term::from_field_bytes(
-                            self.source.lang,
-                            lang.as_ref().to_ascii_lowercase().as_bytes(),
-                        ),
-                        IndexRecordOption::Basic,
-                    )) as Box<dyn Query>
-                })
-                .collect::<Vec<_>>();
-            Box::new(BooleanQuery::union(queries))
-        });
+    /// Compare the files of two branches of a repo, as they were indexed.
+    ///
+    /// Branches of remote repos are indexed by their name on `origin`, which may be left out. An
+    /// error wrapping [`BranchNotIndexed`] is returned if either branch has no indexed files.
+    pub async fn diff_branches(
+        &self,
+        repo_ref: &RepoRef,
+        base: &str,
+        head: &str,
+    ) -> Result<BranchDiff> {
+        let base = indexed_branch_name(repo_ref, base);
+        let head = indexed_branch_name(repo_ref, head);
 
-        let query = BooleanQuery::intersection(query);
-        let collector = TopDocs::with_limit(500);
-        searcher
-            .search(&query, &collector)
-            .expect("failed to search index")
-            .into_par_iter()
-            .map(|(_, doc_addr)| {
-                let retrieved_doc = searcher
-                    .doc(doc_addr)
-                    .expect("failed to get document by address");
-                ContentReader.read_document(&self.source, retrieved_doc)
+        let reader = self.reader.read().await;
+        let searcher = reader.searcher();
+
+        // trigrams only narrow down the candidates, branch names are compared exactly below
+        let in_branch = |branch: &str| {
+            Box::new(BooleanQuery::intersection(
+                trigrams(branch)
+                    .map(|token| {
+                        Box::new(TermQuery::new(
+                            Term::from_field_text(self.source.branches, token.as_str()),
+                            IndexRecordOption::Basic,
+                        )) as Box<dyn Query>
+                    })
+                    .collect(),
+            )) as Box<dyn Query>
+        };
+
+        let query = BooleanQuery::intersection(vec![
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.source.repo_ref, &repo_ref.to_string()),
+                IndexRecordOption::Basic,
+            )) as Box<dyn Query>,
+            Box::new(BooleanQuery::union(vec![
+                in_branch(&base),
+                in_branch(&head),
+            ])),
+        ]);
+
+        let fast_fields = searcher
+            .segment_readers()
+            .iter()
+            .map(|segment| -> Result<_> {
+                let fast_fields = segment.fast_fields();
+                Ok((
+                    fast_fields.bytes(self.source.raw_relative_path)?,
+                    fast_fields.bytes(self.source.raw_branches)?,
+                ))
             })
-            .collect()
+            .collect::<Result<Vec<_>>>()?;
+
+        // A file that is the same in both branches is a single document listing both, so files
+        // are told apart by document, and only the paths and branches of documents are read
+        // until the modified files are diffed.
+        let mut base_docs = HashMap::new();
+        let mut head_docs = HashMap::new();
+
+        for addr in searcher.search(&query, &DocSetCollector)? {
+            let (paths, branches) = &fast_fields[addr.segment_ord as usize];
+            let Ok(path) = std::str::from_utf8(paths.get_bytes(addr.doc_id)) else {
+                continue;
+            };
+
+            if path.ends_with('/') {
+                continue;
+            }
+
+            for branch in String::from_utf8_lossy(branches.get_bytes(addr.doc_id)).lines() {
+                if branch == base {
+                    base_docs.insert(path.to_owned(), addr);
+                }
+
+                if branch == head {
+                    head_docs.insert(path.to_owned(), addr);
+                }
+            }
+        }
+
+        for (branch, docs) in [(&base, &base_docs), (&head, &head_docs)] {
+            if docs.is_empty() {
+                return Err(BranchNotIndexed(branch.clone()).into());
+            }
+        }
+
+        let mut changes = BranchDiff::default();
+        let mut modified = vec![];
+
+        for (path, old) in base_docs {
+            match head_docs.remove(&path) {
+                None => changes.removed.push(path),
+                Some(new) if new != old => modified.push((path, old, new)),
+                Some(_) => {}
+            }
+        }
+
+        changes.added = head_docs.into_keys().collect();
+        changes.added.sort();
+        changes.removed.sort();
+        modified.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (i, (path, old, new)) in modified.into_iter().enumerate() {
+            let diff = if i < MAX_DIFFED_FILES {
+                let old = ContentReader.read_document(&self.source, searcher.doc(old)?);
+                let new = ContentReader.read_document(&self.source, searcher.doc(new)?);
+                Some(unified_diff(&path, &old.content, &new.content))
+            } else {
+                None
+            };
+
+            let truncated = diff.as_ref().map(|d| d.len() > MAX_DIFF_LEN);
+            changes.modified.push(ModifiedFile {
+                diff: diff.map(truncate_diff),
+                truncated: truncated.unwrap_or_default(),
+                path,
+            });
+        }
+
+        Ok(changes)
     }
 }
 
+/// The most modified files that are diffed when comparing branches. Only the paths of the rest
+/// are listed.
+const MAX_DIFFED_FILES: usize = 100;
+
+/// The longest diff of a single file that is returned when comparing branches, in bytes.
+const MAX_DIFF_LEN: usize = 64 * 1024;
+
+/// The name that `branch` of a repo is indexed by. Remote branches are indexed by their name on
+/// the `origin` remote, but users will usually leave it out.
+fn indexed_branch_name(repo_ref: &RepoRef, branch: &str) -> String {
+    if repo_ref.is_local() || branch.starts_with("origin/") {
+        branch.to_owned()
+    } else {
+        format!("origin/{branch}")
+    }
+}
+
+/// A branch that was compared, but has no files in the index.
+#[derive(thiserror::Error, Debug)]
+#[error("no files are indexed in branch `{0}`")]
+pub struct BranchNotIndexed(pub String);
+
+/// Files that differ between two branches of a repo.
+#[derive(Serialize, Default, Debug)]
+pub struct BranchDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<ModifiedFile>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ModifiedFile {
+    pub path: String,
+    /// Unified diff of the file's contents, or `None` if too many files were modified to diff
+    /// them all
+    pub diff: Option<String>,
+    /// Whether the diff was cut off at `MAX_DIFF_LEN` bytes
+    pub truncated: bool,
+}
+
+/// Cut a diff down to at most `MAX_DIFF_LEN` bytes, at the end of a line.
+fn truncate_diff(mut diff: String) -> String {
+    if diff.len() > MAX_DIFF_LEN {
+        let end = diff.as_bytes()[..MAX_DIFF_LEN]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map(|i| i + 1)
+            .unwrap_or_default();
+        diff.truncate(end);
+    }
+
+    diff
+}
+
+fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    let input = InternedInput::new(before, after);
+    let hunks = blob::diff(
+        Algorithm::Histogram,
+        &input,
+        UnifiedDiffBuilder::new(&input),
+    );
+    format!("--- a/{path}\n+++ b/{path}\n{hunks}")
+}
+
 impl File {
+    /// Cache keys of the documents indexed at `paths` in a repo, relative to its root.
+    fn cache_keys_at(
+        &self,
+        writer: &IndexWriter,
+        reporef: &RepoRef,
+        paths: HashSet<String>,
+    ) -> Result<HashSet<String>> {
+        let searcher = writer.index().reader()?.searcher();
+        let query = TermQuery::new(
+            Term::from_field_text(self.repo_ref, &reporef.to_string()),
+            IndexRecordOption::Basic,
+        );
+
+        let paths = Arc::new(paths);
+        let collector = BytesFilterCollector::new(
+            self.raw_relative_path,
+            move |path| {
+                std::str::from_utf8(path)
+                    .map(|path| is_outdated(&paths, path))
+                    .unwrap_or_default()
+            },
+            DocSetCollector,
+        );
+
+        let mut keys = HashSet::new();
+        for addr in searcher.search(&query, &collector)? {
+            let doc = searcher.doc(addr)?;
+            if let Some(key) = doc.get_first(self.unique_hash).and_then(Value::as_text) {
+                keys.insert(key.to_owned());
+            }
+        }
+
+        Ok(keys)
+    }
+
     #[tracing::instrument(fields(repo=%workload.repo_ref, entry_disk_path=?workload.dir_entry.path()), skip_all)]
     fn worker(&self, workload: Workload<'_>, writer: &IndexWriter) -> Result<()> {
         let Workload {
@@ -416,6 +764,7 @@ impl File {
             repo_metadata,
             file_cache,
             cache_snapshot,
+            symbol_sites,
             dir_entry,
         } = workload;
 
@@ -448,10 +797,22 @@ impl File {
             let mut hash = blake3::Hasher::new();
             hash.update(semantic_hash.as_ref());
             hash.update(branch_list.join("\n").as_bytes());
+
+            // Only per-entry commit times are hashed, as the repo-wide fallback changes with
+            // every new commit.
+            if let Some(last_commit) = dir_entry.last_commit() {
+                hash.update(&last_commit.unix_secs.to_le_bytes());
+                hash.update(last_commit.author.as_bytes());
+            }
+
             hash.finalize().to_hex().to_string()
         };
 
-        let last_commit = repo_metadata.last_commit_unix_secs.unwrap_or(0);
+        let last_commit = dir_entry
+            .last_commit()
+            .map(|c| c.unix_secs)
+            .or(repo_metadata.last_commit_unix_secs)
+            .unwrap_or(0);
 
         match dir_entry {
             _ if is_cache_fresh(cache_snapshot, &tantivy_hash, &entry_pathbuf) => {
@@ -487,6 +848,7 @@ impl File {
                         last_commit,
                         repo_metadata,
                         file_cache,
+                        symbol_sites,
                     )
                     .ok_or(anyhow::anyhow!("failed to build document"))?;
                 writer.add_document(doc)?;
@@ -540,6 +902,7 @@ impl RepoDir {
         let relative_path_str = relative_path_str.replace('\\', "/");
 
         let branches = self.branches.join("\n");
+        let last_commit_author = self.last_commit.map(|c| c.author).unwrap_or_default();
 
         doc!(
                 schema.raw_repo_name => repo_name.as_bytes(),
@@ -549,6 +912,8 @@ impl RepoDir {
                 schema.repo_ref => repo_ref,
                 schema.repo_name => repo_name,
                 schema.last_commit_unix_seconds => last_commit,
+                schema.last_commit_author => last_commit_author,
+                schema.raw_branches => branches.as_bytes(),
                 schema.branches => branches,
                 schema.is_directory => true,
                 schema.unique_hash => tantivy_cache_key,
@@ -561,6 +926,7 @@ impl RepoDir {
                 schema.avg_line_length => f64::default(),
                 schema.symbol_locations => bincode::serialize(&SymbolLocations::default()).unwrap(),
                 schema.symbols => String::default(),
+                schema.raw_symbols => Vec::<u8>::default(),
         )
     }
 }
@@ -580,12 +946,14 @@ impl RepoFile {
         last_commit: u64,
         repo_metadata: &RepoMetadata,
         file_cache: &FileCache,
+        symbol_sites: &Mutex<Vec<FileSites>>,
     ) -> Option<tantivy::schema::Document> {
         let relative_path_str = relative_path.to_string_lossy().to_string();
         #[cfg(windows)]
         let relative_path_str = relative_path_str.replace('\\', "/");
 
         let branches = self.branches.join("\n");
+        let last_commit_author = self.last_commit.map(|c| c.author).unwrap_or_default();
         let lang_str = repo_metadata
             .langs
             .get(entry_pathbuf, self.buffer.as_ref())
@@ -610,9 +978,10 @@ impl RepoFile {
             }
         };
 
+        let symbol_list = symbol_locations.list();
+
         // flatten the list of symbols into a string with just text
-        let symbols = symbol_locations
-            .list()
+        let symbols = symbol_list
             .iter()
             .map(|sym| self.buffer[sym.range.start.byte..sym.range.end.byte].to_owned())
             .collect::<HashSet<_>>()
@@ -620,6 +989,11 @@ impl RepoFile {
             .collect::<Vec<_>>()
             .join("\n");
 
+        let symbol_kinds = symbol_list
+            .into_iter()
+            .map(|sym| sym.kind)
+            .collect::<HashSet<_>>();
+
         // add an NL if this file is not NL-terminated
         if !self.buffer.ends_with('\n') {
             self.buffer += "\n";
@@ -639,6 +1013,16 @@ impl RepoFile {
 
         let lines_avg = self.buffer.len() as f64 / self.buffer.lines().count() as f64;
 
+        if let Some(scope_graph) = symbol_locations.scope_graph() {
+            symbol_sites.lock().unwrap().push(FileSites {
+                cache_key: tantivy_cache_key.clone(),
+                relative_path: relative_path_str.clone(),
+                lang: lang_str.to_ascii_lowercase(),
+                branches: branches.clone(),
+                sites: db::symbol_sites(scope_graph, self.buffer.as_bytes()),
+            });
+        }
+
         if let Some(semantic) = &schema.semantic {
             tokio::task::block_in_place(|| {
                 Handle::current().block_on(async {
@@ -650,6 +1034,7 @@ impl RepoFile {
                             &self.buffer,
                             lang_str,
                             &self.branches,
+                            last_commit,
                             file_cache.chunks_for_file(&semantic_cache_key).await,
                         )
                         .await
@@ -657,7 +1042,7 @@ impl RepoFile {
             });
         }
 
-        Some(doc!(
+        let mut doc = doc!(
             schema.raw_content => self.buffer.as_bytes(),
             schema.raw_repo_name => repo_name.as_bytes(),
             schema.raw_relative_path => relative_path_str.as_bytes(),
@@ -671,11 +1056,20 @@ impl RepoFile {
             schema.lang => lang_str.to_ascii_lowercase().as_bytes(),
             schema.avg_line_length => lines_avg,
             schema.last_commit_unix_seconds => last_commit,
+            schema.last_commit_author => last_commit_author,
             schema.symbol_locations => bincode::serialize(&symbol_locations).unwrap(),
+            schema.raw_symbols => symbols.as_bytes(),
             schema.symbols => symbols,
+            schema.raw_branches => branches.as_bytes(),
             schema.branches => branches,
             schema.is_directory => false,
-        ))
+        );
+
+        for kind in symbol_kinds {
+            doc.add_bytes(schema.symbol_kinds, kind.into_bytes());
+        }
+
+        Some(doc)
     }
 }
 
@@ -698,7 +1092,7 @@ fn is_cache_fresh(cache: &FileCacheSnapshot, unique_hash: &str, entry_pathbuf: &
     false
 }
 
-fn build_fuzzy_regex_filter(query_str: &str) -> Option<regex::RegexSet> {
+pub(crate) fn build_fuzzy_regex_filter(query_str: &str) -> Option<regex::RegexSet> {
     fn additions(s: &str, i: usize, j: usize) -> String {
         if i > j {
             additions(s, j, i)
@@ -760,10 +1154,61 @@ fn build_fuzzy_regex_filter(query_str: &str) -> Option<regex::RegexSet> {
         .ok()
 }
 
+/// Whether the document indexed at `path` is outdated by changes to `changed` paths.
+///
+/// The contents of expanded archives are outdated along with the archive.
+fn is_outdated(changed: &HashSet<String>, path: &str) -> bool {
+    let in_archive = path
+        .split_once(ARCHIVE_SEPARATOR)
+        .map(|(archive, _)| changed.contains(archive))
+        .unwrap_or_default();
+
+    changed.contains(path) || in_archive
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn archive_contents_are_outdated_with_the_archive() {
+        let changed = HashSet::from(["libs/foo.jar".to_owned(), "src/".to_owned()]);
+
+        assert!(is_outdated(&changed, "libs/foo.jar"));
+        assert!(is_outdated(&changed, "libs/foo.jar!/com/foo/Bar.java"));
+        assert!(is_outdated(&changed, "src/"));
+        assert!(!is_outdated(&changed, "libs/bar.jar!/com/foo/Bar.java"));
+        assert!(!is_outdated(&changed, "src/lib.rs"));
+    }
+
+    #[test]
+    fn unified_diff_of_modified_file() {
+        assert_eq!(
+            unified_diff("src/lib.rs", "a\nb\nc\n", "a\nx\nc\n"),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n"
+        );
+    }
+
+    #[test]
+    fn truncated_diff_ends_at_a_line() {
+        let line = "+".repeat(99) + "\n";
+        let diff = truncate_diff(line.repeat(MAX_DIFF_LEN / 100 + 1));
+        assert_eq!(diff.len(), MAX_DIFF_LEN / 100 * 100);
+        assert!(diff.ends_with('\n'));
+
+        assert_eq!(truncate_diff("a\n".to_owned()), "a\n");
+    }
+
+    #[test]
+    fn remote_branches_are_indexed_on_origin() {
+        let local = RepoRef::from(&std::env::temp_dir());
+        let remote = "github.com/bloopai/bloop".parse::<RepoRef>().unwrap();
+
+        assert_eq!(indexed_branch_name(&local, "main"), "main");
+        assert_eq!(indexed_branch_name(&remote, "main"), "origin/main");
+        assert_eq!(indexed_branch_name(&remote, "origin/main"), "origin/main");
+    }
+
     #[test]
     fn fuzzy_multibyte_should_compile() {
         let multibyte_str = "查询解析器在哪";
@@ -5,13 +5,15 @@ use tantivy::{
     Index,
 };
 
-use super::{file::File, repo::Repo, DocumentRead};
+use super::{commit::Commit, file::File, repo::Repo, DocumentRead};
 use crate::{
     intelligence::TreeSitterFile,
     query::{
         compiler::Compiler,
         parser::{self, Query, Target},
+        structural,
     },
+    repo::iterator::LastCommit,
     symbol::SymbolLocations,
     text_range::TextRange,
 };
@@ -43,6 +45,8 @@ pub struct FileDocument {
     pub repo_ref: String,
     pub lang: Option<String>,
     pub branches: String,
+    /// The last commit that changed the file, if it's known
+    pub last_commit: Option<LastCommit>,
 }
 
 pub struct RepoDocument {
@@ -63,6 +67,8 @@ impl DocumentRead for ContentReader {
             query,
             Query {
                 open: Some(false) | None,
+                structural: Some(false) | None,
+                commits: Some(false) | None,
                 target: Some(Target::Content(..) | Target::Symbol(..)),
                 ..
             }
@@ -82,14 +88,19 @@ impl DocumentRead for ContentReader {
             .priority(&[schema.relative_path])
             .literal(schema.relative_path, |q| q.path.clone())
             .literal(schema.repo_name, |q| q.repo.clone())
+            .term(schema.repo_ref, |q| q.repo_ref.as_ref())
             .literal(schema.branches, |q| q.branch.clone())
             .byte_string(schema.lang, |q| q.lang.as_ref())
             .literal(schema.symbols, |q| {
                 q.target.as_ref().and_then(Target::symbol).cloned()
             })
+            .byte_string(schema.symbol_kinds, |q| q.symbol_kind())
             .literal(schema.content, |q| {
                 q.target.as_ref().and_then(Target::content).cloned()
             })
+            .range(schema.last_commit_unix_seconds, |q| q.commit_time_bounds())
+            // Other negated terms are checked precisely by the collector.
+            .negated_byte_string(schema.lang, |q| q.negated_langs().collect())
             .compile(queries, tantivy_index)
     }
 
@@ -131,6 +142,61 @@ impl DocumentRead for ContentReader {
     }
 }
 
+/// Reads content documents that match a tree-sitter pattern, for queries in `mode:structural`.
+pub struct StructuralReader;
+
+#[async_trait]
+impl DocumentRead for StructuralReader {
+    type Schema = File;
+    type Document = ContentDocument;
+
+    fn query_matches(&self, query: &Query<'_>) -> bool {
+        matches!(
+            query,
+            Query {
+                open: Some(false) | None,
+                structural: Some(true),
+                commits: Some(false) | None,
+                // Patterns are written against a single grammar.
+                lang: Some(..),
+                target: Some(Target::Content(..)),
+                ..
+            }
+        )
+    }
+
+    fn compile<'a, I>(
+        &self,
+        schema: &File,
+        queries: I,
+        tantivy_index: &Index,
+    ) -> Result<Box<dyn tantivy::query::Query>>
+    where
+        I: Iterator<Item = &'a Query<'a>>,
+    {
+        Compiler::new()
+            .priority(&[schema.relative_path])
+            .literal(schema.relative_path, |q| q.path.clone())
+            .literal(schema.repo_name, |q| q.repo.clone())
+            .term(schema.repo_ref, |q| q.repo_ref.as_ref())
+            .literal(schema.branches, |q| q.branch.clone())
+            .byte_string(schema.lang, |q| q.lang.as_ref())
+            // Only text that any match must contain narrows down candidates, the pattern itself
+            // is run by the collector.
+            .literal(schema.content, |q| {
+                let pattern = q.target.as_ref()?.content()?.as_plain()?;
+                structural::required_literal(&pattern).map(|lit| parser::Literal::Plain(lit.into()))
+            })
+            .range(schema.last_commit_unix_seconds, |q| q.commit_time_bounds())
+            .negated_byte_string(schema.lang, |q| q.negated_langs().collect())
+            .compile(queries, tantivy_index)
+    }
+
+    fn read_document(&self, schema: &File, doc: tantivy::Document) -> Self::Document {
+        ContentReader.read_document(schema, doc)
+    }
+}
+
 pub struct FileReader;
 
 #[async_trait]
@@ -147,11 +213,13 @@ impl DocumentRead for FileReader {
             //   lang:Rust path:server
             Query {
                 open: Some(false) | None,
+                commits: Some(false) | None,
                 target: None,
                 lang: Some(..),
                 ..
             } | Query {
                 open: Some(false) | None,
+                commits: Some(false) | None,
                 target: None,
                 path: Some(..),
                 ..
@@ -171,8 +239,11 @@ impl DocumentRead for FileReader {
         Compiler::new()
             .literal(schema.relative_path, |q| q.path.clone())
             .literal(schema.repo_name, |q| q.repo.clone())
+            .term(schema.repo_ref, |q| q.repo_ref.as_ref())
             .literal(schema.branches, |q| q.branch.clone())
             .byte_string(schema.lang, |q| q.lang.as_ref())
+            .range(schema.last_commit_unix_seconds, |q| q.commit_time_bounds())
+            .negated_byte_string(schema.lang, |q| q.negated_langs().collect())
             .compile(queries, tantivy_index)
     }
 
@@ -183,12 +254,20 @@ impl DocumentRead for FileReader {
         let lang = read_lang_field(&doc, schema.lang);
         let branches = read_text_field(&doc, schema.branches);
 
+        let author = read_text_field(&doc, schema.last_commit_author);
+        let last_commit = doc
+            .get_first(schema.last_commit_unix_seconds)
+            .and_then(Value::as_u64)
+            .filter(|_| !author.is_empty())
+            .map(|unix_secs| LastCommit { unix_secs, author });
+
         FileDocument {
             relative_path,
             repo_name,
             repo_ref,
             lang,
             branches,
+            last_commit,
         }
     }
 }
@@ -205,6 +284,7 @@ impl DocumentRead for RepoReader {
             query,
             Query {
                 open: Some(false) | None,
+                commits: Some(false) | None,
                 repo: Some(..),
                 path: None,
                 target: None,
@@ -240,6 +320,85 @@ impl DocumentRead for RepoReader {
     }
 }
 
+/// Reads commits from the history index, for queries in `mode:commit`.
+pub struct CommitReader;
+
+#[derive(Debug)]
+pub struct CommitDocument {
+    pub repo_name: String,
+    pub repo_ref: String,
+    pub commit_id: String,
+    pub message: String,
+    pub author: String,
+    pub unix_secs: u64,
+    pub paths: Vec<String>,
+}
+
+#[async_trait]
+impl DocumentRead for CommitReader {
+    type Document = CommitDocument;
+    type Schema = Commit;
+
+    fn query_matches(&self, query: &Query<'_>) -> bool {
+        matches!(
+            query,
+            Query {
+                open: Some(false) | None,
+                commits: Some(true),
+                target: None | Some(Target::Content(..)),
+                ..
+            }
+        )
+    }
+
+    fn compile<'a, I>(
+        &self,
+        schema: &Commit,
+        queries: I,
+        tantivy_index: &Index,
+    ) -> Result<Box<dyn tantivy::query::Query>>
+    where
+        I: Iterator<Item = &'a Query<'a>>,
+    {
+        Compiler::new()
+            .literal(schema.repo_name, |q| q.repo.clone())
+            .term(schema.repo_ref, |q| q.repo_ref.as_ref())
+            .literal(schema.paths, |q| q.path.clone())
+            .literal(schema.message, |q| {
+                q.target.as_ref().and_then(Target::content).cloned()
+            })
+            .range(schema.unix_seconds, |q| q.commit_time_bounds())
+            .compile(queries, tantivy_index)
+    }
+
+    fn read_document(&self, schema: &Commit, doc: tantivy::Document) -> Self::Document {
+        let repo_name = read_text_field(&doc, schema.repo_name);
+        let repo_ref = read_text_field(&doc, schema.repo_ref);
+        let commit_id = read_text_field(&doc, schema.commit_id);
+        let message = read_text_field(&doc, schema.message);
+        let author = read_text_field(&doc, schema.author);
+        let unix_secs = doc
+            .get_first(schema.unix_seconds)
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
+        let paths = doc
+            .get_all(schema.paths)
+            .filter_map(Value::as_text)
+            .map(ToOwned::to_owned)
+            .collect();
+
+        CommitDocument {
+            repo_name,
+            repo_ref,
+            commit_id,
+            message,
+            author,
+            unix_secs,
+            paths,
+        }
+    }
+}
+
 pub struct OpenReader;
 
 #[derive(Debug)]
@@ -262,6 +421,7 @@ impl DocumentRead for OpenReader {
             query,
             Query {
                 open: Some(true),
+                commits: Some(false) | None,
 
                 // All open queries must specify at least the repository name. We don't accept regex
                 // inputs for this type of query.
@@ -287,6 +447,7 @@ impl DocumentRead for OpenReader {
     {
         Compiler::new()
             .literal(schema.repo_name, |q| q.repo.clone())
+            .term(schema.repo_ref, |q| q.repo_ref.as_ref())
             .literal(schema.branches, |q| q.branch.clone())
             .literal(schema.relative_path, |q| match &q.path {
                 // We coerce path searches to always return sibling files. These are sorted later
@@ -364,6 +525,8 @@ fn read_lang_field(doc: &tantivy::Document, lang: Field) -> Option<String> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use pretty_assertions::assert_eq;
+    use tantivy::{collector::TopDocs, tokenizer::NgramTokenizer, Document};
 
     #[test]
     fn test_base_name() {
@@ -371,4 +534,78 @@ mod test {
         assert_eq!(base_name(&format!("bar/")), format!("bar/"));
         assert_eq!(base_name("foo.txt"), "");
     }
+
+    #[test]
+    fn commit_reader() {
+        let schema = Commit::new();
+        let index = Index::create_in_ram(schema.schema.clone());
+        index
+            .tokenizers()
+            .register("default", NgramTokenizer::new(1, 3, false));
+
+        let mut writer = index.writer(15_000_000).unwrap();
+        for (id, message, paths, unix_secs) in [
+            ("a1", "Fix the parser", &["src/parser.rs"][..], 10),
+            ("b2", "Add a lexer", &["src/lexer.rs", "src/lib.rs"][..], 20),
+        ] {
+            let mut doc = Document::new();
+            doc.add_text(schema.repo_ref, "local//bloop");
+            doc.add_text(schema.repo_name, "bloop");
+            doc.add_bytes(schema.raw_repo_name, "bloop".as_bytes());
+            doc.add_text(schema.commit_id, id);
+            doc.add_bytes(schema.raw_message, message.as_bytes());
+            doc.add_text(schema.message, message);
+            doc.add_text(schema.author, "Ferris");
+            doc.add_u64(schema.unix_seconds, unix_secs);
+            doc.add_bytes(schema.raw_paths, paths.join("\n").as_bytes());
+            for path in paths {
+                doc.add_text(schema.paths, path);
+            }
+
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let search = |query: &str| {
+            let queries = parser::parse(query).unwrap();
+            assert!(queries.iter().all(|q| CommitReader.query_matches(q)));
+
+            let compiled = CommitReader
+                .compile(&schema, queries.iter(), &index)
+                .unwrap();
+            let mut ids = searcher
+                .search(&compiled, &TopDocs::with_limit(10))
+                .unwrap()
+                .into_iter()
+                .map(|(_, addr)| {
+                    let doc = CommitReader.read_document(&schema, searcher.doc(addr).unwrap());
+                    doc.commit_id
+                })
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(search("mode:commit parser"), ["a1"]);
+        assert_eq!(search("mode:commit path:lib.rs"), ["b2"]);
+        assert_eq!(search("mode:commit repo:bloop"), ["a1", "b2"]);
+        assert_eq!(
+            search("mode:commit repo:bloop before:1970-01-01"),
+            Vec::<String>::new()
+        );
+
+        let queries = parser::parse("mode:commit lexer").unwrap();
+        let compiled = CommitReader
+            .compile(&schema, queries.iter(), &index)
+            .unwrap();
+        let (_, addr) = searcher.search(&compiled, &TopDocs::with_limit(1)).unwrap()[0];
+        let doc = CommitReader.read_document(&schema, searcher.doc(addr).unwrap());
+        assert_eq!(doc.repo_name, "bloop");
+        assert_eq!(doc.repo_ref, "local//bloop");
+        assert_eq!(doc.message, "Add a lexer");
+        assert_eq!(doc.author, "Ferris");
+        assert_eq!(doc.unix_secs, 20);
+        assert_eq!(doc.paths, ["src/lexer.rs", "src/lib.rs"]);
+    }
 }
@@ -1,6 +1,6 @@
 use tantivy::schema::{
     BytesOptions, Field, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions,
-    FAST, STORED, STRING,
+    FAST, INDEXED, STORED, STRING,
 };
 
 use crate::{db::SqlDb, semantic::Semantic};
@@ -48,11 +48,21 @@ pub struct File {
     pub symbols: Field,
     pub symbol_locations: Field,
 
+    /// the same list of symbols as `symbols`, newline-separated, for fuzzy scoring
+    pub raw_symbols: Field,
+
+    /// the distinct kinds of symbols defined in this file, e.g.:
+    /// ["function", "struct"]
+    pub symbol_kinds: Field,
+
     /// fast fields for scoring
     pub lang: Field,
     pub avg_line_length: Field,
     pub last_commit_unix_seconds: Field,
 
+    /// author of the last commit that touched this entry
+    pub last_commit_author: Field,
+
     /// fast byte versions of certain fields for collector-level filtering
     pub raw_content: Field,
     pub raw_repo_name: Field,
@@ -60,6 +70,8 @@ pub struct File {
 
     /// list of branches in which this file can be found
     pub branches: Field,
+    /// the same list of branches, newline-separated, for facet counts
+    pub raw_branches: Field,
 
     /// Whether this entry is a file or a directory
     pub is_directory: Field,
@@ -88,6 +100,8 @@ impl File {
         let symbols = builder.add_text_field("symbols", trigram.clone());
         let symbol_locations =
             builder.add_bytes_field("symbol_locations", BytesOptions::default().set_stored());
+        let symbol_kinds =
+            builder.add_bytes_field("symbol_kinds", BytesOptions::default().set_indexed());
 
         let branches = builder.add_text_field("branches", trigram);
 
@@ -96,11 +110,15 @@ impl File {
             BytesOptions::default().set_stored().set_indexed() | FAST,
         );
         let avg_line_length = builder.add_f64_field("line_length", FAST);
-        let last_commit_unix_seconds = builder.add_u64_field("last_commit_unix_seconds", FAST);
+        let last_commit_unix_seconds =
+            builder.add_u64_field("last_commit_unix_seconds", FAST | INDEXED | STORED);
+        let last_commit_author = builder.add_text_field("last_commit_author", STRING | STORED);
 
         let raw_content = builder.add_bytes_field("raw_content", FAST);
         let raw_repo_name = builder.add_bytes_field("raw_repo_name", FAST);
         let raw_relative_path = builder.add_bytes_field("raw_relative_path", FAST);
+        let raw_symbols = builder.add_bytes_field("raw_symbols", FAST);
+        let raw_branches = builder.add_bytes_field("raw_branches", FAST);
 
         let is_directory = builder.add_bool_field("is_directory", FAST);
 
@@ -114,15 +132,19 @@ impl File {
             line_end_indices,
             symbols,
             symbol_locations,
+            raw_symbols,
+            symbol_kinds,
             lang,
             avg_line_length,
             last_commit_unix_seconds,
+            last_commit_author,
             schema: builder.build(),
             semantic,
             raw_content,
             raw_repo_name,
             raw_relative_path,
             branches,
+            raw_branches,
             is_directory,
             sql,
 
@@ -180,3 +202,77 @@ impl Repo {
         }
     }
 }
+
+/// An index of the commit history of each repository, to search commit messages and the paths
+/// changed by each commit
+pub struct Commit {
+    pub(super) schema: Schema,
+
+    /// Path to the root of the repo on disk
+    pub repo_disk_path: Field,
+
+    /// Unique repo identifier, of the form:
+    ///  local: local//path/to/repo
+    /// github: github.com/org/repo
+    pub repo_ref: Field,
+
+    /// Indexed repo name, of the form:
+    ///  local: repo
+    /// github: github.com/org/repo
+    pub repo_name: Field,
+    pub raw_repo_name: Field,
+
+    /// Full hex ID of the commit
+    pub commit_id: Field,
+    pub message: Field,
+    pub author: Field,
+    pub unix_seconds: Field,
+
+    /// Paths changed by the commit, relative to the repo root
+    pub paths: Field,
+
+    /// fast byte versions of certain fields for collector-level filtering
+    pub raw_message: Field,
+    /// the same list of paths as `paths`, newline-separated
+    pub raw_paths: Field,
+}
+
+impl Commit {
+    pub fn new() -> Self {
+        let mut builder = SchemaBuilder::new();
+        let trigram = TextOptions::default().set_stored().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer("default")
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+
+        let repo_disk_path = builder.add_text_field("repo_disk_path", STRING);
+        let repo_ref = builder.add_text_field("repo_ref", STRING | STORED);
+        let repo_name = builder.add_text_field("repo_name", trigram.clone());
+        let raw_repo_name = builder.add_bytes_field("raw_repo_name", FAST);
+
+        let commit_id = builder.add_text_field("commit_id", STRING | STORED);
+        let message = builder.add_text_field("message", trigram.clone());
+        let author = builder.add_text_field("author", STRING | STORED);
+        let unix_seconds = builder.add_u64_field("unix_seconds", FAST | INDEXED | STORED);
+        let paths = builder.add_text_field("paths", trigram);
+
+        let raw_message = builder.add_bytes_field("raw_message", FAST);
+        let raw_paths = builder.add_bytes_field("raw_paths", FAST);
+
+        Self {
+            repo_disk_path,
+            repo_ref,
+            repo_name,
+            raw_repo_name,
+            commit_id,
+            message,
+            author,
+            unix_seconds,
+            paths,
+            raw_message,
+            raw_paths,
+            schema: builder.build(),
+        }
+    }
+}
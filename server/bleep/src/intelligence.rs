@@ -1,6 +1,10 @@
+pub mod call_hierarchy;
 pub mod code_navigation;
+mod imports;
 mod language;
 mod namespace;
+pub mod outline;
+pub mod precise;
 mod scope_resolution;
 
 pub use {
@@ -82,6 +86,21 @@ impl<'a> TreeSitterFile<'a> {
             .collect::<Vec<_>>())
     }
 
+    /// Byte ranges of all nodes captured by `query`, sorted by position in the file.
+    pub fn capture_ranges(self, query: &tree_sitter::Query) -> Vec<std::ops::Range<usize>> {
+        let root_node = self.tree.root_node();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let mut ranges = cursor
+            .matches(query, root_node, self.src)
+            .flat_map(|m| m.captures)
+            .map(|c| c.node.byte_range())
+            .collect::<Vec<_>>();
+
+        ranges.sort_by_key(|r| (r.start, r.end));
+        ranges.dedup();
+        ranges
+    }
+
     /// Produce a lexical scope-graph for this TreeSitterFile.
     pub fn scope_graph(self) -> Result<ScopeGraph, TreeSitterFileError> {
         let query = self
@@ -0,0 +1,259 @@
+//! Calls between functions, derived from the scope graph.
+//!
+//! A reference is a call site of the function it resolves to, made by the innermost function
+//! whose declaration encloses the reference. This is a syntactic approximation: any reference to a
+//! function counts as a call, including passing it as a value.
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    code_navigation::{to_occurrence, Precision},
+    outline::outline,
+    NodeKind,
+};
+use crate::{indexes::reader::ContentDocument, snippet::Snippet, text_range::TextRange};
+
+/// Symbol kinds, across the namespaces of all languages, that are called.
+const CALLABLE_KINDS: &[&str] = &["function", "func", "method", "generator", "macro"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallDirection {
+    /// The functions that call a function
+    Incoming,
+
+    /// The functions that a function calls
+    Outgoing,
+}
+
+/// A function definition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CallItem {
+    pub file: String,
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+
+    /// The range of the name of the function
+    pub range: TextRange,
+
+    /// The range of the declaration of the function, including its body
+    pub extent: TextRange,
+}
+
+/// A reference that calls a function.
+#[derive(Debug, Serialize)]
+pub struct CallSite {
+    pub range: TextRange,
+    pub snippet: Snippet,
+}
+
+/// A caller or callee of a function, with the sites of the calls between them, and its own
+/// callers or callees.
+#[derive(Debug, Serialize)]
+pub struct CallEdge {
+    pub item: CallItem,
+
+    /// `Heuristic` if any of the calls was resolved by name only
+    pub precision: Precision,
+
+    /// Call sites in the file of the calling function
+    pub sites: Vec<CallSite>,
+
+    pub children: Vec<CallEdge>,
+}
+
+/// The functions defined in a document, in no particular order.
+pub fn callables(doc: &ContentDocument) -> Vec<CallItem> {
+    let (Some(lang_id), Some(graph)) = (doc.lang.as_deref(), doc.symbol_locations.scope_graph())
+    else {
+        return vec![];
+    };
+
+    let mut items = vec![];
+    let mut symbols = outline(&doc.content, lang_id, graph);
+    while let Some(symbol) = symbols.pop() {
+        let callable = symbol
+            .kind
+            .map(|kind| CALLABLE_KINDS.contains(&kind))
+            .unwrap_or_default();
+
+        if callable {
+            items.push(CallItem {
+                file: doc.relative_path.clone(),
+                name: symbol.name,
+                kind: symbol.kind,
+                range: symbol.range,
+                extent: symbol.extent,
+            });
+        }
+
+        symbols.extend(symbol.children);
+    }
+
+    items
+}
+
+/// The innermost function whose declaration contains `range`.
+pub fn enclosing_callable(callables: &[CallItem], range: TextRange) -> Option<&CallItem> {
+    callables
+        .iter()
+        .filter(|item| item.extent.contains(&range) && item.range != range)
+        .min_by_key(|item| item.extent.size())
+}
+
+/// The references made directly by `item`, rather than by the functions nested in it, sorted by
+/// position.
+pub fn call_sites(
+    doc: &ContentDocument,
+    item: &CallItem,
+    callables: &[CallItem],
+) -> Vec<TextRange> {
+    let Some(graph) = doc.symbol_locations.scope_graph() else {
+        return vec![];
+    };
+
+    let mut sites = graph
+        .graph
+        .node_weights()
+        .filter_map(|node| match node {
+            NodeKind::Ref(reference) => Some(reference.range),
+            _ => None,
+        })
+        .filter(|range| {
+            enclosing_callable(callables, *range)
+                .map(|caller| caller.range == item.range)
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    sites.sort();
+    sites
+}
+
+pub fn call_site(doc: &ContentDocument, range: TextRange) -> CallSite {
+    CallSite {
+        range,
+        snippet: to_occurrence(doc, range),
+    }
+}
+
+/// Add a call site to the edge to `item`, adding the edge if it isn't in `edges` yet.
+pub fn push_site(edges: &mut Vec<CallEdge>, item: &CallItem, precision: Precision, site: CallSite) {
+    let idx = match edges
+        .iter()
+        .position(|edge| edge.item.file == item.file && edge.item.range == item.range)
+    {
+        Some(idx) => idx,
+        None => {
+            edges.push(CallEdge {
+                item: item.clone(),
+                precision,
+                sites: vec![],
+                children: vec![],
+            });
+            edges.len() - 1
+        }
+    };
+
+    let edge = &mut edges[idx];
+    if precision == Precision::Heuristic {
+        edge.precision = Precision::Heuristic;
+    }
+
+    if edge.sites.iter().all(|s| s.range != site.range) {
+        edge.sites.push(site);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{intelligence::TreeSitterFile, symbol::SymbolLocations};
+    use pretty_assertions::assert_eq;
+
+    fn document(src: &str) -> ContentDocument {
+        let graph = TreeSitterFile::try_build(src.as_bytes(), "Rust")
+            .and_then(TreeSitterFile::scope_graph)
+            .unwrap();
+
+        ContentDocument {
+            content: src.to_owned(),
+            lang: Some("Rust".to_owned()),
+            relative_path: "src/lib.rs".to_owned(),
+            line_end_indices: src.match_indices('\n').map(|(i, _)| i as u32).collect(),
+            symbol_locations: SymbolLocations::TreeSitter(graph),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nested_calls() {
+        let src = r#"
+fn run() {
+    fn step() {
+        log();
+    }
+
+    step();
+    log();
+}
+
+fn log() {}
+"#;
+
+        let doc = document(src);
+        let callables = callables(&doc);
+        let named = |name: &str| callables.iter().find(|item| item.name == name).unwrap();
+        let text = |range: TextRange| &src[range.start.byte..range.end.byte];
+
+        let mut names = callables
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["log", "run", "step"]);
+
+        let run_sites = call_sites(&doc, named("run"), &callables);
+        assert_eq!(
+            run_sites.iter().map(|r| text(*r)).collect::<Vec<_>>(),
+            ["step", "log"]
+        );
+        assert_eq!(
+            enclosing_callable(&callables, run_sites[0]).map(|item| item.name.as_str()),
+            Some("run")
+        );
+
+        let step_sites = call_sites(&doc, named("step"), &callables);
+        assert_eq!(
+            step_sites.iter().map(|r| text(*r)).collect::<Vec<_>>(),
+            ["log"]
+        );
+        assert!(call_sites(&doc, named("log"), &callables).is_empty());
+    }
+
+    #[test]
+    fn edges_group_sites() {
+        let src = "fn a() { b(); b(); }\nfn b() {}\n";
+        let doc = document(src);
+        let callables = callables(&doc);
+        let a = callables.iter().find(|item| item.name == "a").unwrap();
+        let b = callables.iter().find(|item| item.name == "b").unwrap();
+
+        let mut edges = vec![];
+        for range in call_sites(&doc, a, &callables) {
+            push_site(&mut edges, b, Precision::Precise, call_site(&doc, range));
+        }
+        push_site(
+            &mut edges,
+            b,
+            Precision::Heuristic,
+            call_site(&doc, edges[0].sites[0].range),
+        );
+
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].sites.len(), 2);
+        assert_eq!(edges[0].precision, Precision::Heuristic);
+    }
+}
@@ -4,8 +4,12 @@
 
 use std::ops::Not;
 
-use super::NodeKind;
+use super::{
+    imports::{resolve_import, resolve_qualified, ImportTarget},
+    NodeKind,
+};
 use crate::{
+    db::{SiteKind, SiteMatch},
     indexes::reader::ContentDocument,
     repo::RepoRef,
     snippet::{Snipper, Snippet},
@@ -16,6 +20,10 @@ use serde::Serialize;
 
 #[derive(Debug, Serialize)]
 pub struct FileSymbols {
+    /// The repo of the file, when it's not the repo the token is in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_ref: Option<String>,
+
     /// The file to which the following occurrences belong
     pub file: String,
 
@@ -26,6 +34,7 @@ pub struct FileSymbols {
 #[derive(Serialize, Debug)]
 pub struct Occurrence {
     pub kind: OccurrenceKind,
+    pub precision: Precision,
     pub range: TextRange,
     pub snippet: Snippet,
 }
@@ -36,7 +45,7 @@ impl Occurrence {
     }
 }
 
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Debug, Default, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum OccurrenceKind {
     #[default]
@@ -44,18 +53,31 @@ pub enum OccurrenceKind {
     Definition,
 }
 
+/// How an occurrence was found.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    /// Resolved through the scope graph of a file, or through an import
+    Precise,
+
+    /// Matched by name only
+    Heuristic,
+}
+
 pub enum CodeNavigationError {}
 
 pub struct CodeNavigationContext<'a> {
     pub repo_ref: RepoRef,
     pub token: Token<'a>,
-    pub all_docs: Vec<ContentDocument>,
-    pub source_document_idx: usize,
+    pub source_document: ContentDocument,
+    /// Sites of the top-level symbols named like the token in other files of the repo, along
+    /// with the documents they're in
+    pub repo_sites: Vec<(ContentDocument, Vec<SiteMatch>)>,
 }
 
 impl<'a> CodeNavigationContext<'a> {
     fn source_document(&self) -> &ContentDocument {
-        self.all_docs.get(self.source_document_idx).unwrap()
+        &self.source_document
     }
 
     pub fn token_info(&self) -> Vec<FileSymbols> {
@@ -100,6 +122,12 @@ impl<'a> CodeNavigationContext<'a> {
                 .into_iter()
                 .chain(local_references)
                 .collect()
+        } else if let Some(target) = self.qualified_import_target() {
+            self.repo_wide_sites(
+                SiteKind::Definition,
+                OccurrenceKind::Definition,
+                Some(&target),
+            )
         } else {
             Vec::new()
         }
@@ -149,12 +177,6 @@ impl<'a> CodeNavigationContext<'a> {
             .unwrap_or_default()
     }
 
-    fn non_source_documents(&self) -> impl Iterator<Item = &ContentDocument> {
-        self.all_docs
-            .iter()
-            .filter(|doc| doc.relative_path != self.source_document().relative_path)
-    }
-
     pub fn active_token_range(&self) -> std::ops::Range<usize> {
         self.token.start_byte..self.token.end_byte
     }
@@ -174,6 +196,7 @@ impl<'a> CodeNavigationContext<'a> {
             .definitions(node_idx)
             .map(|idx| Occurrence {
                 kind: OccurrenceKind::Definition,
+                precision: Precision::Precise,
                 range: scope_graph.graph[idx].range(),
                 snippet: to_occurrence(self.source_document(), scope_graph.graph[idx].range()),
             })
@@ -182,42 +205,62 @@ impl<'a> CodeNavigationContext<'a> {
         data.sort_by_key(|occurrence| occurrence.range.start.byte);
 
         data.is_empty().not().then(|| FileSymbols {
+            repo_ref: None,
             file: self.token.relative_path.to_owned(),
             data,
         })
     }
 
+    /// Definitions in the files that the token is imported from, if that can be resolved, or any
+    /// top-level definition with the same name otherwise.
     fn repo_wide_definitions(&self) -> Vec<FileSymbols> {
-        self.non_source_documents()
-            .filter_map(|doc| {
-                let scope_graph = doc.symbol_locations.scope_graph()?;
-                let content = doc.content.as_bytes();
-                let mut data = scope_graph
-                    .graph
-                    .node_indices()
-                    .filter(|idx| scope_graph.is_top_level(*idx))
-                    .filter(|idx| {
-                        if let Some(NodeKind::Def(d)) = scope_graph.get_node(*idx) {
-                            d.name(content) == self.active_token_text().as_bytes()
-                        } else {
-                            false
-                        }
-                    })
-                    .map(|idx| Occurrence {
-                        kind: OccurrenceKind::Definition,
-                        range: scope_graph.graph[idx].range(),
-                        snippet: to_occurrence(doc, scope_graph.graph[idx].range()),
-                    })
-                    .collect::<Vec<_>>();
+        let imported = self
+            .import_target()
+            .or_else(|| self.qualified_import_target())
+            .map(|target| {
+                self.repo_wide_sites(
+                    SiteKind::Definition,
+                    OccurrenceKind::Definition,
+                    Some(&target),
+                )
+            })
+            .unwrap_or_default();
 
-                data.sort_by_key(|occurrence| occurrence.range.start.byte);
+        if imported.is_empty() {
+            self.repo_wide_sites(SiteKind::Definition, OccurrenceKind::Definition, None)
+        } else {
+            imported
+        }
+    }
 
-                data.is_empty().not().then(|| FileSymbols {
-                    file: doc.relative_path.to_owned(),
-                    data,
-                })
-            })
-            .collect()
+    /// The files that the import of the token, or the token itself if it is an import, refers to.
+    fn import_target(&self) -> Option<ImportTarget> {
+        let doc = self.source_document();
+        let scope_graph = doc.symbol_locations.scope_graph()?;
+        let node_idx = scope_graph.node_by_range(self.token.start_byte, self.token.end_byte)?;
+        let import_idx = match scope_graph.get_node(node_idx)? {
+            NodeKind::Import(_) => node_idx,
+            NodeKind::Ref(_) => scope_graph.imports(node_idx).next()?,
+            _ => return None,
+        };
+
+        let range = scope_graph.graph[import_idx].range();
+        resolve_import(
+            &doc.content,
+            doc.lang.as_deref()?,
+            &doc.relative_path,
+            range.start.byte..range.end.byte,
+        )
+    }
+
+    /// The package that the token is qualified by, as in `pkg.Name`.
+    fn qualified_import_target(&self) -> Option<ImportTarget> {
+        let doc = self.source_document();
+        resolve_qualified(
+            &doc.content,
+            doc.lang.as_deref()?,
+            self.active_token_range(),
+        )
     }
 
     fn local_references(&self) -> Option<FileSymbols> {
@@ -230,6 +273,7 @@ impl<'a> CodeNavigationContext<'a> {
             .chain(scope_graph.references(node_idx))
             .map(|idx| Occurrence {
                 kind: OccurrenceKind::Reference,
+                precision: Precision::Precise,
                 range: scope_graph.graph[idx].range(),
                 snippet: to_occurrence(self.source_document(), scope_graph.graph[idx].range()),
             })
@@ -241,38 +285,51 @@ impl<'a> CodeNavigationContext<'a> {
         data.sort_by_key(|occurrence| occurrence.range.start.byte);
 
         data.is_empty().not().then(|| FileSymbols {
+            repo_ref: None,
             file: self.token.relative_path.to_owned(),
             data,
         })
     }
 
     fn repo_wide_references(&self) -> Vec<FileSymbols> {
-        self.non_source_documents()
-            .filter_map(|doc| {
-                let scope_graph = doc.symbol_locations.scope_graph()?;
-                let content = doc.content.as_bytes();
-                let mut data = scope_graph
-                    .graph
-                    .node_indices()
-                    .filter(|idx| scope_graph.is_top_level(*idx))
-                    .filter(|idx| match scope_graph.get_node(*idx).unwrap() {
-                        NodeKind::Def(n) => n.name(content) == self.active_token_text().as_bytes(),
-                        NodeKind::Import(n) => {
-                            n.name(content) == self.active_token_text().as_bytes()
-                        }
-                        _ => false,
-                    })
-                    .flat_map(|idx| scope_graph.references(idx))
-                    .map(|idx| Occurrence {
-                        kind: OccurrenceKind::Reference,
-                        range: scope_graph.graph[idx].range(),
-                        snippet: to_occurrence(doc, scope_graph.graph[idx].range()),
+        self.repo_wide_sites(SiteKind::Reference, OccurrenceKind::Reference, None)
+    }
+
+    /// Occurrences from the symbol table, either in the files an import refers to, or in any file.
+    fn repo_wide_sites(
+        &self,
+        site_kind: SiteKind,
+        kind: OccurrenceKind,
+        target: Option<&ImportTarget>,
+    ) -> Vec<FileSymbols> {
+        let precision = match target {
+            Some(_) => Precision::Precise,
+            None => Precision::Heuristic,
+        };
+
+        self.repo_sites
+            .iter()
+            .filter(|(doc, _)| {
+                target
+                    .map(|target| target.matches(&doc.relative_path))
+                    .unwrap_or(true)
+            })
+            .filter_map(|(doc, sites)| {
+                let mut data = sites
+                    .iter()
+                    .filter(|site| site.kind == site_kind)
+                    .map(|site| Occurrence {
+                        kind,
+                        precision,
+                        range: site.range,
+                        snippet: to_occurrence(doc, site.range),
                     })
                     .collect::<Vec<_>>();
 
                 data.sort_by_key(|occurrence| occurrence.range.start.byte);
 
                 data.is_empty().not().then(|| FileSymbols {
+                    repo_ref: None,
                     file: doc.relative_path.to_owned(),
                     data,
                 })
@@ -287,6 +344,7 @@ impl<'a> CodeNavigationContext<'a> {
             .imports(node_idx)
             .map(|idx| Occurrence {
                 kind: OccurrenceKind::Definition,
+                precision: Precision::Precise,
                 range: scope_graph.graph[idx].range(),
                 snippet: to_occurrence(self.source_document(), scope_graph.graph[idx].range()),
             })
@@ -295,6 +353,7 @@ impl<'a> CodeNavigationContext<'a> {
         data.sort_by_key(|occurrence| occurrence.range.start.byte);
 
         data.is_empty().not().then(|| FileSymbols {
+            repo_ref: None,
             file: self.token.relative_path.to_owned(),
             data,
         })
@@ -307,7 +366,7 @@ pub struct Token<'a> {
     pub end_byte: usize,
 }
 
-fn to_occurrence(doc: &ContentDocument, range: TextRange) -> Snippet {
+pub(super) fn to_occurrence(doc: &ContentDocument, range: TextRange) -> Snippet {
     let src = &doc.content;
     let line_end_indices = &doc.line_end_indices;
     let highlight = range.start.byte..range.end.byte;
@@ -0,0 +1,487 @@
+//! Resolve the module an import refers to, in order to narrow repo-wide definitions down to the
+//! files that can be imported.
+//!
+//! Resolution is syntactic: it works out the paths that a module could live at from the import
+//! statement and the path of the importing file alone, without reading build configuration such as
+//! `Cargo.toml`, `tsconfig.json` or `go.mod`.
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use super::TreeSitterFile;
+
+/// Extensions tried for JS/TS imports that omit them.
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "d.ts", "js", "jsx", "mjs", "cjs"];
+
+/// The files an import may refer to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImportTarget {
+    /// Any of these paths, relative to the repo root
+    Files(Vec<String>),
+
+    /// Any path that ends with one of these, for imports relative to an unknown source root
+    Suffixes(Vec<String>),
+
+    /// Any file directly inside a directory that this import path ends with
+    Package(String),
+}
+
+impl ImportTarget {
+    /// Whether the file at `path`, relative to the repo root, could be the target of the import.
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Files(files) => files.iter().any(|file| file == path),
+            Self::Suffixes(suffixes) => suffixes
+                .iter()
+                .any(|suffix| path == suffix || path.ends_with(&format!("/{suffix}"))),
+            Self::Package(import_path) => match path.rsplit_once('/') {
+                Some((dir, _)) => import_path == dir || import_path.ends_with(&format!("/{dir}")),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Resolve the import statement that introduces the name at `range` of the file at `path`.
+///
+/// Handles Rust `use` declarations, JS/TS `import` statements and `require` calls with relative
+/// specifiers, and Python `from .. import ..` statements.
+pub fn resolve_import(
+    src: &str,
+    lang_id: &str,
+    path: &str,
+    range: Range<usize>,
+) -> Option<ImportTarget> {
+    let file = TreeSitterFile::try_build(src.as_bytes(), lang_id).ok()?;
+    let node = file
+        .tree
+        .root_node()
+        .descendant_for_byte_range(range.start, range.end)?;
+
+    match lang_id.to_ascii_lowercase().as_str() {
+        "rust" => rust_import(node, src, path),
+        "javascript" | "jsx" | "typescript" | "tsx" => js_import(node, src, path),
+        "python" => python_import(node, src, path),
+        _ => None,
+    }
+}
+
+/// Resolve the package of a qualified name, such as `Name` in `pkg.Name`, where `range` is that
+/// of the name.
+///
+/// Only Go is supported, where imported packages are always referred to this way.
+pub fn resolve_qualified(src: &str, lang_id: &str, range: Range<usize>) -> Option<ImportTarget> {
+    if !lang_id.eq_ignore_ascii_case("go") {
+        return None;
+    }
+
+    let file = TreeSitterFile::try_build(src.as_bytes(), lang_id).ok()?;
+    let root = file.tree.root_node();
+    let node = root.descendant_for_byte_range(range.start, range.end)?;
+
+    let parent = node.parent()?;
+    let package = match parent.kind() {
+        "selector_expression" if is_field(parent, "field", node) => {
+            parent.child_by_field_name("operand")?
+        }
+        "qualified_type" if is_field(parent, "name", node) => {
+            parent.child_by_field_name("package")?
+        }
+        _ => return None,
+    };
+    let package = package.utf8_text(src.as_bytes()).ok()?;
+
+    let mut cursor = root.walk();
+    let imports = root
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "import_declaration")
+        .collect::<Vec<_>>();
+
+    imports
+        .into_iter()
+        .flat_map(go_import_specs)
+        .find_map(|spec| {
+            let import_path = spec
+                .child_by_field_name("path")?
+                .utf8_text(src.as_bytes())
+                .ok()?
+                .trim_matches(|c| c == '"' || c == '`');
+
+            let name = match spec.child_by_field_name("name") {
+                Some(name) => name.utf8_text(src.as_bytes()).ok()?,
+                None => import_path.rsplit('/').next()?,
+            };
+
+            (name == package).then(|| ImportTarget::Package(import_path.to_owned()))
+        })
+}
+
+fn go_import_specs(decl: Node<'_>) -> Vec<Node<'_>> {
+    let mut cursor = decl.walk();
+    let specs = decl
+        .named_children(&mut cursor)
+        .flat_map(|child| match child.kind() {
+            "import_spec" => vec![child],
+            "import_spec_list" => {
+                let mut cursor = child.walk();
+                let specs = child
+                    .named_children(&mut cursor)
+                    .filter(|spec| spec.kind() == "import_spec")
+                    .collect();
+                specs
+            }
+            _ => vec![],
+        })
+        .collect();
+    specs
+}
+
+fn is_field(parent: Node<'_>, field: &str, child: Node<'_>) -> bool {
+    parent
+        .child_by_field_name(field)
+        .map(|node| node.id() == child.id())
+        .unwrap_or_default()
+}
+
+/// Resolve a `use` declaration to the files of the module the item is in.
+///
+/// The crate root is assumed to be the closest `src` directory, and modules to follow the default
+/// file layout: `a/b.rs` or `a/b/mod.rs` for `crate::a::b`.
+fn rust_import(node: Node<'_>, src: &str, path: &str) -> Option<ImportTarget> {
+    let text = |node: Node<'_>| node.utf8_text(src.as_bytes()).ok();
+    let segments = |node: Node<'_>| {
+        text(node).map(|path| {
+            path.split("::")
+                .map(|s| s.trim().to_owned())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // the full path of the imported item
+    let mut item_path = vec![text(node)?.to_owned()];
+    let mut current = node;
+
+    loop {
+        let parent = current.parent()?;
+        match parent.kind() {
+            "scoped_identifier" if is_field(parent, "name", current) => {
+                let mut prefix = segments(parent.child_by_field_name("path")?)?;
+                prefix.append(&mut item_path);
+                item_path = prefix;
+            }
+            "use_as_clause" => item_path = segments(parent.child_by_field_name("path")?)?,
+            "scoped_use_list" => {
+                let mut prefix = segments(parent.child_by_field_name("path")?)?;
+                prefix.append(&mut item_path);
+                item_path = prefix;
+            }
+            "use_list" => {}
+            "use_declaration" => break,
+            _ => return None,
+        }
+
+        current = parent;
+    }
+
+    item_path.pop();
+    rust_module_files(path, &item_path).map(ImportTarget::Files)
+}
+
+/// The files that the module at `module_path` could be defined in, when used from `path`.
+fn rust_module_files(path: &str, module_path: &[String]) -> Option<Vec<String>> {
+    let (dir, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let crate_root = {
+        let mut parts = dir.split('/').collect::<Vec<_>>();
+        let src_idx = parts.iter().rposition(|part| *part == "src")?;
+        parts.truncate(src_idx + 1);
+        parts.join("/")
+    };
+
+    // the directory that the submodules of the current module are in
+    let current_dir = match file_name {
+        "mod.rs" | "lib.rs" | "main.rs" => dir.to_owned(),
+        _ => join(dir, file_name.strip_suffix(".rs")?),
+    };
+
+    let (mut module_dir, rest) = match module_path.first().map(String::as_str) {
+        Some("crate") => (crate_root.clone(), &module_path[1..]),
+        Some("self") => (current_dir, &module_path[1..]),
+        Some("super") => {
+            let supers = module_path.iter().take_while(|s| *s == "super").count();
+            let mut module_dir = current_dir;
+            for _ in 0..supers {
+                if module_dir.len() <= crate_root.len() {
+                    return None;
+                }
+                module_dir = module_dir.rsplit_once('/')?.0.to_owned();
+            }
+            (module_dir, &module_path[supers..])
+        }
+        _ => (crate_root.clone(), module_path),
+    };
+
+    for segment in rest {
+        module_dir = join(&module_dir, segment);
+    }
+
+    if module_dir == crate_root {
+        Some(vec![
+            join(&module_dir, "lib.rs"),
+            join(&module_dir, "main.rs"),
+        ])
+    } else {
+        Some(vec![
+            format!("{module_dir}.rs"),
+            join(&module_dir, "mod.rs"),
+        ])
+    }
+}
+
+/// Resolve a relative ES module or CommonJS import to the files it could refer to.
+fn js_import(node: Node<'_>, src: &str, path: &str) -> Option<ImportTarget> {
+    let mut current = node;
+    let source = loop {
+        current = current.parent()?;
+        match current.kind() {
+            "import_statement" => break current.child_by_field_name("source")?,
+            "variable_declarator" => {
+                let call = current.child_by_field_name("value")?;
+                let function = call.child_by_field_name("function")?;
+                if function.utf8_text(src.as_bytes()).ok()? != "require" {
+                    return None;
+                }
+
+                let arguments = call.child_by_field_name("arguments")?;
+                break arguments.named_child(0)?;
+            }
+            _ => {}
+        }
+    };
+
+    let specifier = source
+        .utf8_text(src.as_bytes())
+        .ok()?
+        .trim_matches(|c| c == '"' || c == '\'' || c == '`');
+
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return None;
+    }
+
+    let dir = path
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or_default();
+    let module = normalize(&join(dir, specifier))?;
+
+    let files = std::iter::once(module.clone())
+        .chain(JS_EXTENSIONS.iter().map(|ext| format!("{module}.{ext}")))
+        .chain(
+            JS_EXTENSIONS
+                .iter()
+                .map(|ext| join(&module, &format!("index.{ext}"))),
+        )
+        .collect();
+
+    Some(ImportTarget::Files(files))
+}
+
+/// Resolve a `from module import name` statement to the files the module could be defined in.
+///
+/// Relative imports are resolved against the importing file. Absolute imports may be relative to
+/// any source root, so they match on a path suffix.
+fn python_import(node: Node<'_>, src: &str, path: &str) -> Option<ImportTarget> {
+    let mut current = node;
+    let statement = loop {
+        current = current.parent()?;
+        if current.kind() == "import_from_statement" {
+            break current;
+        }
+    };
+
+    let module = statement
+        .child_by_field_name("module_name")?
+        .utf8_text(src.as_bytes())
+        .ok()?;
+
+    let name = module.trim_start_matches('.');
+    let dots = module.len() - name.len();
+    let module_path = name.replace('.', "/");
+
+    if dots == 0 {
+        return Some(ImportTarget::Suffixes(vec![
+            format!("{module_path}.py"),
+            format!("{module_path}/__init__.py"),
+        ]));
+    }
+
+    let mut package = path
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or_default();
+    for _ in 1..dots {
+        package = match package.rsplit_once('/') {
+            Some((parent, _)) => parent,
+            None if !package.is_empty() => "",
+            None => return None,
+        };
+    }
+
+    if module_path.is_empty() {
+        Some(ImportTarget::Files(vec![join(package, "__init__.py")]))
+    } else {
+        let module = join(package, &module_path);
+        Some(ImportTarget::Files(vec![
+            format!("{module}.py"),
+            join(&module, "__init__.py"),
+        ]))
+    }
+}
+
+fn join(dir: &str, path: &str) -> String {
+    if dir.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{dir}/{path}")
+    }
+}
+
+/// Resolve `.` and `..` components of a relative path, or `None` if it points outside the repo.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn resolve(src: &str, lang_id: &str, path: &str, name: &str) -> Option<ImportTarget> {
+        let start = src.find(name).unwrap();
+        resolve_import(src, lang_id, path, start..start + name.len())
+    }
+
+    fn files(files: &[&str]) -> Option<ImportTarget> {
+        Some(ImportTarget::Files(
+            files.iter().copied().map(ToOwned::to_owned).collect(),
+        ))
+    }
+
+    #[test]
+    fn rust_use_paths() {
+        let path = "server/bleep/src/indexes/file.rs";
+
+        assert_eq!(
+            resolve("use crate::repo::Config;", "Rust", path, "Config"),
+            files(&["server/bleep/src/repo.rs", "server/bleep/src/repo/mod.rs"]),
+        );
+        assert_eq!(
+            resolve("use super::{reader::{Reader, X}};", "Rust", path, "X"),
+            files(&[
+                "server/bleep/src/indexes/reader.rs",
+                "server/bleep/src/indexes/reader/mod.rs"
+            ]),
+        );
+        assert_eq!(
+            resolve("use self::schema::File as Schema;", "Rust", path, "Schema"),
+            files(&[
+                "server/bleep/src/indexes/file/schema.rs",
+                "server/bleep/src/indexes/file/schema/mod.rs"
+            ]),
+        );
+        assert_eq!(
+            resolve("use crate::Application;", "Rust", path, "Application"),
+            files(&["server/bleep/src/lib.rs", "server/bleep/src/main.rs"]),
+        );
+        assert_eq!(resolve("use crate::a::B;", "Rust", "lib.rs", "B"), None);
+    }
+
+    #[test]
+    fn js_relative_imports() {
+        let target = resolve(
+            "import { Button } from '../components/Button';",
+            "TypeScript",
+            "client/src/pages/Home.tsx",
+            "Button",
+        )
+        .unwrap();
+
+        assert!(target.matches("client/src/components/Button.tsx"));
+        assert!(target.matches("client/src/components/Button/index.ts"));
+        assert!(!target.matches("client/src/pages/Button.tsx"));
+
+        let target = resolve(
+            "const utils = require('./utils');",
+            "JavaScript",
+            "lib/main.js",
+            "utils",
+        )
+        .unwrap();
+        assert!(target.matches("lib/utils.js"));
+
+        assert_eq!(
+            resolve("import React from 'react';", "JavaScript", "a.js", "React"),
+            None
+        );
+    }
+
+    #[test]
+    fn python_module_paths() {
+        assert_eq!(
+            resolve(
+                "from ..models import User",
+                "Python",
+                "app/views/user.py",
+                "User"
+            ),
+            files(&["app/models.py", "app/models/__init__.py"]),
+        );
+        assert_eq!(
+            resolve(
+                "from . import helper",
+                "Python",
+                "app/views/user.py",
+                "helper"
+            ),
+            files(&["app/views/__init__.py"]),
+        );
+
+        let target = resolve("from app.models import User", "Python", "x.py", "User").unwrap();
+        assert!(target.matches("src/app/models.py"));
+        assert!(!target.matches("src/otherapp/models.py"));
+    }
+
+    #[test]
+    fn go_packages() {
+        let src = r#"package main
+
+import (
+	"fmt"
+	cfg "github.com/acme/app/internal/config"
+)
+
+func main() { fmt.Println(cfg.Load()) }
+"#;
+        let start = src.find("Load").unwrap();
+        let target = resolve_qualified(src, "Go", start..start + 4).unwrap();
+
+        assert_eq!(
+            target,
+            ImportTarget::Package("github.com/acme/app/internal/config".into())
+        );
+        assert!(target.matches("internal/config/load.go"));
+        assert!(!target.matches("internal/config/sub/load.go"));
+        assert!(!target.matches("config.go"));
+    }
+}
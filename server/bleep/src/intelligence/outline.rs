@@ -0,0 +1,194 @@
+//! Build the outline of a file: its definitions, nested by the declarations that enclose them.
+//!
+//! The scope graph only records the range of the name of each definition. The range of the whole
+//! declaration, which is what encloses nested definitions, is taken from the syntax tree.
+
+use std::cmp::Reverse;
+
+use serde::Serialize;
+use tree_sitter::Node;
+
+use super::{NodeKind, ScopeGraph, TreeSitterFile};
+use crate::text_range::TextRange;
+
+/// A definition in the outline of a file.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct OutlineSymbol {
+    pub name: String,
+
+    /// The symbol kind, from the namespaces of the language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+
+    /// The range of the name of this definition
+    pub range: TextRange,
+
+    /// The range of the declaration that introduces this definition
+    pub extent: TextRange,
+
+    pub children: Vec<OutlineSymbol>,
+}
+
+/// The definitions of `graph`, nested by enclosing declaration, in order of appearance.
+///
+/// If `src` cannot be parsed, every definition is listed at the top level.
+pub fn outline(src: &str, lang_id: &str, graph: &ScopeGraph) -> Vec<OutlineSymbol> {
+    let file = TreeSitterFile::try_build(src.as_bytes(), lang_id).ok();
+    let root = file.as_ref().map(|f| f.tree.root_node());
+
+    let mut defs = graph
+        .graph
+        .node_indices()
+        .filter_map(|idx| match &graph.graph[idx] {
+            NodeKind::Def(def) => Some((idx, def.range)),
+            _ => None,
+        })
+        .filter_map(|(idx, range)| {
+            let name = src.get(range.start.byte..range.end.byte)?.to_owned();
+            let extent = root
+                .and_then(|root| declaration_range(root, range))
+                .unwrap_or(range);
+
+            Some(OutlineSymbol {
+                name,
+                kind: graph.symbol_name_of(idx),
+                range,
+                extent,
+                children: vec![],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Outer declarations come before the ones they enclose.
+    defs.sort_by_key(|def| (def.extent.start, Reverse(def.extent.end), def.range));
+
+    let mut top_level = vec![];
+    let mut ancestors: Vec<OutlineSymbol> = vec![];
+    for def in defs {
+        // Definitions introduced by the same pattern, like `const [a, b] = pair;`, are siblings.
+        while let Some(parent) = ancestors.last() {
+            if parent.extent.contains(&def.range) && parent.extent != def.extent {
+                break;
+            }
+
+            let done = ancestors.pop().unwrap();
+            attach(&mut top_level, &mut ancestors, done);
+        }
+
+        ancestors.push(def);
+    }
+
+    while let Some(done) = ancestors.pop() {
+        attach(&mut top_level, &mut ancestors, done);
+    }
+
+    top_level
+}
+
+fn attach(
+    top_level: &mut Vec<OutlineSymbol>,
+    ancestors: &mut [OutlineSymbol],
+    symbol: OutlineSymbol,
+) {
+    match ancestors.last_mut() {
+        Some(parent) => parent.children.push(symbol),
+        None => top_level.push(symbol),
+    }
+}
+
+/// The range of the declaration whose name is at `range`.
+///
+/// This is the parent of the name, or the declaration around a C-style declarator, as in
+/// `int *f(void) { .. }`.
+fn declaration_range(root: Node<'_>, range: TextRange) -> Option<TextRange> {
+    let name = root.descendant_for_byte_range(range.start.byte, range.end.byte)?;
+    let mut declaration = name.parent()?;
+
+    while declaration.kind().ends_with("_declarator") && declaration.kind() != "variable_declarator"
+    {
+        match declaration.parent() {
+            Some(parent) => declaration = parent,
+            None => break,
+        }
+    }
+
+    Some(declaration.range().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// The outline as `kind name` lines, indented by depth.
+    fn render(src: &str, lang_id: &str) -> String {
+        fn render_into(out: &mut String, symbols: &[OutlineSymbol], depth: usize) {
+            for symbol in symbols {
+                let kind = symbol.kind.unwrap_or("?");
+                out.push_str(&format!("{}{kind} {}\n", "  ".repeat(depth), symbol.name));
+                render_into(out, &symbol.children, depth + 1);
+            }
+        }
+
+        let graph = TreeSitterFile::try_build(src.as_bytes(), lang_id)
+            .and_then(TreeSitterFile::scope_graph)
+            .unwrap();
+
+        let mut out = String::new();
+        render_into(&mut out, &outline(src, lang_id, &graph), 0);
+        out
+    }
+
+    #[test]
+    fn rust_items() {
+        let src = r#"
+struct Point {
+    x: u8,
+}
+
+impl Point {
+    fn norm(self, scale: u8) -> u8 {
+        let sum = self.x * scale;
+        sum
+    }
+}
+
+const ORIGIN: u8 = 0;
+"#;
+
+        assert_eq!(
+            render(src, "Rust"),
+            "\
+struct Point
+  field x
+function norm
+  variable self
+  variable scale
+  variable sum
+const ORIGIN
+"
+        );
+    }
+
+    #[test]
+    fn javascript_siblings() {
+        let src = r#"
+const [a, b] = pair;
+
+function f(x) {
+    const y = x;
+}
+"#;
+
+        assert_eq!(
+            render(src, "JavaScript"),
+            "\
+constant a
+constant b
+function f
+  variable x
+  constant y
+"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Precise code navigation data, ingested from the SCIP indexes and LSIF dumps that
+//! language-specific indexers produce.
+//!
+//! Both formats are flattened into a list of occurrences of symbols, where references and
+//! definitions of the same entity share a symbol. Symbols of exported entities are global, and
+//! link occurrences across indexes, and so across repositories.
+
+mod lsif;
+
+use std::ops::Range;
+
+use anyhow::{Context, Result};
+use protobuf::Message;
+
+/// File names of indexes that are ingested when found at the root of a repository.
+pub const INDEX_FILE_NAMES: &[&str] = &["index.scip", "dump.lsif"];
+
+/// An occurrence of a symbol in an indexed document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreciseOccurrence {
+    pub relative_path: String,
+    pub symbol: String,
+    /// Whether `symbol` identifies the same entity in every index, rather than in this one only
+    pub global: bool,
+    pub definition: bool,
+    pub range: LineRange,
+}
+
+/// The code units that the columns of a range count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    /// The encoding of LSP positions, and so of LSIF dumps
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    pub fn parse(encoding: &str) -> Option<Self> {
+        match encoding {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        }
+    }
+
+    fn len(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// A range of 0-indexed lines and columns, where columns count code units of `encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub encoding: PositionEncoding,
+}
+
+impl LineRange {
+    /// Whether the byte at `offset` in `content` is inside this range, which excludes its end.
+    pub fn contains(&self, content: &str, offset: usize) -> bool {
+        self.to_byte_range(content)
+            .map(|range| range.contains(&offset))
+            .unwrap_or_default()
+    }
+
+    /// The byte range this covers in `content`, or `None` if it's out of bounds.
+    pub fn to_byte_range(&self, content: &str) -> Option<Range<usize>> {
+        let start = byte_offset(content, self.start_line, self.start_column, self.encoding)?;
+        let end = byte_offset(content, self.end_line, self.end_column, self.encoding)?;
+        (start <= end).then_some(start..end)
+    }
+}
+
+fn byte_offset(
+    content: &str,
+    line: usize,
+    column: usize,
+    encoding: PositionEncoding,
+) -> Option<usize> {
+    let line_start = match line {
+        0 => 0,
+        _ => content.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let text = content[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in text.char_indices() {
+        if units >= column {
+            return Some(line_start + i);
+        }
+        units += encoding.len(c);
+    }
+
+    Some(line_start + text.len())
+}
+
+/// Parse a SCIP index, or an LSIF dump in either its line-delimited or array form.
+///
+/// Paths in the index are relative to `root`, a directory of the repository.
+pub fn parse(data: &[u8], root: &str) -> Result<Vec<PreciseOccurrence>> {
+    let root = root.trim_matches('/');
+    let is_json = data
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map(|b| *b == b'{' || *b == b'[')
+        .unwrap_or_default();
+
+    if is_json {
+        lsif::parse(data, root)
+    } else {
+        parse_scip(data, root)
+    }
+}
+
+fn parse_scip(data: &[u8], root: &str) -> Result<Vec<PreciseOccurrence>> {
+    use scip::types::{Index, PositionEncoding as ScipEncoding, SymbolRole};
+
+    let index = Index::parse_from_bytes(data).context("invalid SCIP index")?;
+    let mut occurrences = vec![];
+
+    for document in index.documents {
+        let relative_path = join(root, &document.relative_path);
+        let encoding = match document.position_encoding.enum_value_or_default() {
+            ScipEncoding::UTF8CodeUnitOffsetFromLineStart => PositionEncoding::Utf8,
+            ScipEncoding::UTF32CodeUnitOffsetFromLineStart => PositionEncoding::Utf32,
+            // indexers that don't specify an encoding predate the field, and follow LSP
+            _ => PositionEncoding::Utf16,
+        };
+
+        for occurrence in document.occurrences {
+            let [start_line, start_column, end_line, end_column] = match occurrence.range[..] {
+                [line, start, end] => [line, start, line, end],
+                [start_line, start, end_line, end] => [start_line, start, end_line, end],
+                _ => continue,
+            }
+            .map(|n| n.max(0) as usize);
+
+            if occurrence.symbol.is_empty() {
+                continue;
+            }
+
+            // local symbols are only unique within a document
+            let global = !occurrence.symbol.starts_with("local ");
+            let symbol = if global {
+                unversioned(&occurrence.symbol)
+            } else {
+                format!("{relative_path} {}", occurrence.symbol)
+            };
+
+            occurrences.push(PreciseOccurrence {
+                relative_path: relative_path.clone(),
+                symbol,
+                global,
+                definition: (occurrence.symbol_roles & SymbolRole::Definition as i32) != 0,
+                range: LineRange {
+                    start_line,
+                    start_column,
+                    end_line,
+                    end_column,
+                    encoding,
+                },
+            });
+        }
+    }
+
+    Ok(occurrences)
+}
+
+/// Drop the package version of a SCIP symbol, so that references to a dependency match its
+/// definitions in the index of the dependency, whichever version it is at.
+///
+/// Symbols are formatted as `<scheme> <manager> <package> <version> <descriptors>`, where spaces
+/// inside a field are escaped by doubling them.
+fn unversioned(symbol: &str) -> String {
+    let mut fields = vec![];
+    let mut rest = symbol;
+
+    while fields.len() < 4 {
+        let bytes = rest.as_bytes();
+        let mut i = 0;
+        let end = loop {
+            match (bytes.get(i), bytes.get(i + 1)) {
+                (Some(b' '), Some(b' ')) => i += 2,
+                (Some(b' '), _) => break i,
+                (Some(_), _) => i += 1,
+                (None, _) => return symbol.to_owned(),
+            }
+        };
+
+        fields.push(&rest[..end]);
+        rest = &rest[end + 1..];
+    }
+
+    format!("{} {} {} . {rest}", fields[0], fields[1], fields[2])
+}
+
+fn join(root: &str, path: &str) -> String {
+    if root.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{root}/{path}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn line_ranges() {
+        let content = "fn main() {\n    let é = \"😀\"; x\n}\n";
+        let x = content.find('x').unwrap();
+
+        for (encoding, column) in [
+            (PositionEncoding::Utf8, 21),
+            (PositionEncoding::Utf16, 18),
+            (PositionEncoding::Utf32, 17),
+        ] {
+            let range = LineRange {
+                start_line: 1,
+                start_column: column,
+                end_line: 1,
+                end_column: column + 1,
+                encoding,
+            };
+
+            assert_eq!(range.to_byte_range(content), Some(x..x + 1), "{encoding:?}");
+            assert!(range.contains(content, x));
+            assert!(!range.contains(content, x + 1));
+            assert!(!range.contains(content, x - 1));
+        }
+    }
+
+    #[test]
+    fn unversioned_symbols() {
+        assert_eq!(
+            unversioned("scip-typescript npm @acme/ui 1.2.0 src/`button.ts`/Button#"),
+            "scip-typescript npm @acme/ui . src/`button.ts`/Button#"
+        );
+        assert_eq!(
+            unversioned("rust-analyzer cargo my  crate 0.1.0 foo/Bar#"),
+            "rust-analyzer cargo my  crate . foo/Bar#"
+        );
+        assert_eq!(unversioned("malformed"), "malformed");
+    }
+}
@@ -0,0 +1,315 @@
+//! Parse LSIF dumps, which describe a graph of documents, the ranges in them, and the results of
+//! LSP requests on those ranges.
+//!
+//! Ranges that lead to the same result set through `next` edges are occurrences of the same
+//! entity. A moniker attached to any of them names the entity across dumps.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{join, LineRange, PositionEncoding, PreciseOccurrence};
+
+/// The most `next` edges followed from a range to find its result set.
+const MAX_CHAIN_LEN: usize = 32;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Element {
+    id: Value,
+    #[serde(rename = "type")]
+    element_type: String,
+    label: String,
+
+    // vertices
+    uri: Option<String>,
+    start: Option<Position>,
+    end: Option<Position>,
+    project_root: Option<String>,
+    scheme: Option<String>,
+    identifier: Option<String>,
+    kind: Option<String>,
+
+    // edges
+    out_v: Option<Value>,
+    in_v: Option<Value>,
+    in_vs: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize)]
+struct Position {
+    line: usize,
+    character: usize,
+}
+
+struct Moniker {
+    symbol: String,
+    global: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    project_root: Option<String>,
+    /// Document URIs, by ID
+    documents: HashMap<String, String>,
+    ranges: HashMap<String, LineRange>,
+    /// The document of each range
+    contained_in: HashMap<String, String>,
+    next: HashMap<String, String>,
+    monikers: HashMap<String, Moniker>,
+    /// The moniker attached to a range or result set
+    moniker_of: HashMap<String, String>,
+    definition_results: HashSet<String>,
+    /// `item` edges, from results to ranges
+    items: Vec<(String, Vec<String>)>,
+}
+
+impl Graph {
+    fn add(&mut self, element: Element) {
+        let id = key(&element.id);
+        let out_v = element.out_v.as_ref().map(key);
+        let in_vs = element
+            .in_v
+            .iter()
+            .chain(element.in_vs.iter().flatten())
+            .map(key);
+
+        match (element.element_type.as_str(), element.label.as_str()) {
+            ("vertex", "metaData") => self.project_root = element.project_root,
+            ("vertex", "document") => {
+                if let Some(uri) = element.uri {
+                    self.documents.insert(id, uri);
+                }
+            }
+            ("vertex", "range") => {
+                if let (Some(start), Some(end)) = (element.start, element.end) {
+                    let range = LineRange {
+                        start_line: start.line,
+                        start_column: start.character,
+                        end_line: end.line,
+                        end_column: end.character,
+                        encoding: PositionEncoding::Utf16,
+                    };
+                    self.ranges.insert(id, range);
+                }
+            }
+            ("vertex", "definitionResult") => {
+                self.definition_results.insert(id);
+            }
+            ("vertex", "moniker") => {
+                if let (Some(scheme), Some(identifier)) = (element.scheme, element.identifier) {
+                    let moniker = Moniker {
+                        symbol: format!("lsif {scheme} {identifier}"),
+                        global: element.kind.as_deref() != Some("local"),
+                    };
+                    self.monikers.insert(id, moniker);
+                }
+            }
+            ("edge", "contains") => {
+                if let Some(out_v) = out_v {
+                    for in_v in in_vs {
+                        self.contained_in.insert(in_v, out_v.clone());
+                    }
+                }
+            }
+            ("edge", "next") => {
+                if let (Some(out_v), Some(in_v)) = (out_v, in_vs.last()) {
+                    self.next.insert(out_v, in_v);
+                }
+            }
+            ("edge", "moniker") => {
+                if let (Some(out_v), Some(in_v)) = (out_v, in_vs.last()) {
+                    self.moniker_of.insert(out_v, in_v);
+                }
+            }
+            ("edge", "item") => {
+                if let Some(out_v) = out_v {
+                    self.items.push((out_v, in_vs.collect()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn occurrences(self, root: &str) -> Vec<PreciseOccurrence> {
+        let definitions = self
+            .items
+            .iter()
+            .filter(|(result, _)| self.definition_results.contains(result))
+            .flat_map(|(_, ranges)| ranges)
+            .collect::<HashSet<_>>();
+
+        let project_root = self.project_root.as_deref().unwrap_or_default();
+        let paths = self
+            .documents
+            .iter()
+            .filter_map(|(id, uri)| Some((id, join(root, &relative_path(uri, project_root)?))))
+            .collect::<HashMap<_, _>>();
+
+        let mut occurrences = vec![];
+        for (id, range) in &self.ranges {
+            let Some(path) = self.contained_in.get(id).and_then(|doc| paths.get(doc)) else {
+                continue;
+            };
+
+            let mut node = id;
+            let mut moniker = self.moniker(node);
+            for _ in 0..MAX_CHAIN_LEN {
+                let Some(next) = self.next.get(node) else {
+                    break;
+                };
+
+                node = next;
+                moniker = moniker.or_else(|| self.moniker(node));
+            }
+
+            let (symbol, global) = match moniker {
+                Some(moniker) if moniker.global => (moniker.symbol.clone(), true),
+                // result sets are only unique within a dump
+                _ => (format!("{root}#{node}"), false),
+            };
+
+            occurrences.push(PreciseOccurrence {
+                relative_path: path.clone(),
+                symbol,
+                global,
+                definition: definitions.contains(id),
+                range: *range,
+            });
+        }
+
+        occurrences
+    }
+
+    fn moniker(&self, id: &str) -> Option<&Moniker> {
+        self.monikers.get(self.moniker_of.get(id)?)
+    }
+}
+
+/// Parse a dump, which is either a JSON array of elements, or one element per line.
+pub(super) fn parse(data: &[u8], root: &str) -> Result<Vec<PreciseOccurrence>> {
+    let mut graph = Graph::default();
+    let is_array = data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[');
+
+    if is_array {
+        let elements: Vec<Element> = serde_json::from_slice(data).context("invalid LSIF dump")?;
+        for element in elements {
+            graph.add(element);
+        }
+    } else {
+        for (i, line) in data.split(|b| *b == b'\n').enumerate() {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            let element = serde_json::from_slice(line)
+                .with_context(|| format!("invalid LSIF element on line {}", i + 1))?;
+            graph.add(element);
+        }
+    }
+
+    Ok(graph.occurrences(root))
+}
+
+fn key(id: &Value) -> String {
+    match id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+/// The path of a document relative to the project root, both of which are URIs.
+fn relative_path(uri: &str, project_root: &str) -> Option<String> {
+    let path = uri
+        .strip_prefix(project_root.trim_end_matches('/'))?
+        .strip_prefix('/')?;
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' => path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    Some(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_dump() {
+        let dump = r#"
+{"id":1,"type":"vertex","label":"metaData","version":"0.4.3","projectRoot":"file:///ci/repo","positionEncoding":"utf-16"}
+{"id":2,"type":"vertex","label":"document","uri":"file:///ci/repo/src/a%20b.ts","languageId":"typescript"}
+{"id":3,"type":"vertex","label":"document","uri":"file:///ci/repo/src/c.ts","languageId":"typescript"}
+{"id":4,"type":"vertex","label":"resultSet"}
+{"id":5,"type":"vertex","label":"range","start":{"line":0,"character":16},"end":{"line":0,"character":19}}
+{"id":6,"type":"vertex","label":"range","start":{"line":2,"character":4},"end":{"line":2,"character":7}}
+{"id":7,"type":"edge","label":"next","outV":5,"inV":4}
+{"id":8,"type":"edge","label":"next","outV":6,"inV":4}
+{"id":9,"type":"vertex","label":"definitionResult"}
+{"id":10,"type":"edge","label":"textDocument/definition","outV":4,"inV":9}
+{"id":11,"type":"edge","label":"item","outV":9,"inVs":[5],"document":2}
+{"id":12,"type":"vertex","label":"moniker","scheme":"tsc","identifier":"src/a b:foo","kind":"export"}
+{"id":13,"type":"edge","label":"moniker","outV":4,"inV":12}
+{"id":14,"type":"edge","label":"contains","outV":2,"inVs":[5]}
+{"id":15,"type":"edge","label":"contains","outV":3,"inVs":[6]}
+"#;
+
+        let mut occurrences = parse(dump.as_bytes(), "web").unwrap();
+        occurrences.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+        assert_eq!(
+            occurrences,
+            [
+                PreciseOccurrence {
+                    relative_path: "web/src/a b.ts".into(),
+                    symbol: "lsif tsc src/a b:foo".into(),
+                    global: true,
+                    definition: true,
+                    range: LineRange {
+                        start_line: 0,
+                        start_column: 16,
+                        end_line: 0,
+                        end_column: 19,
+                        encoding: PositionEncoding::Utf16,
+                    },
+                },
+                PreciseOccurrence {
+                    relative_path: "web/src/c.ts".into(),
+                    symbol: "lsif tsc src/a b:foo".into(),
+                    global: true,
+                    definition: false,
+                    range: LineRange {
+                        start_line: 2,
+                        start_column: 4,
+                        end_line: 2,
+                        end_column: 7,
+                        encoding: PositionEncoding::Utf16,
+                    },
+                },
+            ]
+        );
+    }
+}
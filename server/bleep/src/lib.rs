@@ -25,7 +25,13 @@ use state::PersistedState;
 use std::fs::canonicalize;
 use user::UserProfile;
 
-use crate::{background::SyncQueue, indexes::Indexes, semantic::Semantic, state::RepositoryPool};
+use crate::{
+    background::SyncQueue,
+    indexes::Indexes,
+    remotes::{gitea::Gitea, gitlab::Gitlab},
+    semantic::Semantic,
+    state::RepositoryPool,
+};
 use anyhow::{bail, Result};
 use axum::extract::FromRef;
 
@@ -248,7 +254,10 @@ impl Application {
         } else {
             if !self.config.disable_background {
                 tokio::spawn(periodic::sync_github_status(self.clone()));
+                tokio::spawn(periodic::sync_provider_status::<Gitlab>(self.clone()));
+                tokio::spawn(periodic::sync_provider_status::<Gitea>(self.clone()));
                 tokio::spawn(periodic::check_repo_updates(self.clone()));
+                tokio::spawn(periodic::rerun_saved_searches(self.clone()));
                 tokio::spawn(periodic::log_and_branch_rotate(self.clone()));
             }
 
@@ -1,5 +1,7 @@
 mod logrotate;
 mod remotes;
+mod saved_searches;
 
 pub(crate) use logrotate::*;
 pub(crate) use remotes::*;
+pub(crate) use saved_searches::*;
@@ -16,7 +16,10 @@ use tracing::{debug, error, info, warn};
 
 use crate::{
     env::Feature,
-    remotes,
+    remotes::{
+        self,
+        provider::{Api, Provider},
+    },
     repo::{Backend, RepoRef, SyncStatus},
     Application,
 };
@@ -96,6 +99,48 @@ pub(crate) async fn sync_github_status(app: Application) {
     }
 }
 
+/// Refresh the repository lists of every instance of a provider that's accessed with a token,
+/// such as GitLab, and remove the credentials of instances that reject them.
+pub(crate) async fn sync_provider_status<A: Api>(app: Application) {
+    const POLL_PERIOD: Duration = POLL_INTERVAL_MINUTE[1];
+    const LIVENESS: Duration = Duration::from_secs(1);
+
+    loop {
+        let instances = app.credentials.instances::<A>();
+        let Some(updated) = app.credentials.updated(&A::BACKEND) else {
+            sleep(LIVENESS).await;
+            continue;
+        };
+
+        let refreshed = instances.refresh().await;
+        let rejected = refreshed.len() < instances.len();
+        debug!(provider = A::NAME, "repo lists updated");
+
+        app.credentials.set_instances(refreshed);
+        if rejected {
+            if let Err(err) = app
+                .config
+                .source
+                .save_credentials(&app.credentials.serialize().await)
+            {
+                error!(?err, "failed to save credentials to disk");
+            }
+        }
+
+        // swallow the event that's generated from this update
+        _ = updated.recv_async().await;
+
+        tokio::select! {
+            _ = sleep(POLL_PERIOD) => {
+                debug!(provider = A::NAME, "timeout expired; refreshing repositories");
+            },
+            _ = updated.recv_async() => {
+                debug!(provider = A::NAME, "credentials changed; refreshing repositories");
+            },
+        }
+    }
+}
+
 async fn update_credentials(app: &Application) {
     if app.env.allow(Feature::GithubInstallation) {
         match app.credentials.github().and_then(|c| c.expiry()) {
@@ -138,7 +183,7 @@ async fn update_credentials(app: &Application) {
 }
 
 pub(crate) async fn check_repo_updates(app: Application) {
-    while app.credentials.github().is_none() {
+    while app.credentials.is_empty() {
         sleep(Duration::from_millis(100)).await
     }
 
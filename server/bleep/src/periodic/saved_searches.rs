@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::TryStreamExt;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, warn};
+
+use crate::{
+    background::{Progress, ProgressEvent},
+    db::{SavedMatch, SavedSearchDiff, SavedSearches},
+    query::execute::{ApiQuery, QueryResult},
+    repo::{RepoRef, SyncStatus},
+    Application,
+};
+
+/// Re-run every saved search once a repository has been synced successfully, and report changes
+/// in their matches in that repository.
+pub(crate) async fn rerun_saved_searches(app: Application) {
+    let mut progress = app.sync_queue.subscribe();
+
+    loop {
+        let reporef = match progress.recv().await {
+            Ok(Progress {
+                reporef,
+                event: ProgressEvent::StatusChange(SyncStatus::Done),
+                ..
+            }) => reporef,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(skipped, "missed sync progress events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let searches = match SavedSearches::new(&app.sql).list().await {
+            Ok(searches) => searches,
+            Err(err) => {
+                error!(?err, "failed to load saved searches");
+                continue;
+            }
+        };
+
+        for search in searches {
+            match run_saved_search(&app, search.id, &search.query, Some(&reporef)).await {
+                Ok(diff) if diff.is_empty() => {
+                    debug!(id = search.id, "saved search unchanged");
+                }
+                Ok(diff) => {
+                    app.sync_queue
+                        .notify_saved_search(reporef.clone(), search.id, &diff);
+                }
+                Err(err) => {
+                    warn!(?err, id = search.id, "failed to run saved search");
+                }
+            }
+        }
+    }
+}
+
+/// Run a saved search against the current index, and store the changes in its matches since the
+/// last run.
+///
+/// Only `repo_ref` is searched if it is set, as the other repositories haven't changed.
+pub(crate) async fn run_saved_search(
+    app: &Application,
+    id: i64,
+    query: &str,
+    repo_ref: Option<&RepoRef>,
+) -> Result<SavedSearchDiff> {
+    let mut q = ApiQuery::new(query.to_owned());
+    if let Some(repo_ref) = repo_ref {
+        q = q.in_repo(repo_ref);
+    }
+
+    let results = q
+        .query_all(Arc::clone(&app.indexes))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let matches = results.iter().flat_map(matches_of).collect::<Vec<_>>();
+    let repo_ref = repo_ref.map(RepoRef::to_string);
+
+    SavedSearches::new(&app.sql)
+        .record_run(id, repo_ref.as_deref(), &matches)
+        .await
+}
+
+/// Every highlighted line in a query result.
+///
+/// Only content matches are tracked, as other results don't point to lines in a file.
+fn matches_of(result: &QueryResult) -> Vec<SavedMatch> {
+    let QueryResult::Snippets(file) = result else {
+        return Vec::new();
+    };
+
+    let mut matches = file
+        .snippets
+        .iter()
+        .flat_map(|snippet| {
+            snippet.highlights.iter().map(|highlight| {
+                let before = &snippet.data[..highlight.start];
+                let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                let line_end = snippet.data[highlight.start..]
+                    .find('\n')
+                    .map(|i| i + highlight.start)
+                    .unwrap_or(snippet.data.len());
+
+                SavedMatch {
+                    repo_ref: file.repo_ref.clone(),
+                    relative_path: file.relative_path.clone(),
+                    line: snippet.line_range.start + before.matches('\n').count(),
+                    text: snippet.data[line_start..line_end].trim().to_owned(),
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Several highlights on the same line are a single match.
+    matches.dedup_by(|a, b| a.line == b.line);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snippet::{SnippedFile, Snippet};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn matches_per_line() {
+        let result = QueryResult::Snippets(SnippedFile {
+            relative_path: "src/lib.rs".into(),
+            repo_name: "bloop".into(),
+            repo_ref: "github.com/bloopai/bloop".into(),
+            lang: Some("rust".into()),
+            snippets: vec![Snippet {
+                data: "fn a() {\n    old(); old();\n}".into(),
+                highlights: vec![13..16, 20..23],
+                symbols: vec![],
+                line_range: 9..11,
+            }],
+        });
+
+        assert_eq!(
+            matches_of(&result),
+            vec![SavedMatch {
+                repo_ref: "github.com/bloopai/bloop".into(),
+                relative_path: "src/lib.rs".into(),
+                line: 10,
+                text: "old(); old();".into(),
+            }]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     mem,
+    ops::Bound,
 };
 
 use anyhow::{Context, Result};
@@ -9,7 +10,7 @@ use compact_str::CompactString;
 use either::Either;
 use smallvec::SmallVec;
 use tantivy::{
-    query::{AllQuery, BooleanQuery, BoostQuery, TermQuery},
+    query::{AllQuery, BooleanQuery, BoostQuery, Occur, RangeQuery, TermQuery},
     schema::{Field, IndexRecordOption},
     Index, Term,
 };
@@ -27,15 +28,25 @@ enum Extraction<'a> {
 
     /// Match a string against a tantivy `bytes` field.
     ByteString(&'a Cow<'a, str>),
+
+    /// Match a range of values against a tantivy `u64` field.
+    Range(Bound<u64>, Bound<u64>),
+
+    /// Match a whole string against an untokenized tantivy `text` field.
+    Term(&'a Cow<'a, str>),
 }
 
 /// A closure that tries to pull out an `Extraction` variant, given a `Query` reference.
 type Extractor = dyn for<'a> FnMut(&'a Query<'a>) -> Option<Extraction<'a>>;
 
+/// A closure that pulls out all negated `Extraction`s for a field, given a `Query` reference.
+type NegationExtractor = dyn for<'a> FnMut(&'a Query<'a>) -> Vec<Extraction<'a>>;
+
 #[derive(Default)]
 pub struct Compiler {
     priority: HashSet<Field>,
     extractors: HashMap<Field, Box<Extractor>>,
+    negations: HashMap<Field, Box<NegationExtractor>>,
 }
 
 impl Compiler {
@@ -81,6 +92,58 @@ impl Compiler {
         self
     }
 
+    /// Add a `u64` range field to the compiler.
+    ///
+    /// The field must be indexed in the Tantivy schema for range queries to match.
+    pub fn range<F>(mut self, tantivy_field: Field, mut extractor: F) -> Self
+    where
+        F: for<'b> FnMut(&'b Query<'b>) -> Option<(Bound<u64>, Bound<u64>)> + 'static,
+    {
+        self.extractors.insert(
+            tantivy_field,
+            Box::new(move |q| extractor(q).map(|(lower, upper)| Extraction::Range(lower, upper))),
+        );
+        self
+    }
+
+    /// Add an exact term field to the compiler.
+    ///
+    /// Matches `Cow<str>` as a single term against a tantivy `text` field indexed as `STRING`.
+    pub fn term<F>(mut self, tantivy_field: Field, mut extractor: F) -> Self
+    where
+        F: for<'b> FnMut(&'b Query<'b>) -> Option<&'b Cow<'b, str>> + 'static,
+    {
+        self.extractors.insert(
+            tantivy_field,
+            Box::new(move |q| extractor(q).map(Extraction::Term)),
+        );
+        self
+    }
+
+    /// Add a negated byte string field to the compiler.
+    ///
+    /// Documents matching any of the returned byte strings are excluded with a `MustNot` clause.
+    ///
+    /// There is no equivalent for literals: they are matched against the trigram index, which
+    /// only approximates the text, so excluding its matches would drop valid results. Negated
+    /// literals are checked precisely by the collector instead.
+    pub fn negated_byte_string<F>(mut self, tantivy_field: Field, mut extractor: F) -> Self
+    where
+        F: for<'b> FnMut(&'b Query<'b>) -> Vec<&'b Cow<'b, str>> + 'static,
+    {
+        self.negations.insert(
+            tantivy_field,
+            Box::new(move |q| {
+                extractor(q)
+                    .into_iter()
+                    .map(Extraction::ByteString)
+                    .collect()
+            }),
+        );
+
+        self
+    }
+
     /// Compile a list of queries into a single Tantivy query that matches any
     /// of them.
     pub fn compile<'a, I>(mut self, queries: I, index: &Index) -> Result<DynQuery>
@@ -90,62 +153,39 @@ impl Compiler {
         let mut sub_queries: SmallVec<[DynQuery; 2]> = SmallVec::new();
 
         for query in queries {
-            let mut intersection = Vec::new();
+            let mut clauses = Vec::new();
 
             for (field, extractor) in &mut self.extractors {
                 let Some(extraction) = extractor(query) else {
                     continue
                 };
 
-                let field_query = match extraction {
-                    Extraction::Literal(Literal::Plain(text)) => {
-                        let tokenizer = index
-                            .tokenizer_for_field(*field)
-                            .context("field is missing tokenizer")?;
-
-                        let mut token_stream = tokenizer.token_stream(&text);
-                        let tokens = std::iter::from_fn(move || {
-                            token_stream.next().map(|tok| CompactString::new(&tok.text))
-                        });
-
-                        let terms = if query.is_case_sensitive() {
-                            tokens.map(|s| str_to_query(*field, &s)).collect::<Vec<_>>()
-                        } else {
-                            tokens
-                                .map(|s| {
-                                    let terms = case_permutations(&s)
-                                        .map(|s| str_to_query(*field, &s))
-                                        .collect();
-
-                                    Box::new(BooleanQuery::union(terms)) as DynQuery
-                                })
-                                .collect()
-                        };
-
-                        let mut field_query: DynQuery = Box::new(BooleanQuery::intersection(terms));
-
-                        if self.priority.contains(field) {
-                            field_query = Box::new(BoostQuery::new(field_query, 10.0));
-                        }
-
-                        field_query
-                    }
-                    Extraction::Literal(Literal::Regex(regex)) => {
-                        let plan = planner::plan(&regex)?;
-                        plan_to_query(plan, *field, query.is_case_sensitive())
-                    }
-
-                    Extraction::ByteString(bs) => {
-                        let term = Term::from_field_bytes(*field, bs.as_bytes());
-                        let q = TermQuery::new(term, IndexRecordOption::Basic);
-                        Box::new(q) as DynQuery
-                    }
-                };
+                let is_plain = matches!(extraction, Extraction::Literal(Literal::Plain(..)));
+                let mut field_query = extraction_to_query(extraction, *field, query, index)?;
+
+                if is_plain && self.priority.contains(field) {
+                    field_query = Box::new(BoostQuery::new(field_query, 10.0));
+                }
+
+                clauses.push((Occur::Must, field_query));
+            }
+
+            let has_positive_clauses = !clauses.is_empty();
+
+            for (field, extractor) in &mut self.negations {
+                for extraction in extractor(query) {
+                    let field_query = extraction_to_query(extraction, *field, query, index)?;
+                    clauses.push((Occur::MustNot, field_query));
+                }
+            }
 
-                intersection.push(field_query);
+            // A boolean query consisting only of `MustNot` clauses matches nothing, so we start
+            // from the full document set instead.
+            if !has_positive_clauses && !clauses.is_empty() {
+                clauses.push((Occur::Must, Box::new(AllQuery)));
             }
 
-            sub_queries.push(Box::new(BooleanQuery::intersection(intersection)));
+            sub_queries.push(Box::new(BooleanQuery::new(clauses)));
         }
 
         Ok(if sub_queries.len() == 1 {
@@ -156,6 +196,61 @@ impl Compiler {
     }
 }
 
+fn extraction_to_query(
+    extraction: Extraction<'_>,
+    field: Field,
+    query: &Query<'_>,
+    index: &Index,
+) -> Result<DynQuery> {
+    Ok(match extraction {
+        Extraction::Literal(Literal::Plain(text)) => {
+            let tokenizer = index
+                .tokenizer_for_field(field)
+                .context("field is missing tokenizer")?;
+
+            let mut token_stream = tokenizer.token_stream(&text);
+            let tokens = std::iter::from_fn(move || {
+                token_stream.next().map(|tok| CompactString::new(&tok.text))
+            });
+
+            let terms = if query.is_case_sensitive() {
+                tokens.map(|s| str_to_query(field, &s)).collect::<Vec<_>>()
+            } else {
+                tokens
+                    .map(|s| {
+                        let terms = case_permutations(&s)
+                            .map(|s| str_to_query(field, &s))
+                            .collect();
+
+                        Box::new(BooleanQuery::union(terms)) as DynQuery
+                    })
+                    .collect()
+            };
+
+            Box::new(BooleanQuery::intersection(terms))
+        }
+        Extraction::Literal(Literal::Regex(regex)) => {
+            let plan = planner::plan(&regex)?;
+            plan_to_query(plan, field, query.is_case_sensitive())
+        }
+
+        Extraction::ByteString(bs) => {
+            let term = Term::from_field_bytes(field, bs.as_bytes());
+            let q = TermQuery::new(term, IndexRecordOption::Basic);
+            Box::new(q) as DynQuery
+        }
+
+        Extraction::Range(lower, upper) => {
+            Box::new(RangeQuery::new_u64_bounds(field, lower, upper))
+        }
+
+        Extraction::Term(s) => {
+            let term = Term::from_field_text(field, s);
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as DynQuery
+        }
+    })
+}
+
 fn plan_to_query(plan: planner::Fragment, field: Field, case_sensitive: bool) -> DynQuery {
     match plan {
         planner::Fragment::Literal(s) => {
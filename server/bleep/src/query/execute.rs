@@ -1,24 +1,43 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
     sync::Arc,
 };
 
-use super::{parser, ranking::DocumentTweaker};
+use super::{
+    parser,
+    ranking::{DocumentTweaker, RecencyScorer},
+    structural::StructuralPattern,
+};
 use crate::{
-    collector::{BytesFilterCollector, FrequencyCollector},
+    collector::{
+        self, BytesFilterCollector, FacetCollector, FieldsFilterCollector, FrequencyCollector,
+    },
     indexes::{
-        reader::{base_name, ContentReader, FileReader, OpenReader, RepoReader},
-        DocumentRead, File, Indexable, Indexer, Indexes, Repo,
+        reader::{
+            base_name, CommitReader, ContentReader, FileReader, OpenReader, RepoReader,
+            StructuralReader,
+        },
+        Commit, DocumentRead, File, Indexable, Indexer, Indexes, Repo, SearchScope,
     },
+    repo::RepoRef,
     snippet::{HighlightedString, SnippedFile, Snipper},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
-use regex::{bytes::RegexBuilder as ByteRegexBuilder, RegexBuilder};
+use futures::{Stream, StreamExt};
+use regex::{
+    bytes::{Regex as ByteRegex, RegexBuilder as ByteRegexBuilder},
+    RegexBuilder,
+};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use tantivy::collector::{MultiCollector, TopDocs};
+use tantivy::{
+    collector::{MultiCollector, TopDocs},
+    schema::Field,
+    Searcher, SegmentOrdinal,
+};
 
 const fn default_page_size() -> usize {
     100
@@ -39,7 +58,7 @@ fn div_ceil(a: usize, b: usize) -> usize {
     d + usize::from(r > 0)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ApiQuery {
     /// A query written in the bloop query language
     pub q: String,
@@ -68,6 +87,18 @@ pub struct ApiQuery {
     /// The number of lines of context in the snippet after the search result
     #[serde(alias = "ca", default = "default_context")]
     context_after: usize,
+
+    /// The view of the index searched, and its segment, used when streaming results
+    #[serde(skip)]
+    scope: Option<SearchScope>,
+
+    /// Whether to collect every result, in index order, instead of a ranked page of results.
+    #[serde(skip)]
+    all_results: bool,
+
+    /// The only repo to search, if set with `in_repo`
+    #[serde(skip)]
+    repo_ref: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -102,10 +133,38 @@ pub struct PagingMetadata {
     total_count: Option<usize>,
 }
 
+/// An event in a stream of query results.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryEvent {
+    /// Results from searching a single segment of an index with one reader.
+    Results {
+        reader: &'static str,
+        segment: Option<SegmentOrdinal>,
+        data: Vec<QueryResult>,
+    },
+
+    /// Totals across all readers and segments, sent once every search is done.
+    Stats {
+        total_count: usize,
+        stats: ResultStats,
+    },
+}
+
+/// Facet counts over every match of a query, not just the returned page.
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct ResultStats {
     pub lang: HashMap<String, usize>,
     pub repo: HashMap<String, usize>,
+    /// Top-level directories of matching paths
+    #[serde(default)]
+    pub dir: HashMap<String, usize>,
+    /// File extensions of matching paths, without the leading `.`
+    #[serde(default)]
+    pub ext: HashMap<String, usize>,
+    /// Branches that matching files can be found in
+    #[serde(default)]
+    pub branch: HashMap<String, usize>,
 }
 
 #[derive(Serialize)]
@@ -127,6 +186,9 @@ pub enum QueryResult {
     #[serde(rename = "dir")]
     Directory(DirectoryData),
 
+    #[serde(rename = "commit_result")]
+    CommitResult(CommitResultData),
+
     // Only returned by autocomplete
     #[serde(rename = "flag")]
     Flag(String),
@@ -148,6 +210,29 @@ pub struct FileResultData {
     repo_ref: String,
     lang: Option<String>,
     branches: String,
+    /// The last commit that changed the file, if it's known
+    last_commit: Option<LastCommitData>,
+}
+
+#[derive(Serialize)]
+pub struct LastCommitData {
+    author: String,
+    /// Commit time, in seconds since the unix epoch
+    date: u64,
+}
+
+#[derive(Serialize)]
+pub struct CommitResultData {
+    repo_name: String,
+    repo_ref: String,
+    /// Full hex ID of the commit
+    id: String,
+    author: String,
+    /// Commit time, in seconds since the unix epoch
+    date: u64,
+    message: HighlightedString,
+    /// Paths changed by the commit, relative to the repo root
+    paths: Vec<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -196,6 +281,29 @@ pub trait ExecuteQuery {
 }
 
 impl ApiQuery {
+    /// A query for the first page of results, with default options.
+    pub fn new(q: String) -> Self {
+        Self {
+            q,
+            page: 0,
+            page_size: default_page_size(),
+            calculate_totals: true,
+            context_before: default_context(),
+            context_after: default_context(),
+            scope: None,
+            all_results: false,
+            repo_ref: None,
+        }
+    }
+
+    /// Only search documents of one repo.
+    pub fn in_repo(self, repo_ref: &RepoRef) -> Self {
+        Self {
+            repo_ref: Some(repo_ref.to_string()),
+            ..self
+        }
+    }
+
     pub async fn query(self: Arc<Self>, indexes: Arc<Indexes>) -> Result<QueryResponse> {
         let query = self.q.clone();
         let compiled = parser::parse(&query)?;
@@ -236,6 +344,11 @@ impl ApiQuery {
             if ContentReader.query_matches(q) {
                 tracing::trace!("executing with ContentReader");
                 return ContentReader.execute(&indexes.file, &queries, &self).await;
+            } else if StructuralReader.query_matches(q) {
+                tracing::trace!("executing with StructuralReader");
+                return StructuralReader
+                    .execute(&indexes.file, &queries, &self)
+                    .await;
             } else if RepoReader.query_matches(q) {
                 tracing::trace!("executing with RepoReader");
                 return RepoReader.execute(&indexes.repo, &queries, &self).await;
@@ -245,12 +358,209 @@ impl ApiQuery {
             } else if OpenReader.query_matches(q) {
                 tracing::trace!("executing with OpenReader");
                 return OpenReader.execute(&indexes.file, &queries, &self).await;
+            } else if CommitReader.query_matches(q) {
+                tracing::trace!("executing with CommitReader");
+                return CommitReader.execute(&indexes.commit, &queries, &self).await;
             }
         }
 
         bail!("mangled query")
     }
 
+    /// Execute the query, and yield every result rather than a single page.
+    ///
+    /// As with `query`, only the reader that the first query targets is used. Each segment of the
+    /// index is searched once, as it was when the stream started, and its results are yielded in
+    /// index order rather than ranked.
+    pub fn query_all(self, indexes: Arc<Indexes>) -> impl Stream<Item = Result<QueryResult>> {
+        async_stream::try_stream! {
+            let q = ApiQuery {
+                all_results: true,
+                ..self
+            };
+            let mut queries = parser::parse(&q.q)?;
+            for query in &mut queries {
+                query.repo_ref = q.repo_ref.as_deref().map(Cow::Borrowed);
+            }
+
+            let file = indexes.file.searcher().await;
+            let repo = indexes.repo.searcher().await;
+            let commit = indexes.commit.searcher().await;
+
+            let responses = queries
+                .iter()
+                .find_map(|query| {
+                    if ContentReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "content",
+                                &ContentReader,
+                                &indexes.file,
+                                &file,
+                                &queries,
+                                &q,
+                                true,
+                            )
+                            .boxed(),
+                        )
+                    } else if StructuralReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "structural",
+                                &StructuralReader,
+                                &indexes.file,
+                                &file,
+                                &queries,
+                                &q,
+                                true,
+                            )
+                            .boxed(),
+                        )
+                    } else if RepoReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "repo",
+                                &RepoReader,
+                                &indexes.repo,
+                                &repo,
+                                &queries,
+                                &q,
+                                true,
+                            )
+                            .boxed(),
+                        )
+                    } else if FileReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "file",
+                                &FileReader,
+                                &indexes.file,
+                                &file,
+                                &queries,
+                                &q,
+                                true,
+                            )
+                            .boxed(),
+                        )
+                    } else if OpenReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "open",
+                                &OpenReader,
+                                &indexes.file,
+                                &file,
+                                &queries,
+                                &q,
+                                false,
+                            )
+                            .boxed(),
+                        )
+                    } else if CommitReader.query_matches(query) {
+                        Some(
+                            stream_reader(
+                                "commit",
+                                &CommitReader,
+                                &indexes.commit,
+                                &commit,
+                                &queries,
+                                &q,
+                                true,
+                            )
+                            .boxed(),
+                        )
+                    } else {
+                        None
+                    }
+                })
+                .ok_or_else(|| anyhow!("mangled query"))?;
+
+            for await response in responses {
+                let (_, _, response) = response?;
+                for result in response.data {
+                    yield result;
+                }
+            }
+        }
+    }
+
+    /// Execute the query, streaming results as each segment of each index is searched, and finish
+    /// with stats for the whole result set.
+    ///
+    /// Unlike `query`, every reader that matches the query contributes results. Results are only
+    /// ranked within a segment, and `page` and `page_size` apply to the stream as a whole, in the
+    /// order results are sent.
+    pub fn query_stream(self, indexes: Arc<Indexes>) -> impl Stream<Item = Result<QueryEvent>> {
+        async_stream::try_stream! {
+            let queries = parser::parse(&self.q)?;
+
+            // Indexes may be written to while the stream is consumed, so each one is searched as
+            // it was when the stream started.
+            let file = indexes.file.searcher().await;
+            let repo = indexes.repo.searcher().await;
+            let commit = indexes.commit.searcher().await;
+
+            let responses = stream_reader(
+                "content",
+                &ContentReader,
+                &indexes.file,
+                &file,
+                &queries,
+                &self,
+                true,
+            )
+            .chain(stream_reader(
+                "structural",
+                &StructuralReader,
+                &indexes.file,
+                &file,
+                &queries,
+                &self,
+                true,
+            ))
+            .chain(stream_reader(
+                "repo",
+                &RepoReader,
+                &indexes.repo,
+                &repo,
+                &queries,
+                &self,
+                true,
+            ))
+            .chain(stream_reader(
+                "file",
+                &FileReader,
+                &indexes.file,
+                &file,
+                &queries,
+                &self,
+                true,
+            ))
+            .chain(stream_reader(
+                "commit",
+                &CommitReader,
+                &indexes.commit,
+                &commit,
+                &queries,
+                &self,
+                true,
+            ))
+            // Directory listings are built from all documents at once, and can't be split.
+            .chain(stream_reader(
+                "open",
+                &OpenReader,
+                &indexes.file,
+                &file,
+                &queries,
+                &self,
+                false,
+            ));
+
+            for await event in page_stream(&self, responses) {
+                yield event?;
+            }
+        }
+    }
+
     fn limit(&self) -> usize {
         // do not permit a page-size of 0
         self.page_size.max(1)
@@ -259,6 +569,217 @@ impl ApiQuery {
     fn offset(&self) -> usize {
         self.page_size * self.page
     }
+
+    /// Collect the page of results ranked by `top_k`, or every result if they were all requested.
+    fn page_collector<C>(&self, top_k: C) -> PagedCollector<C> {
+        if self.all_results {
+            PagedCollector::All
+        } else {
+            PagedCollector::Top(top_k)
+        }
+    }
+}
+
+/// Execute a query with `reader` over the index as seen by `searcher`, one segment at a time if
+/// `segmented` is set, if any of the queries match the reader.
+///
+/// Each search returns as many results as the whole stream may need, and is cut down to the
+/// requested page by `page_stream`.
+fn stream_reader<'a, R>(
+    name: &'static str,
+    reader: &'a R,
+    indexer: &'a Indexer<R::Index>,
+    searcher: &'a Searcher,
+    queries: &'a [parser::Query<'a>],
+    q: &'a ApiQuery,
+    segmented: bool,
+) -> impl Stream<Item = Result<(&'static str, Option<SegmentOrdinal>, QueryResponse)>> + 'a
+where
+    R: ExecuteQuery + DocumentRead + Sync,
+{
+    async_stream::try_stream! {
+        if queries.iter().any(|query| reader.query_matches(query)) {
+            let segments = if segmented {
+                (0..searcher.segment_readers().len() as SegmentOrdinal)
+                    .map(Some)
+                    .collect()
+            } else {
+                vec![None]
+            };
+
+            for segment in segments {
+                let q = ApiQuery {
+                    page: 0,
+                    page_size: q.offset() + q.limit(),
+                    scope: Some(SearchScope {
+                        searcher: searcher.clone(),
+                        segment,
+                    }),
+                    ..q.clone()
+                };
+
+                yield (name, segment, reader.execute(indexer, queries, &q).await?);
+            }
+        }
+    }
+}
+
+/// Keep the results of a stream of responses that fall in the page requested by `q`, counting
+/// them in the order they are sent, and finish with stats for all of the responses.
+fn page_stream<'a>(
+    q: &'a ApiQuery,
+    responses: impl Stream<Item = Result<(&'static str, Option<SegmentOrdinal>, QueryResponse)>> + 'a,
+) -> impl Stream<Item = Result<QueryEvent>> + 'a {
+    async_stream::try_stream! {
+        let mut skip = q.offset();
+        let mut remaining = q.limit();
+        let mut total_count = 0;
+        let mut stats = ResultStats::default();
+
+        for await response in responses {
+            let (reader, segment, response) = response?;
+            total_count += response.metadata.total_count.unwrap_or(response.count);
+            stats = stats.merge(response.stats);
+
+            let mut data = response.data;
+            let skipped = skip.min(data.len());
+            data.drain(..skipped);
+            data.truncate(remaining);
+            skip -= skipped;
+            remaining -= data.len();
+
+            // searches past the requested page still count towards the stats
+            if !data.is_empty() {
+                yield QueryEvent::Results {
+                    reader,
+                    segment,
+                    data,
+                };
+            }
+        }
+
+        yield QueryEvent::Stats { total_count, stats };
+    }
+}
+
+/// Positions of the raw `File` fields in `file_filter_fields`, as read by a `QueryFilter`.
+const FILE_CONTENT: usize = 0;
+const FILE_PATH: usize = 1;
+const FILE_REPO: usize = 2;
+const FILE_BRANCHES: usize = 3;
+const FILE_SYMBOLS: usize = 4;
+
+fn file_filter_fields(schema: &File) -> Vec<Field> {
+    vec![
+        schema.raw_content,
+        schema.raw_relative_path,
+        schema.raw_repo_name,
+        schema.raw_branches,
+        schema.raw_symbols,
+    ]
+}
+
+/// The checks that a single query makes on the raw fields of a candidate document.
+///
+/// Candidates come from the trigram index, which only approximates each literal, so the text is
+/// checked here. Queries are `or`ed together: a document is accepted if the filter of any query
+/// accepts it, which keeps each negation scoped to the query it was written in.
+///
+/// Fields are referred to by their position in the list given to the `FieldsFilterCollector`.
+#[derive(Clone)]
+struct QueryFilter {
+    case_sensitive: bool,
+
+    /// Regexes that must match a field. An invalid regex, stored as `None`, matches nothing.
+    required: Vec<(usize, Option<ByteRegex>)>,
+
+    /// Regexes that must not match a field.
+    excluded: Vec<(usize, ByteRegex)>,
+
+    /// Regexes that must leave at least one line of a newline-separated field unmatched.
+    ///
+    /// This is how branches are negated: a file on both `main` and `dev` is still found on
+    /// `main` with `-branch:dev`.
+    excluded_lines: Vec<(usize, Vec<ByteRegex>)>,
+}
+
+impl QueryFilter {
+    fn new(q: &parser::Query<'_>) -> Self {
+        Self {
+            case_sensitive: q.is_case_sensitive(),
+            required: vec![],
+            excluded: vec![],
+            excluded_lines: vec![],
+        }
+    }
+
+    /// The negations of `q` on the fields listed by `file_filter_fields`.
+    fn file_negations(q: &parser::Query<'_>) -> Self {
+        Self::new(q)
+            .exclude(FILE_CONTENT, q.negated_content())
+            .exclude(FILE_PATH, q.negated_paths())
+            .exclude(FILE_REPO, q.negated_repos())
+            .exclude(FILE_SYMBOLS, q.negated_symbols())
+            .exclude_lines(FILE_BRANCHES, q.negated_branches())
+    }
+
+    fn require(mut self, field: usize, lit: Option<&parser::Literal<'_>>) -> Self {
+        if let Some(lit) = lit {
+            let regex = self.regex(lit);
+            self.required.push((field, regex));
+        }
+        self
+    }
+
+    fn exclude<'a, 'q: 'a>(
+        mut self,
+        field: usize,
+        lits: impl Iterator<Item = &'a parser::Literal<'q>>,
+    ) -> Self {
+        for lit in lits {
+            if let Some(regex) = self.regex(lit) {
+                self.excluded.push((field, regex));
+            }
+        }
+        self
+    }
+
+    fn exclude_lines<'a, 'q: 'a>(
+        mut self,
+        field: usize,
+        lits: impl Iterator<Item = &'a parser::Literal<'q>>,
+    ) -> Self {
+        let regexes = lits.filter_map(|lit| self.regex(lit)).collect::<Vec<_>>();
+        if !regexes.is_empty() {
+            self.excluded_lines.push((field, regexes));
+        }
+        self
+    }
+
+    fn regex(&self, lit: &parser::Literal<'_>) -> Option<ByteRegex> {
+        ByteRegexBuilder::new(&lit.regex_str())
+            .multi_line(true)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .ok()
+    }
+
+    fn accepts(&self, fields: &[&[u8]]) -> bool {
+        self.required.iter().all(|(field, regex)| {
+            regex
+                .as_ref()
+                .map(|r| r.is_match(fields[*field]))
+                .unwrap_or_default()
+        }) && !self
+            .excluded
+            .iter()
+            .any(|(field, regex)| regex.is_match(fields[*field]))
+            && self.excluded_lines.iter().all(|(field, regexes)| {
+                fields[*field]
+                    .split(|&b| b == b'\n')
+                    .any(|line| !regexes.iter().any(|r| r.is_match(line)))
+            })
+    }
 }
 
 impl PagingMetadata {
@@ -273,6 +794,28 @@ impl PagingMetadata {
 }
 
 impl ResultStats {
+    fn merge(mut self, other: Self) -> Self {
+        for (lang, count) in other.lang {
+            *self.lang.entry(lang).or_default() += count;
+        }
+
+        for (repo, count) in other.repo {
+            *self.repo.entry(repo).or_default() += count;
+        }
+
+        for (facets, other) in [
+            (&mut self.dir, other.dir),
+            (&mut self.ext, other.ext),
+            (&mut self.branch, other.branch),
+        ] {
+            for (facet, count) in other {
+                *facets.entry(facet).or_default() += count;
+            }
+        }
+
+        self
+    }
+
     fn with_lang_freqs(mut self, mut lang_freqs: HashMap<Vec<u8>, usize>) -> Self {
         self.lang = lang_freqs
             .iter_mut()
@@ -286,19 +829,35 @@ impl ResultStats {
         self
     }
 
-    fn with_repo_freqs(mut self, mut repo_freqs: HashMap<Vec<u8>, usize>) -> Self {
-        self.repo = repo_freqs
-            .iter_mut()
-            .filter(|(k, _)| !k.is_empty())
-            .map(|(k, v)| {
-                let k = String::from_utf8_lossy(k).to_string();
-                (k, *v)
-            })
-            .collect();
+    fn with_repo_freqs(mut self, repo_freqs: HashMap<Vec<u8>, usize>) -> Self {
+        self.repo = string_freqs(repo_freqs);
+        self
+    }
+
+    fn with_dir_freqs(mut self, dir_freqs: HashMap<Vec<u8>, usize>) -> Self {
+        self.dir = string_freqs(dir_freqs);
+        self
+    }
+
+    fn with_ext_freqs(mut self, ext_freqs: HashMap<Vec<u8>, usize>) -> Self {
+        self.ext = string_freqs(ext_freqs);
+        self
+    }
+
+    fn with_branch_freqs(mut self, branch_freqs: HashMap<Vec<u8>, usize>) -> Self {
+        self.branch = string_freqs(branch_freqs);
         self
     }
 }
 
+fn string_freqs(freqs: HashMap<Vec<u8>, usize>) -> HashMap<String, usize> {
+    freqs
+        .into_iter()
+        .filter(|(k, _)| !k.is_empty())
+        .map(|(k, v)| (String::from_utf8_lossy(&k).to_string(), v))
+        .collect()
+}
+
 #[async_trait]
 impl ExecuteQuery for ContentReader {
     type Index = File;
@@ -310,30 +869,28 @@ impl ExecuteQuery for ContentReader {
         q: &ApiQuery,
     ) -> Result<QueryResponse> {
         // queries that produce content results
-        let relevant_queries = queries.iter().filter(|q| self.query_matches(q));
+        let relevant_queries = || queries.iter().filter(|q| self.query_matches(q));
 
         // a list of targets, for a query of the form `symbol:foo or bar`, this is:
         // - a symbol target: foo
         // - a content target: bar
-        let targets = relevant_queries
-            .filter_map(|q| Some((q.target.as_ref()?, q.is_case_sensitive())))
+        let targets = relevant_queries()
+            .filter_map(|q| Some((q.target.as_ref()?, q.is_case_sensitive(), q.symbol_kind())))
             .collect::<SmallVec<[_; 2]>>();
 
-        // a regex filter to get rid of docs that contain the trigrams but not the text
-        let byte_regexes = targets
-            .iter()
-            .filter_map(|(target, case)| {
-                ByteRegexBuilder::new(&target.literal().regex_str())
-                    .multi_line(true)
-                    .case_insensitive(!case)
-                    .build()
-                    .ok()
+        // a filter to get rid of docs that contain the trigrams but not the text, or that match
+        // negated terms
+        let filters = relevant_queries()
+            .filter_map(|q| {
+                let target = q.target.as_ref()?.literal();
+                Some(QueryFilter::file_negations(q).require(FILE_CONTENT, Some(target)))
             })
             .collect::<Vec<_>>();
 
-        let raw_content = indexer.source.raw_content;
+        let path_field = indexer.source.raw_relative_path;
         let repo_field = indexer.source.raw_repo_name;
         let lang_field = indexer.source.lang;
+        let branches_field = indexer.source.raw_branches;
 
         // our results will consist of the top-k docs...
         let top_k = TopDocs::with_limit(q.limit())
@@ -349,23 +906,32 @@ impl ExecuteQuery for ContentReader {
         let total_count_handle = metadata_collector.add_collector(total_count_collector);
         let lang_stats_handle = metadata_collector.add_collector(lang_stats_collector);
         let repo_stats_handle = metadata_collector.add_collector(repo_stats_collector);
+        let dir_stats_handle = metadata_collector
+            .add_collector(FacetCollector::new(path_field, collector::top_level_dir));
+        let ext_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(path_field, collector::extension));
+        let branch_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(branches_field, collector::lines));
 
         // our final search results contain top-k, total count, language stats, repo stats,
-        // filtered by the target regex
-        let collector = BytesFilterCollector::new(
-            raw_content,
-            move |b| byte_regexes.iter().any(|r| r.is_match(b)), // a doc is accepted if it contains at least 1 target
-            (top_k, metadata_collector),
+        // filtered by the target regex and any negated terms
+        let collector = FieldsFilterCollector::new(
+            file_filter_fields(&indexer.source),
+            // a doc is accepted if it contains at least 1 target
+            move |fields| filters.iter().any(|f| f.accepts(fields)),
+            (q.page_collector(top_k), metadata_collector),
         );
 
-        let mut results = indexer.query(queries.iter(), self, collector).await?;
+        let mut results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
         let data = results
             .docs
             .filter_map(|doc| {
                 let snipper = Snipper::default().context(q.context_before, q.context_after);
                 let mut all_snippets = None::<SnippedFile>;
 
-                for (target, case_sensitive) in &targets {
+                for (target, case_sensitive, kind) in &targets {
                     let (is_symbol, lit) = match target {
                         parser::Target::Symbol(lit) => (true, lit),
                         parser::Target::Content(lit) => (false, lit),
@@ -374,6 +940,7 @@ impl ExecuteQuery for ContentReader {
                     if let Some(snippets) = snipper
                         .find_symbols(is_symbol)
                         .case_sensitive(*case_sensitive)
+                        .symbol_kind(kind.map(AsRef::as_ref))
                         .all_for_doc(&lit.regex_str(), &doc)
                         .unwrap()
                     {
@@ -393,7 +960,10 @@ impl ExecuteQuery for ContentReader {
 
         let stats = ResultStats::default()
             .with_lang_freqs(lang_stats_handle.extract(&mut results.metadata))
-            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata));
+            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata))
+            .with_dir_freqs(dir_stats_handle.extract(&mut results.metadata))
+            .with_ext_freqs(ext_stats_handle.extract(&mut results.metadata))
+            .with_branch_freqs(branch_stats_handle.extract(&mut results.metadata));
 
         let metadata = PagingMetadata::new(q.page, q.page_size, Some(total_count));
 
@@ -408,6 +978,113 @@ impl ExecuteQuery for ContentReader {
     }
 }
 
+#[async_trait]
+impl ExecuteQuery for StructuralReader {
+    type Index = File;
+
+    async fn execute(
+        &self,
+        indexer: &Indexer<Self::Index>,
+        queries: &[parser::Query<'_>],
+        q: &ApiQuery,
+    ) -> Result<QueryResponse> {
+        let relevant = || queries.iter().filter(|q| self.query_matches(q));
+
+        // each pattern is kept with the negations of its query
+        let (patterns, filters): (Vec<_>, Vec<_>) = relevant()
+            .filter_map(|q| {
+                let pattern = q.target.as_ref()?.content()?.clone().unwrap();
+                let pattern = StructuralPattern::new(&pattern, q.lang.as_ref()?);
+                Some(pattern.map(|p| (p, QueryFilter::file_negations(q))))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        // The collector only sees raw content, so every document must be parsed with the same
+        // grammar.
+        if patterns
+            .windows(2)
+            .any(|pair| pair[0].lang_id() != pair[1].lang_id())
+        {
+            bail!("structural queries must all target the same language");
+        }
+
+        let patterns = Arc::new(patterns);
+        let path_field = indexer.source.raw_relative_path;
+        let repo_field = indexer.source.raw_repo_name;
+        let lang_field = indexer.source.lang;
+        let branches_field = indexer.source.raw_branches;
+
+        let top_k = TopDocs::with_limit(q.limit())
+            .and_offset(q.offset())
+            .tweak_score(DocumentTweaker(indexer.source.clone()));
+
+        let mut metadata_collector = MultiCollector::new();
+        let total_count_handle = metadata_collector.add_collector(tantivy::collector::Count);
+        let lang_stats_handle = metadata_collector.add_collector(FrequencyCollector(lang_field));
+        let repo_stats_handle = metadata_collector.add_collector(FrequencyCollector(repo_field));
+        let dir_stats_handle = metadata_collector
+            .add_collector(FacetCollector::new(path_field, collector::top_level_dir));
+        let ext_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(path_field, collector::extension));
+        let branch_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(branches_field, collector::lines));
+
+        // candidates from the trigram index are only accepted if the pattern matches their syntax
+        // tree, so that counts and pages are exact
+        let collector = FieldsFilterCollector::new(
+            file_filter_fields(&indexer.source),
+            {
+                let patterns = Arc::clone(&patterns);
+                move |fields| {
+                    patterns
+                        .iter()
+                        .zip(&filters)
+                        .any(|(p, f)| f.accepts(fields) && p.is_match(fields[FILE_CONTENT]))
+                }
+            },
+            (q.page_collector(top_k), metadata_collector),
+        );
+
+        let mut results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
+
+        let snipper = Snipper::default().context(q.context_before, q.context_after);
+        let data = results
+            .docs
+            .filter_map(|doc| {
+                let mut highlights = patterns
+                    .iter()
+                    .flat_map(|p| p.matches(doc.content.as_bytes()))
+                    .collect::<Vec<_>>();
+                highlights.sort_by_key(|r| (r.start, r.end));
+                highlights.dedup();
+
+                snipper
+                    .for_highlights(highlights, &doc)
+                    .map(QueryResult::Snippets)
+            })
+            .collect::<Vec<QueryResult>>();
+
+        let total_count = total_count_handle.extract(&mut results.metadata);
+        let stats = ResultStats::default()
+            .with_lang_freqs(lang_stats_handle.extract(&mut results.metadata))
+            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata))
+            .with_dir_freqs(dir_stats_handle.extract(&mut results.metadata))
+            .with_ext_freqs(ext_stats_handle.extract(&mut results.metadata))
+            .with_branch_freqs(branch_stats_handle.extract(&mut results.metadata));
+
+        Ok(QueryResponse {
+            count: data.len(),
+            metadata: PagingMetadata::new(q.page, q.page_size, Some(total_count)),
+            data,
+            stats,
+        })
+    }
+}
+
 #[async_trait]
 impl ExecuteQuery for FileReader {
     type Index = File;
@@ -418,29 +1095,27 @@ impl ExecuteQuery for FileReader {
         queries: &[parser::Query<'_>],
         q: &ApiQuery,
     ) -> Result<QueryResponse> {
-        let (filter_regexes, byte_filter_regexes): (Vec<_>, Vec<_>) = queries
-            .iter()
-            .filter(|q| self.query_matches(q))
+        let relevant = || queries.iter().filter(|q| self.query_matches(q));
+
+        let filter_regexes = relevant()
             .filter_map(|q| {
-                let regex_str = q.path.as_ref()?.regex_str();
-                let case_insensitive = !q.is_case_sensitive();
-                let regex = RegexBuilder::new(&regex_str)
-                    .case_insensitive(case_insensitive)
+                RegexBuilder::new(&q.path.as_ref()?.regex_str())
+                    .case_insensitive(!q.is_case_sensitive())
                     .build()
-                    .ok()?;
-                let byte_regex = ByteRegexBuilder::new(&regex_str)
-                    .case_insensitive(case_insensitive)
-                    .build()
-                    .ok()?;
-                Some((regex, byte_regex))
+                    .ok()
             })
-            .unzip();
+            .collect::<Vec<_>>();
+
+        let filters = relevant()
+            .map(|q| QueryFilter::file_negations(q).require(FILE_PATH, q.path.as_ref()))
+            .collect::<Vec<_>>();
 
         let top_k = TopDocs::with_limit(q.limit()).and_offset(q.offset());
 
         let path_field = indexer.source.raw_relative_path;
         let repo_field = indexer.source.raw_repo_name;
         let lang_field = indexer.source.lang;
+        let branches_field = indexer.source.raw_branches;
 
         let total_count_collector = tantivy::collector::Count;
         let lang_stats_collector = FrequencyCollector(lang_field);
@@ -450,14 +1125,23 @@ impl ExecuteQuery for FileReader {
         let total_count_handle = metadata_collector.add_collector(total_count_collector);
         let lang_stats_handle = metadata_collector.add_collector(lang_stats_collector);
         let repo_stats_handle = metadata_collector.add_collector(repo_stats_collector);
-
-        let collector = BytesFilterCollector::new(
-            path_field,
-            move |b| byte_filter_regexes.iter().any(|r| r.is_match(b)), // a doc is accepted if it contains at least 1 target
-            (top_k, metadata_collector),
+        let dir_stats_handle = metadata_collector
+            .add_collector(FacetCollector::new(path_field, collector::top_level_dir));
+        let ext_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(path_field, collector::extension));
+        let branch_stats_handle =
+            metadata_collector.add_collector(FacetCollector::new(branches_field, collector::lines));
+
+        let collector = FieldsFilterCollector::new(
+            file_filter_fields(&indexer.source),
+            // a doc is accepted if it matches at least 1 query
+            move |fields| filters.iter().any(|f| f.accepts(fields)),
+            (q.page_collector(top_k), metadata_collector),
         );
 
-        let mut results = indexer.query(queries.iter(), self, collector).await?;
+        let mut results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
 
         let data = results
             .docs
@@ -474,6 +1158,10 @@ impl ExecuteQuery for FileReader {
                     repo_ref: f.repo_ref,
                     lang: f.lang,
                     branches: f.branches,
+                    last_commit: f.last_commit.map(|c| LastCommitData {
+                        author: c.author,
+                        date: c.unix_secs,
+                    }),
                 })
             })
             .collect::<Vec<QueryResult>>();
@@ -482,7 +1170,10 @@ impl ExecuteQuery for FileReader {
 
         let stats = ResultStats::default()
             .with_lang_freqs(lang_stats_handle.extract(&mut results.metadata))
-            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata));
+            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata))
+            .with_dir_freqs(dir_stats_handle.extract(&mut results.metadata))
+            .with_ext_freqs(ext_stats_handle.extract(&mut results.metadata))
+            .with_branch_freqs(branch_stats_handle.extract(&mut results.metadata));
 
         let metadata = PagingMetadata::new(q.page, q.page_size, Some(total_count));
 
@@ -507,23 +1198,28 @@ impl ExecuteQuery for RepoReader {
         queries: &[parser::Query<'_>],
         q: &ApiQuery,
     ) -> Result<QueryResponse> {
-        let (filter_regexes, byte_filter_regexes): (Vec<_>, Vec<_>) = queries
-            .iter()
-            .filter(|q| self.query_matches(q))
+        let relevant = || queries.iter().filter(|q| self.query_matches(q));
+
+        let filter_regexes = relevant()
             .filter_map(|q| {
-                let regex_str = q.path.as_ref()?.regex_str();
-                let case_insensitive = !q.is_case_sensitive();
-                let regex = RegexBuilder::new(&regex_str)
-                    .case_insensitive(case_insensitive)
-                    .build()
-                    .ok()?;
-                let byte_regex = ByteRegexBuilder::new(&regex_str)
-                    .case_insensitive(case_insensitive)
+                RegexBuilder::new(&q.path.as_ref()?.regex_str())
+                    .case_insensitive(!q.is_case_sensitive())
                     .build()
-                    .ok()?;
-                Some((regex, byte_regex))
+                    .ok()
             })
-            .unzip();
+            .collect::<Vec<_>>();
+
+        // the name is the only field checked
+        let filters = relevant()
+            .filter_map(|q| {
+                let path = q.path.as_ref()?;
+                Some(
+                    QueryFilter::new(q)
+                        .require(0, Some(path))
+                        .exclude(0, q.negated_repos()),
+                )
+            })
+            .collect::<Vec<_>>();
 
         let top_k = TopDocs::with_limit(q.limit()).and_offset(q.offset());
 
@@ -537,11 +1233,14 @@ impl ExecuteQuery for RepoReader {
 
         let collector = BytesFilterCollector::new(
             name_field,
-            move |b| byte_filter_regexes.iter().any(|r| r.is_match(b)), // a doc is accepted if it contains at least 1 target
-            (top_k, metadata_collector),
+            // a doc is accepted if it contains at least 1 target
+            move |b| filters.iter().any(|f| f.accepts(&[b])),
+            (q.page_collector(top_k), metadata_collector),
         );
 
-        let mut results = indexer.query(queries.iter(), self, collector).await?;
+        let mut results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
 
         let data = results
             .docs
@@ -584,7 +1283,7 @@ impl ExecuteQuery for OpenReader {
         &self,
         indexer: &Indexer<Self::Index>,
         queries: &[parser::Query<'_>],
-        _q: &ApiQuery,
+        q: &ApiQuery,
     ) -> Result<QueryResponse> {
         #[derive(Debug)]
         struct Directive {
@@ -640,7 +1339,9 @@ impl ExecuteQuery for OpenReader {
             (top_docs, empty_collector),
         );
 
-        let results = indexer.query(queries.iter(), self, collector).await?;
+        let results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
 
         // Map of (repo_name, relative_path) -> (String, entry set)
         //
@@ -751,6 +1452,101 @@ impl ExecuteQuery for OpenReader {
     }
 }
 
+#[async_trait]
+impl ExecuteQuery for CommitReader {
+    type Index = Commit;
+
+    async fn execute(
+        &self,
+        indexer: &Indexer<Self::Index>,
+        queries: &[parser::Query<'_>],
+        q: &ApiQuery,
+    ) -> Result<QueryResponse> {
+        let relevant = || queries.iter().filter(|q| self.query_matches(q));
+
+        let message_regexes = relevant()
+            .filter_map(|q| {
+                RegexBuilder::new(&q.target.as_ref()?.content()?.regex_str())
+                    .multi_line(true)
+                    .case_insensitive(!q.is_case_sensitive())
+                    .build()
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+
+        // paths are stored one per line, so anchors apply to each path
+        let filters = relevant()
+            .map(|q| {
+                QueryFilter::new(q)
+                    .require(0, q.target.as_ref().and_then(parser::Target::content))
+                    .require(1, q.path.as_ref())
+                    .exclude(0, q.negated_content())
+                    .exclude(1, q.negated_paths())
+                    .exclude(2, q.negated_repos())
+            })
+            .collect::<Vec<_>>();
+
+        let message_field = indexer.source.raw_message;
+        let paths_field = indexer.source.raw_paths;
+        let repo_field = indexer.source.raw_repo_name;
+
+        // newest commits first
+        let top_k = TopDocs::with_limit(q.limit())
+            .and_offset(q.offset())
+            .custom_score(RecencyScorer(indexer.source.unix_seconds));
+
+        let mut metadata_collector = MultiCollector::new();
+        let total_count_handle = metadata_collector.add_collector(tantivy::collector::Count);
+        let repo_stats_handle = metadata_collector.add_collector(FrequencyCollector(repo_field));
+
+        let collector = FieldsFilterCollector::new(
+            vec![message_field, paths_field, repo_field],
+            move |fields| filters.iter().any(|f| f.accepts(fields)),
+            (q.page_collector(top_k), metadata_collector),
+        );
+
+        let mut results = indexer
+            .query(queries.iter(), self, collector, q.scope.as_ref())
+            .await?;
+
+        let data = results
+            .docs
+            .map(|c| {
+                let mut message = HighlightedString::new(c.message);
+
+                for regex in &message_regexes {
+                    message.apply_regex(regex);
+                }
+
+                QueryResult::CommitResult(CommitResultData {
+                    repo_name: c.repo_name,
+                    repo_ref: c.repo_ref,
+                    id: c.commit_id,
+                    author: c.author,
+                    date: c.unix_secs,
+                    message,
+                    paths: c.paths,
+                })
+            })
+            .collect::<Vec<QueryResult>>();
+
+        let stats = ResultStats::default()
+            .with_repo_freqs(repo_stats_handle.extract(&mut results.metadata));
+
+        let total_count = total_count_handle.extract(&mut results.metadata);
+        let metadata = PagingMetadata::new(q.page, q.page_size, Some(total_count));
+
+        let response = QueryResponse {
+            count: data.len(),
+            data,
+            metadata,
+            stats,
+        };
+
+        Ok(response)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -796,7 +1592,10 @@ mod tests {
             "repo": {"local//bleep": 1},
             "lang": {
                 "Rust": 1
-            }
+            },
+            "dir": {},
+            "ext": {"rs": 1},
+            "branch": {}
           },
         });
 
@@ -823,10 +1622,282 @@ mod tests {
                 page_count: Some(6),
                 total_count: Some(520)
             },
-            stats: ResultStats { repo: repos, lang: langs },
+            stats: ResultStats {
+                repo: repos,
+                lang: langs,
+                ext: HashMap::from([("rs".into(), 1)]),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+        assert_eq!(expected, observed);
+    }
+
+    #[test]
+    fn serialize_stream_stats() {
+        let stats = ResultStats {
+            repo: HashMap::from([("bloop".into(), 2)]),
+            lang: HashMap::from([("Rust".into(), 1)]),
+            dir: HashMap::from([("src".into(), 2)]),
+            ..Default::default()
+        }
+        .merge(ResultStats {
+            repo: HashMap::from([("bloop".into(), 1), ("bleep".into(), 1)]),
+            dir: HashMap::from([("src".into(), 1), ("tests".into(), 1)]),
+            branch: HashMap::from([("main".into(), 2)]),
+            ..Default::default()
+        });
+
+        let observed = serde_json::to_value(QueryEvent::Stats {
+            total_count: 4,
+            stats,
         })
         .unwrap();
 
+        let expected = serde_json::json!({
+            "kind": "stats",
+            "total_count": 4,
+            "stats": {
+                "repo": {"bloop": 3, "bleep": 1},
+                "lang": {"Rust": 1},
+                "dir": {"src": 3, "tests": 1},
+                "ext": {},
+                "branch": {"main": 2},
+            },
+        });
+
         assert_eq!(expected, observed);
     }
+
+    /// Whether any query in `q` accepts a file with raw fields in the order of
+    /// `file_filter_fields`: content, path, repo, branches and symbols.
+    fn accepted(q: &str, fields: [&str; 5]) -> bool {
+        let fields = fields.map(str::as_bytes);
+        parser::parse(q).unwrap().iter().any(|q| {
+            let target = q.target.as_ref().map(parser::Target::literal);
+            QueryFilter::file_negations(q)
+                .require(FILE_CONTENT, target)
+                .accepts(&fields)
+        })
+    }
+
+    #[test]
+    fn negations_are_exact() {
+        // `tes` and `est` are both in the path, but `test` is not
+        let q = "foo -path:test";
+        assert!(accepted(q, ["foo", "src/tes/est.rs", "bloop", "main", ""]));
+        assert!(!accepted(q, ["foo", "src/test.rs", "bloop", "main", ""]));
+
+        let q = "foo -symbol:/^foo_/";
+        assert!(!accepted(q, ["foo", "a.rs", "bloop", "main", "bar\nfoo_x"]));
+        assert!(accepted(q, ["foo", "a.rs", "bloop", "main", "bar\nx_foo_"]));
+    }
+
+    #[test]
+    fn negated_branches_keep_other_branches() {
+        let q = "foo -branch:dev";
+        assert!(accepted(q, ["foo", "a.rs", "bloop", "main\ndev", ""]));
+        assert!(!accepted(q, ["foo", "a.rs", "bloop", "dev", ""]));
+
+        let q = "foo -branch:/^(main|dev)$/";
+        assert!(!accepted(q, ["foo", "a.rs", "bloop", "main\ndev", ""]));
+    }
+
+    #[test]
+    fn negations_are_scoped_to_their_query() {
+        let q = "(foo -path:vendor) or bar";
+        assert!(!accepted(q, ["foo", "vendor/a.rs", "bloop", "main", ""]));
+        assert!(accepted(q, ["bar", "vendor/a.rs", "bloop", "main", ""]));
+        assert!(accepted(q, ["foo", "src/a.rs", "bloop", "main", ""]));
+    }
+
+    /// Add a segment of commits to an in-memory commit index. Later IDs are newer commits.
+    fn add_commits(indexer: &mut Indexer<Commit>, ids: &[&str]) {
+        let schema = &indexer.source;
+        let mut writer = indexer.index.writer(15_000_000).unwrap();
+
+        for id in ids {
+            let mut doc = tantivy::Document::new();
+            doc.add_text(schema.repo_ref, "local//bloop");
+            doc.add_text(schema.repo_name, "bloop");
+            doc.add_bytes(schema.raw_repo_name, "bloop".as_bytes());
+            doc.add_text(schema.commit_id, id);
+            doc.add_text(schema.message, "fix");
+            doc.add_bytes(schema.raw_message, "fix".as_bytes());
+            doc.add_text(schema.author, "Ferris");
+            doc.add_u64(schema.unix_seconds, id.as_bytes()[0].into());
+            writer.add_document(doc).unwrap();
+        }
+
+        writer.commit().unwrap();
+        *indexer.reader.get_mut() = indexer.index.reader().unwrap();
+    }
+
+    /// The commit IDs sent in each event of a stream over `searcher`, and the total count.
+    async fn stream_commits(
+        indexer: &Indexer<Commit>,
+        searcher: &Searcher,
+        page: usize,
+        page_size: usize,
+    ) -> (Vec<(Option<SegmentOrdinal>, Vec<String>)>, usize) {
+        let q = ApiQuery {
+            page,
+            page_size,
+            ..ApiQuery::new("mode:commit repo:bloop".into())
+        };
+        let queries = parser::parse(&q.q).unwrap();
+        let responses = stream_reader(
+            "commit",
+            &CommitReader,
+            indexer,
+            searcher,
+            &queries,
+            &q,
+            true,
+        );
+
+        let mut results = vec![];
+        let mut total = None;
+        for event in page_stream(&q, responses).collect::<Vec<_>>().await {
+            match event.unwrap() {
+                QueryEvent::Results { segment, data, .. } => {
+                    let ids = data
+                        .into_iter()
+                        .map(|result| match result {
+                            QueryResult::CommitResult(commit) => commit.id,
+                            _ => panic!("expected a commit"),
+                        })
+                        .collect();
+                    results.push((segment, ids));
+                }
+                QueryEvent::Stats { total_count, .. } => total = Some(total_count),
+            }
+        }
+
+        (results, total.unwrap())
+    }
+
+    fn commit_indexer() -> Indexer<Commit> {
+        let source = Commit::new();
+        let index = tantivy::Index::create_in_ram(source.schema());
+        index.tokenizers().register(
+            "default",
+            tantivy::tokenizer::NgramTokenizer::new(1, 3, false),
+        );
+
+        Indexer {
+            reader: index.reader().unwrap().into(),
+            index,
+            source,
+            reindex_buffer_size: 15_000_000,
+            reindex_threads: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_pages_across_segments() {
+        let mut indexer = commit_indexer();
+
+        add_commits(&mut indexer, &["a", "b"]);
+        add_commits(&mut indexer, &["c", "d"]);
+        let searcher = indexer.searcher().await;
+
+        // written after the stream's view of the index was taken
+        add_commits(&mut indexer, &["e"]);
+
+        // Segments are searched in no particular order, but each one is ranked newest first.
+        let (first, total) = stream_commits(&indexer, &searcher, 0, 3).await;
+        assert_eq!(total, 4);
+        assert_eq!(first.len(), 2);
+        assert!(first.iter().all(|(segment, _)| segment.is_some()));
+        assert_eq!(first[0].1.len(), 2);
+        assert_eq!(first[1].1.len(), 1);
+
+        let (second, total) = stream_commits(&indexer, &searcher, 1, 3).await;
+        assert_eq!(total, 4);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].0, first[1].0);
+
+        let mut ids = [first, second]
+            .concat()
+            .into_iter()
+            .flat_map(|(_, ids)| ids)
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+
+        assert_eq!(stream_commits(&indexer, &searcher, 2, 3).await, (vec![], 4));
+        assert_eq!(
+            stream_commits(&indexer, &indexer.searcher().await, 2, 3)
+                .await
+                .1,
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn all_results_from_one_view() {
+        let mut indexer = commit_indexer();
+        add_commits(&mut indexer, &["a", "b", "c"]);
+        add_commits(&mut indexer, &["d", "e"]);
+        let searcher = indexer.searcher().await;
+
+        // written after the view of the index was taken
+        add_commits(&mut indexer, &["f"]);
+
+        // Every result is collected, regardless of the page size, in index order rather than
+        // newest first.
+        let q = ApiQuery {
+            page_size: 1,
+            all_results: true,
+            ..ApiQuery::new("mode:commit repo:bloop".into())
+        };
+        let queries = parser::parse(&q.q).unwrap();
+        let responses = stream_reader(
+            "commit",
+            &CommitReader,
+            &indexer,
+            &searcher,
+            &queries,
+            &q,
+            true,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        let mut segments = responses
+            .into_iter()
+            .map(|response| {
+                let (_, segment, response) = response.unwrap();
+                assert!(segment.is_some());
+                response
+                    .data
+                    .into_iter()
+                    .map(|result| match result {
+                        QueryResult::CommitResult(commit) => commit.id,
+                        _ => panic!("expected a commit"),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        segments.sort();
+
+        assert_eq!(segments, [vec!["a", "b", "c"], vec!["d", "e"]]);
+    }
+
+    #[tokio::test]
+    async fn queries_scoped_to_a_repo() {
+        let mut indexer = commit_indexer();
+        add_commits(&mut indexer, &["a", "b"]);
+
+        let q = ApiQuery::new("mode:commit fix".into());
+        for (repo_ref, count) in [("local//bloop", 2), ("local//blo", 0)] {
+            let mut queries = parser::parse(&q.q).unwrap();
+            queries[0].repo_ref = Some(repo_ref.into());
+
+            let response = CommitReader.execute(&indexer, &queries, &q).await.unwrap();
+            assert_eq!(response.count, count, "{repo_ref}");
+        }
+    }
 }
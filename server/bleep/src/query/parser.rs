@@ -1,20 +1,40 @@
 use pest::{iterators::Pair, Parser};
 use regex::Regex;
 use smallvec::{smallvec, SmallVec};
-use std::{borrow::Cow, collections::HashSet, mem};
+use std::{borrow::Cow, collections::HashSet, mem, ops::Bound};
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct Query<'a> {
     pub open: Option<bool>,
     pub case_sensitive: Option<bool>,
     pub global_regex: Option<bool>,
+    /// Whether the content target is a tree-sitter pattern, set with `mode:structural`.
+    pub structural: Option<bool>,
+    /// Whether to search commit history instead of files, set with `mode:commit`.
+    pub commits: Option<bool>,
 
     pub org: Option<Literal<'a>>,
     pub repo: Option<Literal<'a>>,
     pub path: Option<Literal<'a>>,
     pub lang: Option<Cow<'a, str>>,
+    /// The kind of symbol to match, e.g. `function` or `struct`, as named by the language's
+    /// namespaces. This only narrows down symbol searches.
+    pub kind: Option<Cow<'a, str>>,
     pub branch: Option<Literal<'a>>,
     pub target: Option<Target<'a>>,
+
+    /// Only match files last committed at or after this unix timestamp, in seconds.
+    pub after: Option<u64>,
+    /// Only match files last committed before this unix timestamp, in seconds.
+    pub before: Option<u64>,
+
+    /// Terms that must *not* match, e.g. `-path:test` or `not lang:markdown`.
+    pub negations: Vec<Negation<'a>>,
+
+    /// Only match documents of the repo with this exact reference. This can't be written in a
+    /// query, and is set by callers that search a single repo. Repo searches aren't narrowed by
+    /// it, as the repo index only has a tokenized reference.
+    pub repo_ref: Option<Cow<'a, str>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -23,6 +43,15 @@ pub enum Target<'a> {
     Content(Literal<'a>),
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Negation<'a> {
+    Repo(Literal<'a>),
+    Path(Literal<'a>),
+    Lang(Cow<'a, str>),
+    Branch(Literal<'a>),
+    Target(Target<'a>),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 #[allow(clippy::large_enum_variant)]
 pub enum ParsedQuery<'a> {
@@ -46,6 +75,13 @@ pub struct SemanticQuery<'a> {
     pub langs: HashSet<Cow<'a, str>>,
     pub branch: HashSet<Literal<'a>>,
     pub target: Option<Literal<'a>>,
+
+    /// Only match code last committed at or after this unix timestamp, in seconds.
+    #[serde(default)]
+    pub after: Option<u64>,
+    /// Only match code last committed before this unix timestamp, in seconds.
+    #[serde(default)]
+    pub before: Option<u64>,
 }
 
 impl<'a> SemanticQuery<'a> {
@@ -95,6 +131,8 @@ impl<'a> SemanticQuery<'a> {
                 .collect(),
             branch: self.branch.into_iter().map(Literal::into_owned).collect(),
             target: self.target.map(Literal::into_owned),
+            after: self.after,
+            before: self.before,
         }
     }
 }
@@ -107,12 +145,21 @@ impl<'a> Query<'a> {
             open: rhs.open.or(self.open),
             case_sensitive: rhs.case_sensitive.or(self.case_sensitive),
             global_regex: rhs.global_regex.or(self.global_regex),
+            structural: rhs.structural.or(self.structural),
+            commits: rhs.commits.or(self.commits),
 
             org: rhs.org.or(self.org),
             repo: rhs.repo.or(self.repo),
             path: rhs.path.or(self.path),
             lang: rhs.lang.or(self.lang),
+            kind: rhs.kind.or(self.kind),
             branch: rhs.branch.or(self.branch),
+            after: rhs.after.or(self.after),
+            before: rhs.before.or(self.before),
+            repo_ref: rhs.repo_ref.or(self.repo_ref),
+
+            // Unlike positive terms, every negation applies.
+            negations: self.negations.into_iter().chain(rhs.negations).collect(),
 
             target: match (self.target, rhs.target) {
                 (Some(Target::Content(lhs)), Some(Target::Content(rhs))) => {
@@ -177,6 +224,85 @@ impl<'a> Query<'a> {
             self.repo.as_mut().map(Literal::make_regex);
             self.path.as_mut().map(Literal::make_regex);
             self.target.as_mut().map(Target::make_regex);
+            self.negations.iter_mut().for_each(Negation::make_regex);
+        }
+    }
+
+    /// Bounds on the last commit time of matching files, if any date filter is set.
+    pub fn commit_time_bounds(&self) -> Option<(Bound<u64>, Bound<u64>)> {
+        if self.after.is_none() && self.before.is_none() {
+            return None;
+        }
+
+        Some((
+            self.after.map_or(Bound::Unbounded, Bound::Included),
+            self.before.map_or(Bound::Unbounded, Bound::Excluded),
+        ))
+    }
+
+    /// The `kind:` filter, if this is a symbol search.
+    pub fn symbol_kind(&self) -> Option<&Cow<'a, str>> {
+        match self.target {
+            Some(Target::Symbol(..)) => self.kind.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Negated `repo:` literals.
+    pub fn negated_repos(&self) -> impl Iterator<Item = &Literal<'a>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Repo(lit) => Some(lit),
+            _ => None,
+        })
+    }
+
+    /// Negated `path:` literals.
+    pub fn negated_paths(&self) -> impl Iterator<Item = &Literal<'a>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Path(lit) => Some(lit),
+            _ => None,
+        })
+    }
+
+    /// Negated `branch:` literals.
+    pub fn negated_branches(&self) -> impl Iterator<Item = &Literal<'a>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Branch(lit) => Some(lit),
+            _ => None,
+        })
+    }
+
+    /// Negated `lang:` filters.
+    pub fn negated_langs(&self) -> impl Iterator<Item = &Cow<'a, str>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Lang(lang) => Some(lang),
+            _ => None,
+        })
+    }
+
+    /// Negated `symbol:` literals.
+    pub fn negated_symbols(&self) -> impl Iterator<Item = &Literal<'a>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Target(Target::Symbol(lit)) => Some(lit),
+            _ => None,
+        })
+    }
+
+    /// Negated content literals.
+    pub fn negated_content(&self) -> impl Iterator<Item = &Literal<'a>> {
+        self.negations.iter().filter_map(|n| match n {
+            Negation::Target(Target::Content(lit)) => Some(lit),
+            _ => None,
+        })
+    }
+}
+
+impl<'a> Negation<'a> {
+    fn make_regex(&mut self) {
+        match self {
+            Self::Repo(lit) | Self::Path(lit) | Self::Branch(lit) => lit.make_regex(),
+            Self::Target(target) => target.make_regex(),
+            Self::Lang(_) => {}
         }
     }
 }
@@ -226,6 +352,10 @@ pub enum ParseError {
     UnparsedToken(String),
     #[error("multiple mode designators")]
     MultiMode,
+    #[error("invalid date: {0:?}")]
+    InvalidDate(String),
+    #[error("negated filters are only supported in grep mode")]
+    SemanticNegation,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, serde::Serialize, serde::Deserialize)]
@@ -316,6 +446,22 @@ impl<'a> From<Pair<'a, Rule>> for Literal<'a> {
     }
 }
 
+/// Parse a date of the form `YYYY-MM-DD` into a unix timestamp, at midnight UTC.
+fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-').map(str::parse::<u32>);
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    let timestamp = chrono::NaiveDate::from_ymd_opt(year as i32, month, day)?
+        .and_hms_opt(0, 0, 0)?
+        .timestamp();
+
+    u64::try_from(timestamp).ok()
+}
+
 /// Unescape a string, with a specific terminating character.
 ///
 /// Newline and tab strings (`\n` and `\t`) are replaced with the respective character. Backslashes
@@ -359,6 +505,8 @@ fn unescape(s: &str, term: char) -> String {
 enum ForceParsingAs {
     Grep,
     Semantic,
+    Structural,
+    Commit,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -371,8 +519,12 @@ enum Expr<'a> {
     Symbol(Literal<'a>),
     Path(Literal<'a>),
     Lang(Cow<'a, str>),
+    Kind(Cow<'a, str>),
     Content(Literal<'a>),
     Branch(Literal<'a>),
+    After(u64),
+    Before(u64),
+    Not(Negation<'a>),
 
     CaseSensitive(bool),
     Open(bool),
@@ -384,7 +536,7 @@ enum Expr<'a> {
 }
 
 impl<'a> Expr<'a> {
-    fn parse(pair: Pair<'a, Rule>, top_level: bool) -> Result<Self, Pair<'a, Rule>> {
+    fn parse(pair: Pair<'a, Rule>, top_level: bool) -> Result<Self, ParseError> {
         use Expr::*;
 
         Ok(match pair.as_rule() {
@@ -400,6 +552,30 @@ impl<'a> Expr<'a> {
             Rule::org => Org(Literal::from(pair.into_inner().next().unwrap())),
             Rule::branch => Branch(Literal::from(pair.into_inner().next().unwrap())),
             Rule::lang => Lang(pair.into_inner().as_str().into()),
+            Rule::kind => Kind(pair.into_inner().as_str().to_ascii_lowercase().into()),
+            Rule::after => {
+                let date = pair.into_inner().as_str();
+                After(parse_date(date).ok_or_else(|| ParseError::InvalidDate(date.into()))?)
+            }
+            Rule::before => {
+                let date = pair.into_inner().as_str();
+                Before(parse_date(date).ok_or_else(|| ParseError::InvalidDate(date.into()))?)
+            }
+
+            Rule::negation => {
+                let inner = pair.into_inner().next().unwrap();
+                let negation = match Self::parse(inner, top_level)? {
+                    Repo(lit) => Negation::Repo(lit),
+                    Path(lit) => Negation::Path(lit),
+                    Branch(lit) => Negation::Branch(lit),
+                    Lang(lang) => Negation::Lang(super::languages::parse_alias(lang)),
+                    Symbol(lit) => Negation::Target(Target::Symbol(lit)),
+                    Content(lit) => Negation::Target(Target::Content(lit)),
+                    _ => unreachable!(),
+                };
+
+                Not(negation)
+            }
 
             Rule::open => {
                 let inner = pair.into_inner().next().unwrap();
@@ -413,7 +589,7 @@ impl<'a> Expr<'a> {
             Rule::case => {
                 // Avoid parsing this flag unless it's at the top level.
                 if !top_level {
-                    return Err(pair);
+                    return Err(ParseError::UnparsedToken(pair.to_string()));
                 }
 
                 let inner = pair.into_inner().next().unwrap();
@@ -427,7 +603,7 @@ impl<'a> Expr<'a> {
             Rule::global_regex => {
                 // Avoid parsing this flag unless it's at the top level.
                 if !top_level {
-                    return Err(pair);
+                    return Err(ParseError::UnparsedToken(pair.to_string()));
                 }
 
                 let inner = pair.into_inner().next().unwrap();
@@ -441,13 +617,15 @@ impl<'a> Expr<'a> {
             Rule::mode_selector => {
                 // Avoid parsing this flag unless it's at the top level.
                 if !top_level {
-                    return Err(pair);
+                    return Err(ParseError::UnparsedToken(pair.to_string()));
                 }
 
                 let inner = pair.into_inner().next().unwrap();
                 match inner.as_str() {
                     "grep" => GlobalMode(ForceParsingAs::Grep),
                     "semantic" => GlobalMode(ForceParsingAs::Semantic),
+                    "structural" => GlobalMode(ForceParsingAs::Structural),
+                    "commit" => GlobalMode(ForceParsingAs::Commit),
                     _ => unreachable!(),
                 }
             }
@@ -482,7 +660,7 @@ impl<'a> Expr<'a> {
             // https://github.com/pest-parser/pest/issues/520
             Rule::element => Self::parse(pair.into_inner().next().unwrap(), top_level)?,
 
-            _ => Err(pair)?,
+            _ => return Err(ParseError::UnparsedToken(pair.to_string())),
         })
     }
 }
@@ -493,18 +671,21 @@ pub fn parse(query: &str) -> Result<Vec<Query<'_>>, ParseError> {
         .map_err(Box::new)?
         .next()
         .unwrap();
-    let root =
-        Expr::parse(pair, true).map_err(|pair| ParseError::UnparsedToken(pair.to_string()))?;
+    let root = Expr::parse(pair, true)?;
 
     let mut qs = flatten(root);
 
     // Find and redistribute global options.
     let global_regex = qs.iter().fold(None, |a, e| e.global_regex.or(a));
     let case_sensitive = qs.iter().fold(None, |a, e| e.case_sensitive.or(a));
+    let structural = qs.iter().fold(None, |a, e| e.structural.or(a));
+    let commits = qs.iter().fold(None, |a, e| e.commits.or(a));
 
     for q in qs.iter_mut() {
         q.set_global_regex(global_regex);
         q.case_sensitive = case_sensitive;
+        q.structural = structural;
+        q.commits = commits;
     }
 
     Ok(qs.into_vec())
@@ -518,9 +699,13 @@ pub fn parse_nl(query: &str) -> Result<ParsedQuery<'_>, ParseError> {
     let mut langs = HashSet::new();
     let mut branch = HashSet::new();
     let mut target: Option<Literal> = None;
+    let mut after = None;
+    let mut before = None;
     let mut force_parsing_as = None;
+    let mut has_negations = false;
     for pair in pairs {
         match pair.as_rule() {
+            Rule::negation => has_negations = true,
             Rule::repo => {
                 let item = Literal::from(pair.into_inner().next().unwrap());
                 let _ = repos.insert(item);
@@ -537,6 +722,15 @@ pub fn parse_nl(query: &str) -> Result<ParsedQuery<'_>, ParseError> {
                 let item = super::languages::parse_alias(pair.into_inner().as_str().into());
                 let _ = langs.insert(item);
             }
+            Rule::after => {
+                let date = pair.into_inner().as_str();
+                after = Some(parse_date(date).ok_or_else(|| ParseError::InvalidDate(date.into()))?);
+            }
+            Rule::before => {
+                let date = pair.into_inner().as_str();
+                before =
+                    Some(parse_date(date).ok_or_else(|| ParseError::InvalidDate(date.into()))?);
+            }
             Rule::raw_text => {
                 let rhs = Literal::from(pair);
                 if let Some(t) = target {
@@ -554,6 +748,12 @@ pub fn parse_nl(query: &str) -> Result<ParsedQuery<'_>, ParseError> {
                     "semantic" if force_parsing_as.is_none() => {
                         force_parsing_as = Some(ForceParsingAs::Semantic);
                     }
+                    "structural" if force_parsing_as.is_none() => {
+                        force_parsing_as = Some(ForceParsingAs::Structural);
+                    }
+                    "commit" if force_parsing_as.is_none() => {
+                        force_parsing_as = Some(ForceParsingAs::Commit);
+                    }
                     _ => return Err(ParseError::MultiMode),
                 };
             }
@@ -562,13 +762,19 @@ pub fn parse_nl(query: &str) -> Result<ParsedQuery<'_>, ParseError> {
     }
 
     match force_parsing_as {
-        Some(ForceParsingAs::Grep) => parse(query).map(ParsedQuery::Grep),
+        Some(ForceParsingAs::Grep | ForceParsingAs::Structural | ForceParsingAs::Commit) => {
+            parse(query).map(ParsedQuery::Grep)
+        }
+        // Semantic search has no way to exclude results, so negations can't be honoured.
+        _ if has_negations => Err(ParseError::SemanticNegation),
         _ => Ok(ParsedQuery::Semantic(SemanticQuery {
             repos,
             paths,
             langs,
             branch,
             target,
+            after,
+            before,
         })),
     }
 }
@@ -600,10 +806,26 @@ fn flatten(root: Expr<'_>) -> SmallVec<[Query<'_>; 1]> {
             lang: Some(super::languages::parse_alias(lang)),
             ..Default::default()
         }],
+        Expr::Kind(kind) => smallvec![Query {
+            kind: Some(kind),
+            ..Default::default()
+        }],
         Expr::Content(lit) => smallvec![Query {
             target: Some(Target::Content(lit)),
             ..Default::default()
         }],
+        Expr::After(timestamp) => smallvec![Query {
+            after: Some(timestamp),
+            ..Default::default()
+        }],
+        Expr::Before(timestamp) => smallvec![Query {
+            before: Some(timestamp),
+            ..Default::default()
+        }],
+        Expr::Not(negation) => smallvec![Query {
+            negations: vec![negation],
+            ..Default::default()
+        }],
 
         Expr::CaseSensitive(case_sensitive) => smallvec![Query {
             case_sensitive: Some(case_sensitive),
@@ -617,6 +839,14 @@ fn flatten(root: Expr<'_>) -> SmallVec<[Query<'_>; 1]> {
             global_regex: Some(flag),
             ..Default::default()
         }],
+        Expr::GlobalMode(ForceParsingAs::Structural) => smallvec![Query {
+            structural: Some(true),
+            ..Default::default()
+        }],
+        Expr::GlobalMode(ForceParsingAs::Commit) => smallvec![Query {
+            commits: Some(true),
+            ..Default::default()
+        }],
         Expr::GlobalMode(_) => smallvec![Query {
             // we don't propagate this flag down to the query level!
             ..Default::default()
@@ -664,6 +894,15 @@ mod tests {
             }],
         );
 
+        assert_eq!(
+            parse("rev:v1.4.2 ParseError").unwrap(),
+            vec![Query {
+                branch: Some(Literal::Plain("v1.4.2".into())),
+                target: Some(Target::Content(Literal::Plain("ParseError".into()))),
+                ..Query::default()
+            }],
+        );
+
         assert_eq!(
             parse("org:bloopai repo:enterprise-search ParseError").unwrap(),
             vec![Query {
@@ -760,6 +999,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn structural_mode() {
+        let expected = vec![Query {
+            structural: Some(true),
+            lang: Some("rust".into()),
+            target: Some(Target::Content(Literal::Plain(
+                "(function_item name: (identifier) @name)".into(),
+            ))),
+            ..Query::default()
+        }];
+
+        assert_eq!(
+            parse("mode:structural lang:rust '(function_item name: (identifier) @name)'").unwrap(),
+            expected,
+        );
+
+        assert_eq!(
+            parse_nl("lang:rust '(function_item name: (identifier) @name)' mode:structural"),
+            Ok(ParsedQuery::Grep(expected)),
+        );
+
+        assert_eq!(
+            parse("repo:foo bar or repo:baz mode:structural").unwrap(),
+            vec![
+                Query {
+                    structural: Some(true),
+                    repo: Some(Literal::Plain("foo".into())),
+                    target: Some(Target::Content(Literal::Plain("bar".into()))),
+                    ..Query::default()
+                },
+                Query {
+                    structural: Some(true),
+                    repo: Some(Literal::Plain("baz".into())),
+                    ..Query::default()
+                },
+            ],
+        );
+
+        assert!(parse("(mode:structural foo)").is_err());
+        assert_eq!(
+            parse_nl("foo mode:structural mode:grep"),
+            Err(ParseError::MultiMode)
+        );
+    }
+
+    #[test]
+    fn commit_mode() {
+        let expected = vec![Query {
+            commits: Some(true),
+            repo: Some(Literal::Plain("bloop".into())),
+            path: Some(Literal::Plain("webserver".into())),
+            target: Some(Target::Content(Literal::Plain("fix".into()))),
+            ..Query::default()
+        }];
+
+        assert_eq!(
+            parse("mode:commit repo:bloop path:webserver fix").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_nl("fix repo:bloop path:webserver mode:commit"),
+            Ok(ParsedQuery::Grep(expected)),
+        );
+    }
+
     #[test]
     fn intersection_parse() {
         assert_eq!(
@@ -949,6 +1253,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn kind_filter() {
+        let queries = parse("symbol:parse kind:Function or kind:struct foo").unwrap();
+        assert_eq!(
+            queries,
+            vec![
+                Query {
+                    target: Some(Target::Symbol(Literal::Plain("parse".into()))),
+                    kind: Some("function".into()),
+                    ..Query::default()
+                },
+                Query {
+                    target: Some(Target::Content(Literal::Plain("foo".into()))),
+                    kind: Some("struct".into()),
+                    ..Query::default()
+                },
+            ],
+        );
+
+        assert_eq!(queries[0].symbol_kind(), Some(&"function".into()));
+        assert_eq!(queries[1].symbol_kind(), None);
+    }
+
+    #[test]
+    fn negation_parse() {
+        assert_eq!(
+            parse("ParseError -path:test not lang:markdown -content:TODO").unwrap(),
+            vec![Query {
+                target: Some(Target::Content(Literal::Plain("ParseError".into()))),
+                negations: vec![
+                    Negation::Path(Literal::Plain("test".into())),
+                    Negation::Lang("markdown".into()),
+                    Negation::Target(Target::Content(Literal::Plain("TODO".into()))),
+                ],
+                ..Query::default()
+            }],
+        );
+
+        assert_eq!(
+            parse("symbol:foo -symbol:/^foo_/ -repo:bar -branch:dev").unwrap(),
+            vec![Query {
+                target: Some(Target::Symbol(Literal::Plain("foo".into()))),
+                negations: vec![
+                    Negation::Target(Target::Symbol(Literal::Regex("^foo_".into()))),
+                    Negation::Repo(Literal::Plain("bar".into())),
+                    Negation::Branch(Literal::Plain("dev".into())),
+                ],
+                ..Query::default()
+            }],
+        );
+
+        // Negations are distributed over `or` groups, just like other terms.
+        assert_eq!(
+            parse("(foo or bar) -path:vendor").unwrap(),
+            vec![
+                Query {
+                    target: Some(Target::Content(Literal::Plain("foo".into()))),
+                    negations: vec![Negation::Path(Literal::Plain("vendor".into()))],
+                    ..Query::default()
+                },
+                Query {
+                    target: Some(Target::Content(Literal::Plain("bar".into()))),
+                    negations: vec![Negation::Path(Literal::Plain("vendor".into()))],
+                    ..Query::default()
+                },
+            ],
+        );
+
+        // Bare literals are never negated.
+        assert_eq!(
+            parse("-flag").unwrap(),
+            vec![Query {
+                target: Some(Target::Content(Literal::Plain("-flag".into()))),
+                ..Query::default()
+            }],
+        );
+    }
+
+    #[test]
+    fn date_parse() {
+        assert_eq!(
+            parse("foo after:2023-01-01 before:2023-07-01").unwrap(),
+            vec![Query {
+                target: Some(Target::Content(Literal::Plain("foo".into()))),
+                after: Some(1672531200),
+                before: Some(1688169600),
+                ..Query::default()
+            }],
+        );
+
+        let q = &parse("after:2023-01-01").unwrap()[0];
+        assert_eq!(
+            q.commit_time_bounds(),
+            Some((Bound::Included(1672531200), Bound::Unbounded)),
+        );
+        assert_eq!(parse("foo").unwrap()[0].commit_time_bounds(), None);
+
+        assert!(matches!(
+            parse("after:2023-13-01"),
+            Err(ParseError::InvalidDate(date)) if date == "2023-13-01"
+        ));
+        assert!(matches!(
+            parse("before:2023-02-30"),
+            Err(ParseError::InvalidDate(_))
+        ));
+
+        // Malformed dates are searched for as literals.
+        assert_eq!(
+            parse("after:yesterday").unwrap(),
+            vec![Query {
+                target: Some(Target::Content(Literal::Plain("after:yesterday".into()))),
+                ..Query::default()
+            }],
+        );
+    }
+
     #[test]
     fn enable_open() {
         assert_eq!(
@@ -1176,7 +1596,8 @@ mod tests {
                 langs: ["tsx".into()].into(),
                 repos: [Literal::Plain("bloop".into())].into(),
                 paths: [].into(),
-                branch: [].into()
+                branch: [].into(),
+                ..Default::default()
             }),
         );
     }
@@ -1190,6 +1611,19 @@ mod tests {
         assert_eq!(q.repos().count(), 1);
     }
 
+    #[test]
+    fn nl_parse_dates() {
+        let ParsedQuery::Semantic(q) = parse_nl("what changed recently? after:2023-01-01").unwrap()
+        else {
+            panic!("down with this sorta thing")
+        };
+        assert_eq!(q.after, Some(1672531200));
+        assert_eq!(q.before, None);
+        assert_eq!(q.target().unwrap(), "what changed recently?");
+
+        assert!(parse_nl("what changed? before:2023-02-30").is_err());
+    }
+
     #[test]
     fn nl_parse_multiple_filters() {
         assert_eq!(
@@ -1206,6 +1640,7 @@ mod tests {
                 ]
                 .into(),
                 paths: [Literal::Plain("server/bleep".into())].into(),
+                ..Default::default()
             })
         );
     }
@@ -1223,6 +1658,7 @@ mod tests {
                 repos: [Literal::Plain("bloop".into())].into(),
                 paths: [].into(),
                 branch: [].into(),
+                ..Default::default()
             })
         );
 
@@ -1238,6 +1674,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn nl_parse_negations() {
+        assert!(matches!(
+            parse_nl("where is the server started? -path:test"),
+            Err(ParseError::SemanticNegation)
+        ));
+        assert!(matches!(
+            parse_nl("where is the server started? not lang:markdown"),
+            Err(ParseError::SemanticNegation)
+        ));
+        assert!(matches!(
+            parse_nl("start -path:test mode:grep"),
+            Ok(ParsedQuery::Grep(_))
+        ));
+
+        // only labels are negated
+        let ParsedQuery::Semantic(q) = parse_nl("what does -flag do? not now").unwrap() else {
+            panic!("down with this sorta thing")
+        };
+        assert_eq!(q.target().unwrap(), "what does -flag do? not now");
+    }
+
     // NL queries should permit arbitrary text in the `target` field, such as `(` and `|`
     #[test]
     fn nl_parse_arbitrary_text() {
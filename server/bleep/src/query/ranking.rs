@@ -1,12 +1,13 @@
 use std::{sync::Arc, time::SystemTime};
 
 use tantivy::{
-    collector::{ScoreSegmentTweaker, ScoreTweaker},
+    collector::{CustomScorer, CustomSegmentScorer, ScoreSegmentTweaker, ScoreTweaker},
     fastfield::{BytesFastFieldReader, Column},
+    schema::Field,
     DocId, Score,
 };
 
-use crate::indexes::file::File;
+use crate::{indexes::file::File, symbol::FuzzySymbolMatcher};
 
 pub struct DocumentTweaker(pub File);
 pub struct SegmentScorer {
@@ -50,3 +51,68 @@ impl ScoreTweaker<Score> for DocumentTweaker {
         })
     }
 }
+
+/// Scores each document by its best fuzzy symbol match, or 0 if none of its symbols match.
+pub struct SymbolScorer {
+    pub schema: File,
+    pub matcher: Arc<FuzzySymbolMatcher>,
+}
+
+pub struct SegmentSymbolScorer {
+    symbols: BytesFastFieldReader,
+    matcher: Arc<FuzzySymbolMatcher>,
+}
+
+impl CustomSegmentScorer<u32> for SegmentSymbolScorer {
+    fn score(&mut self, doc: DocId) -> u32 {
+        std::str::from_utf8(self.symbols.get_bytes(doc))
+            .map(|symbols| {
+                symbols
+                    .lines()
+                    .filter_map(|name| self.matcher.score(name))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl CustomScorer<u32> for SymbolScorer {
+    type Child = SegmentSymbolScorer;
+
+    fn segment_scorer(
+        &self,
+        segment_reader: &tantivy::SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(SegmentSymbolScorer {
+            symbols: segment_reader
+                .fast_fields()
+                .bytes(self.schema.raw_symbols)?,
+            matcher: Arc::clone(&self.matcher),
+        })
+    }
+}
+
+/// Scores each document by a timestamp field, so that the newest documents come first.
+pub struct RecencyScorer(pub Field);
+
+pub struct SegmentRecencyScorer(Arc<dyn Column<u64>>);
+
+impl CustomSegmentScorer<u64> for SegmentRecencyScorer {
+    fn score(&mut self, doc: DocId) -> u64 {
+        self.0.get_val(doc)
+    }
+}
+
+impl CustomScorer<u64> for RecencyScorer {
+    type Child = SegmentRecencyScorer;
+
+    fn segment_scorer(
+        &self,
+        segment_reader: &tantivy::SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(SegmentRecencyScorer(
+            segment_reader.fast_fields().u64(self.0)?,
+        ))
+    }
+}
@@ -0,0 +1,238 @@
+//! Structural search, with tree-sitter query patterns.
+//!
+//! A structural query is written as a tree-sitter S-expression, for example:
+//!
+//! ```text
+//! mode:structural lang:rust '(call_expression function: (identifier) @f (#eq? @f "unwrap"))'
+//! ```
+//!
+//! Captured nodes are returned as highlights. Patterns without any captures are captured as a
+//! whole, so `(function_item)` highlights every function.
+
+use std::{mem, ops::Range};
+
+use anyhow::{bail, Context, Result};
+
+use crate::intelligence::{Language, TSLanguage, TSLanguageConfig, TreeSitterFile};
+
+/// The capture name given to patterns that don't capture anything themselves.
+const MATCH_CAPTURE: &str = "match";
+
+/// A tree-sitter query compiled for a single language.
+#[derive(Debug)]
+pub struct StructuralPattern {
+    lang: &'static TSLanguageConfig,
+    query: tree_sitter::Query,
+}
+
+impl StructuralPattern {
+    pub fn new(pattern: &str, lang_id: &str) -> Result<Self> {
+        let lang = match TSLanguage::from_id(lang_id) {
+            Language::Supported(lang) => lang,
+            Language::Unsupported => bail!("structural search is not supported for `{lang_id}`"),
+        };
+
+        let grammar = (lang.grammar)();
+        let mut query = tree_sitter::Query::new(grammar, pattern)
+            .with_context(|| format!("invalid structural pattern `{pattern}`"))?;
+
+        if query.capture_names().is_empty() {
+            query = tree_sitter::Query::new(grammar, &format!("{pattern} @{MATCH_CAPTURE}"))
+                .with_context(|| format!("structural pattern `{pattern}` needs a capture"))?;
+        }
+
+        Ok(Self { lang, query })
+    }
+
+    /// The first language id of the grammar this pattern was compiled for.
+    pub fn lang_id(&self) -> &'static str {
+        self.lang.language_ids[0]
+    }
+
+    /// Byte ranges of all nodes captured by this pattern in `src`, in order.
+    ///
+    /// Files that fail to parse have no matches.
+    pub fn matches(&self, src: &[u8]) -> Vec<Range<usize>> {
+        TreeSitterFile::try_build(src, self.lang_id())
+            .map(|file| file.capture_ranges(&self.query))
+            .unwrap_or_default()
+    }
+
+    pub fn is_match(&self, src: &[u8]) -> bool {
+        !self.matches(src).is_empty()
+    }
+}
+
+/// Find the longest piece of text that must appear verbatim in every file matched by `pattern`.
+///
+/// This is used to prefilter candidate files with the trigram index. Strings are only considered
+/// required when they are anonymous nodes or `#eq?` arguments, outside of alternations and
+/// quantifiers. Patterns with several top-level alternatives have no required text.
+pub fn required_literal(pattern: &str) -> Option<String> {
+    let nodes = Sexp::parse(pattern)?;
+    if nodes.len() != 1 {
+        return None;
+    }
+
+    let mut literals = Vec::new();
+    nodes[0].required_literals(&mut literals);
+    literals.into_iter().max_by_key(String::len)
+}
+
+/// A minimal reading of tree-sitter's query syntax, just enough to find literal strings.
+#[derive(Debug)]
+enum Sexp {
+    Str { text: String, optional: bool },
+    Group { children: Vec<Sexp>, optional: bool },
+    Alternation,
+    Atom(String),
+}
+
+impl Sexp {
+    fn parse(pattern: &str) -> Option<Vec<Self>> {
+        Self::parse_seq(&mut pattern.chars().peekable(), None)
+    }
+
+    fn parse_seq(
+        chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+        close: Option<char>,
+    ) -> Option<Vec<Self>> {
+        let mut nodes = Vec::new();
+        // Whether the last character closed a node, which a quantifier can then follow.
+        let mut closed = false;
+
+        while let Some(c) = chars.next() {
+            let quantifies = mem::take(&mut closed);
+            let node = match c {
+                c if c.is_whitespace() => continue,
+                ';' => {
+                    chars.find(|&c| c == '\n');
+                    continue;
+                }
+                c if Some(c) == close => return Some(nodes),
+                ')' | ']' => return None,
+                '(' => Self::Group {
+                    children: Self::parse_seq(chars, Some(')'))?,
+                    optional: false,
+                },
+                '[' => {
+                    Self::parse_seq(chars, Some(']'))?;
+                    Self::Alternation
+                }
+                '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => match chars.next()? {
+                                'n' => text.push('\n'),
+                                't' => text.push('\t'),
+                                'r' => text.push('\r'),
+                                '0' => text.push('\0'),
+                                c => text.push(c),
+                            },
+                            c => text.push(c),
+                        }
+                    }
+                    Self::Str {
+                        text,
+                        optional: false,
+                    }
+                }
+                // `+` repeats a node, which is then still required.
+                '+' if quantifies => continue,
+                '?' | '*' if quantifies => {
+                    match nodes.last_mut() {
+                        Some(Self::Str { optional, .. } | Self::Group { optional, .. }) => {
+                            *optional = true
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                c => {
+                    let mut atom = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || "()[]\";".contains(c) {
+                            break;
+                        }
+                        atom.push(c);
+                        chars.next();
+                    }
+                    Self::Atom(atom)
+                }
+            };
+
+            closed = !matches!(node, Self::Atom(_));
+            nodes.push(node);
+        }
+
+        close.is_none().then_some(nodes)
+    }
+
+    fn required_literals(&self, out: &mut Vec<String>) {
+        match self {
+            Self::Str {
+                text,
+                optional: false,
+            } if !text.is_empty() => out.push(text.clone()),
+            Self::Group {
+                children,
+                optional: false,
+            } => match children.first() {
+                // Only `#eq?` compares against exact text, other predicates may not. Missing
+                // nodes have no text in the file at all.
+                Some(Self::Atom(head))
+                    if head != "#eq?" && (head.starts_with('#') || head == "MISSING") => {}
+                _ => {
+                    for child in children {
+                        child.required_literals(out);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn required_literals() {
+        assert_eq!(
+            required_literal(
+                r#"(call_expression function: (identifier) @f (#eq? @f "unwrap_or_default"))"#
+            ),
+            Some("unwrap_or_default".into())
+        );
+        assert_eq!(
+            required_literal(r#"(function_item "async" name: (identifier) @name)"#),
+            Some("async".into())
+        );
+        assert_eq!(
+            required_literal(r#"((identifier) @x (#match? @x "^foo"))"#),
+            None
+        );
+        assert_eq!(required_literal(r#"(function_item "pub"? (block))"#), None);
+        assert_eq!(required_literal(r#"(block ("return")*)"#), None);
+        assert_eq!(
+            required_literal(r#"(block ("return")+ (#eq? @x "y"))"#),
+            Some("return".into())
+        );
+        assert_eq!(
+            required_literal(r#"(binary_expression operator: ["+" "-"])"#),
+            None
+        );
+        assert_eq!(required_literal(r#"("fn") ("struct")"#), None);
+        assert_eq!(required_literal(r#"(function_item "fn""#), None);
+        assert_eq!(
+            required_literal(
+                "; a comment with \"quotes\"\n(macro_invocation macro: (identifier) @m (#eq? @m \"println\"))"
+            ),
+            Some("println".into())
+        );
+    }
+}
@@ -15,12 +15,19 @@ use tracing::{debug, error, warn};
 
 use crate::{
     background::SyncHandle,
+    env::Feature,
     remotes,
-    repo::{Backend, RepoError, RepoRef, Repository, SyncStatus},
+    repo::{Backend, RepoError, RepoRef, RepoRemote, Repository, SyncStatus},
     Application,
 };
 
+pub mod git;
+pub mod gitea;
 pub mod github;
+pub mod gitlab;
+pub mod provider;
+
+use provider::{Api, Instances, Provider};
 
 type GitCreds = Account;
 
@@ -190,6 +197,13 @@ pub(crate) fn gather_repo_roots(
                     return Skip;
                 }
 
+                if ft.is_file() && de.file_name() == ".git" && is_worktree(de.path()) {
+                    _ = repos.insert(RepoRef::from(
+                        &crate::canonicalize(de.path().parent().expect("worktree has a parent"))
+                            .expect("worktree root is both a dir and exists"),
+                    ));
+                }
+
                 Continue
             })
         });
@@ -202,6 +216,23 @@ pub(crate) fn gather_repo_roots(
     output
 }
 
+/// Linked worktrees have a `.git` file that points to a directory in `.git/worktrees` of the main
+/// repository.
+///
+/// Checked out submodules also have a `.git` file, pointing to `.git/modules`. They are indexed as
+/// part of their parent repository instead.
+fn is_worktree(dot_git: &Path) -> bool {
+    let Ok(contents) = std::fs::read_to_string(dot_git) else {
+        return false;
+    };
+
+    contents
+        .strip_prefix("gitdir:")
+        .and_then(|gitdir| Path::new(gitdir.trim()).parent()?.file_name())
+        .map(|dir| dir == "worktrees")
+        .unwrap_or_default()
+}
+
 struct BackendEntry {
     inner: BackendCredential,
     updated: flume::Receiver<()>,
@@ -243,7 +274,14 @@ impl From<HashMap<Backend, BackendCredential>> for Backends {
 
 impl Backends {
     pub(crate) fn for_repo(&self, repo: &RepoRef) -> Option<BackendCredential> {
-        self.backends.read(&repo.backend(), |_, v| v.inner.clone())
+        let backend = repo.backend();
+        let creds = self.backends.read(&backend, |_, v| v.inner.clone());
+
+        // git remotes may be public, so they're synced anonymously unless credentials are set
+        match backend {
+            Backend::Git => creds.or_else(|| Some(BackendCredential::Git(Default::default()))),
+            _ => creds,
+        }
     }
 
     pub(crate) fn remove(&self, backend: impl Borrow<Backend>) -> Option<BackendCredential> {
@@ -251,25 +289,92 @@ impl Backends {
     }
 
     pub(crate) fn github(&self) -> Option<github::State> {
-        self.backends.read(&Backend::Github, |_, v| {
-            let BackendCredential::Github(ref github) = v.inner;
-            github.clone()
-        })
+        self.backends
+            .read(&Backend::Github, |_, v| match v.inner {
+                BackendCredential::Github(ref github) => Some(github.clone()),
+                _ => None,
+            })
+            .flatten()
     }
 
     pub(crate) fn set_github(&self, gh: github::State) {
+        self.set(Backend::Github, BackendCredential::Github(gh));
+    }
+
+    pub(crate) fn github_updated(&self) -> Option<flume::Receiver<()>> {
+        self.updated(&Backend::Github)
+    }
+
+    pub(crate) fn get(&self, backend: &Backend) -> Option<BackendCredential> {
+        self.backends.read(backend, |_, v| v.inner.clone())
+    }
+
+    pub(crate) fn set(&self, backend: Backend, creds: BackendCredential) {
         self.backends
-            .entry(Backend::Github)
+            .entry(backend)
             .and_modify(|existing| {
-                existing.inner = BackendCredential::Github(gh.clone());
+                existing.inner = creds.clone();
                 _ = existing.updated_tx.send(());
             })
-            .or_insert_with(|| BackendCredential::Github(gh).into());
+            .or_insert_with(|| creds.into());
     }
 
-    pub(crate) fn github_updated(&self) -> Option<flume::Receiver<()>> {
+    /// A channel that receives a message every time the credentials of `backend` change.
+    pub(crate) fn updated(&self, backend: &Backend) -> Option<flume::Receiver<()>> {
+        self.backends.read(backend, |_, v| v.updated.clone())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+
+    /// Repositories listed by every provider with credentials, with their repo refs.
+    pub(crate) async fn remote_repos(&self) -> Vec<(RepoRef, provider::RemoteRepo)> {
+        let mut output = vec![];
+        self.backends
+            .scan_async(|_, v| {
+                for provider in v.inner.providers() {
+                    output.extend(
+                        provider
+                            .repositories()
+                            .iter()
+                            .map(|repo| (provider.repo_ref(repo), repo.clone())),
+                    );
+                }
+            })
+            .await;
+
+        output
+    }
+
+    /// Every configured instance of a self-hostable provider.
+    pub(crate) fn instances<A: Api>(&self) -> Instances<A> {
+        self.get(&A::BACKEND)
+            .and_then(A::instances)
+            .unwrap_or_default()
+    }
+
+    /// Replace the instances of a provider, removing its credentials when there are none left.
+    pub(crate) fn set_instances<A: Api>(&self, instances: Instances<A>) {
+        if instances.is_empty() {
+            self.remove(A::BACKEND);
+        } else {
+            self.set(A::BACKEND, A::credential(instances));
+        }
+    }
+
+    pub(crate) fn git(&self) -> git::State {
         self.backends
-            .read(&Backend::Github, |_, v| v.updated.clone())
+            .read(&Backend::Git, |_, v| match v.inner {
+                BackendCredential::Git(ref git) => Some(git.clone()),
+                _ => None,
+            })
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_git(&self, git: git::State) {
+        self.set(Backend::Git, BackendCredential::Git(git));
     }
 
     pub(crate) async fn serialize(&self) -> impl Serialize + Send + Sync {
@@ -295,31 +400,81 @@ impl Backends {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum BackendCredential {
     Github(github::State),
+    Gitlab(Instances<gitlab::Gitlab>),
+    Gitea(Instances<gitea::Gitea>),
+    Git(git::State),
 }
 
 impl BackendCredential {
+    /// The hosting provider instances these credentials are for. Plain git remotes have none.
+    pub(crate) fn providers(&self) -> Vec<&dyn Provider> {
+        match self {
+            BackendCredential::Github(gh) => vec![gh],
+            BackendCredential::Gitlab(gitlab) => {
+                gitlab.iter().map(|i| i as &dyn Provider).collect()
+            }
+            BackendCredential::Gitea(gitea) => gitea.iter().map(|i| i as &dyn Provider).collect(),
+            BackendCredential::Git(_) => vec![],
+        }
+    }
+
+    async fn clone_repo(&self, repo: &Repository) -> Result<()> {
+        match self {
+            BackendCredential::Github(gh) => gh.clone_repo(repo).await,
+            BackendCredential::Gitlab(gitlab) => gitlab.clone_repo(repo).await,
+            BackendCredential::Gitea(gitea) => gitea.clone_repo(repo).await,
+            BackendCredential::Git(git) => git.clone_repo(repo).await,
+        }
+    }
+
+    async fn pull_repo(&self, repo: &Repository) -> Result<()> {
+        match self {
+            BackendCredential::Github(gh) => gh.pull_repo(repo).await,
+            BackendCredential::Gitlab(gitlab) => gitlab.pull_repo(repo).await,
+            BackendCredential::Gitea(gitea) => gitea.pull_repo(repo).await,
+            BackendCredential::Git(git) => git.pull_repo(repo).await,
+        }
+    }
+
     #[tracing::instrument(fields(repo=%sync_handle.reporef), skip_all)]
     pub(crate) async fn sync(self, sync_handle: &SyncHandle) -> Result<()> {
         let SyncHandle { app, .. } = sync_handle;
 
-        use BackendCredential::*;
         let existing = sync_handle.sync_lock().await;
 
-        let Github(gh) = self;
+        if let Ok(Repository {
+            remote: RepoRemote::Url(ref url),
+            ..
+        }) = existing
+        {
+            if !app.env.allow(Feature::GitRemotes) {
+                return Err(RemoteError::PermissionDenied);
+            }
+
+            // `file://` remotes are local paths, and follow the same rules as local repos
+            if let Some(path) = url.strip_prefix("file://") {
+                if !app.allow_path(path) {
+                    return Err(RemoteError::PermissionDenied);
+                }
+            } else if !app.env.allow(Feature::AnyPathScan) {
+                git::check_public_host(url).await?;
+            }
+        }
+
         let mut synced = match existing {
             Err(err) => return Err(err),
             Ok(repo) if repo.last_index_unix_secs == 0 && repo.disk_path.exists() => {
                 // it is possible syncing was killed, but the repo is
                 // intact. pull if the dir exists, then quietly revert
                 // to cloning if that fails
-                if let Ok(success) = gh.auth.pull_repo(&repo).await {
+                if let Ok(success) = self.pull_repo(&repo).await {
                     Ok(success)
                 } else {
-                    gh.auth.clone_repo(&repo).await
+                    self.clone_repo(&repo).await
                 }
             }
-            Ok(repo) if repo.last_index_unix_secs == 0 => gh.auth.clone_repo(&repo).await,
-            Ok(repo) => gh.auth.pull_repo(&repo).await,
+            Ok(repo) if repo.last_index_unix_secs == 0 => self.clone_repo(&repo).await,
+            Ok(repo) => self.pull_repo(&repo).await,
         };
 
         let new_status = match synced {
@@ -335,7 +490,7 @@ impl BackendCredential {
                 let removed = tokio::fs::remove_dir_all(&repo.disk_path).await;
                 debug!(?removed, "removing recursively");
 
-                synced = gh.auth.clone_repo(&repo).await;
+                synced = self.clone_repo(&repo).await;
                 match synced {
                     Ok(_) => SyncStatus::Queued,
                     Err(ref err) => SyncStatus::Error {
@@ -0,0 +1,182 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use reqwest::Url;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::repo::{url_location, RepoRemote, Repository};
+
+use super::*;
+
+/// Credentials for git servers other than GitHub, by host name.
+///
+/// Hosts without credentials are accessed anonymously. SSH remotes authenticate through the
+/// system's `ssh` configuration and agent, so they only need credentials for HTTP(S).
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct State {
+    pub hosts: BTreeMap<String, Auth>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct Auth {
+    pub username: String,
+    /// A password, or an access token issued by the git server
+    #[serde(serialize_with = "crate::config::serialize_secret_str")]
+    pub password: SecretString,
+}
+
+impl State {
+    pub(crate) async fn clone_repo(&self, repo: &Repository) -> Result<()> {
+        let url = self.check_repo(repo)?;
+        git_clone(self.git_cred(url), url, &repo.disk_path).await
+    }
+
+    pub(crate) async fn pull_repo(&self, repo: &Repository) -> Result<()> {
+        let url = self.check_repo(repo)?;
+        git_pull(self.git_cred(url), repo).await
+    }
+
+    fn check_repo<'a>(&self, repo: &'a Repository) -> Result<&'a str> {
+        match repo.remote {
+            RepoRemote::Url(ref url) => Ok(url),
+            _ => Err(RemoteError::NotSupported(
+                "git backend without a remote url",
+            )),
+        }
+    }
+
+    fn git_cred(&self, url: &str) -> GitCreds {
+        match self.hosts.get(url_location(url)[0]) {
+            Some(auth) => GitCreds {
+                username: auth.username.clone(),
+                password: auth.password.expose_secret().into(),
+            },
+            None => GitCreds {
+                username: "".into(),
+                password: "".into(),
+            },
+        }
+    }
+}
+
+/// Check that every address the host of `url` resolves to is public, so that cloning can't be
+/// used to reach services on the server's own network.
+pub(crate) async fn check_public_host(url: &str) -> Result<()> {
+    let url = Url::parse(url).map_err(|_| RepoError::UnsupportedRemote)?;
+    let host = url.host_str().unwrap_or_default();
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) if host.is_empty() => vec![],
+        Err(_) => tokio::net::lookup_host((host, url.port_or_known_default().unwrap_or_default()))
+            .await?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+
+    if addrs.is_empty() || !addrs.iter().all(is_public) {
+        return Err(RemoteError::NotSupported(
+            "remote on a private or link-local network",
+        ));
+    }
+
+    Ok(())
+}
+
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(&ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    let shared = a == 100 && (b & 0xc0) == 64;
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || shared)
+}
+
+fn is_public_v6(ip: &Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    let unique_local = (first & 0xfe00) == 0xfc00;
+    let link_local = (first & 0xffc0) == 0xfe80;
+
+    !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn credentials_by_host() {
+        let state = State {
+            hosts: BTreeMap::from([(
+                "git.example.com".to_owned(),
+                Auth {
+                    username: "bloop".into(),
+                    password: "token".to_owned().into(),
+                },
+            )]),
+        };
+
+        let cred = state.git_cred("https://git.example.com/team/project.git");
+        assert_eq!(cred.username, "bloop");
+        assert_eq!(cred.password, "token");
+
+        let cred = state.git_cred("https://other.example.com/team/project.git");
+        assert_eq!(cred.username, "");
+    }
+
+    #[test]
+    fn private_addresses() {
+        for ip in [
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(&ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["140.82.121.3", "2606:4700::1111", "::ffff:140.82.121.3"] {
+            assert!(is_public(&ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn private_hosts_are_rejected() {
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "https://[::1]/team/project.git",
+            "ssh://git@127.0.0.1:2222/team/project.git",
+        ] {
+            assert!(check_public_host(url).await.is_err(), "{url}");
+        }
+
+        assert!(check_public_host("https://140.82.121.3/team/project.git")
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+
+use super::{
+    provider::{Api, Instances, RemoteRepo},
+    *,
+};
+
+/// Gitea, or a self-hosted Gitea instance, accessed through access tokens.
+#[derive(Clone, Debug)]
+pub(crate) enum Gitea {}
+
+#[derive(Deserialize)]
+pub(crate) struct Repo {
+    full_name: String,
+    clone_url: String,
+    ssh_url: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<Repo> for RemoteRepo {
+    fn from(r: Repo) -> Self {
+        RemoteRepo {
+            name: r.full_name,
+            clone_url: r.clone_url,
+            ssh_url: r.ssh_url,
+            pushed_at: r.updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct User {
+    login: String,
+}
+
+impl From<User> for String {
+    fn from(user: User) -> Self {
+        user.login
+    }
+}
+
+impl Api for Gitea {
+    const BACKEND: Backend = Backend::Gitea;
+    const NAME: &'static str = "Gitea";
+    const DEFAULT_URL: &'static str = "https://gitea.com";
+    const API_PATH: &'static str = "/api/v1";
+    const REPO_LIST: &'static str = "/user/repos";
+
+    type Repo = Repo;
+    type User = User;
+
+    fn repo_list_query(page: usize) -> Vec<(&'static str, String)> {
+        vec![("limit", "50".into()), ("page", page.to_string())]
+    }
+
+    fn authorize(request: RequestBuilder, token: &str) -> RequestBuilder {
+        request.header("Authorization", format!("token {token}"))
+    }
+
+    fn git_cred(token: &str) -> GitCreds {
+        GitCreds {
+            username: token.into(),
+            password: "".into(),
+        }
+    }
+
+    fn credential(instances: Instances<Self>) -> BackendCredential {
+        BackendCredential::Gitea(instances)
+    }
+
+    fn instances(creds: BackendCredential) -> Option<Instances<Self>> {
+        match creds {
+            BackendCredential::Gitea(instances) => Some(instances),
+            _ => None,
+        }
+    }
+}
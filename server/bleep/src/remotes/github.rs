@@ -10,13 +10,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::repo::{GitRemote, RepoRemote, Repository};
 
-use super::*;
+use super::{
+    provider::{Provider, RemoteRepo},
+    *,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct State {
     pub auth: Auth,
     #[serde(skip)]
-    pub repositories: Arc<Vec<octocrab::models::Repository>>,
+    pub repositories: Arc<Vec<RemoteRepo>>,
 }
 
 impl State {
@@ -31,7 +34,54 @@ impl State {
         self.auth.client()
     }
 
-    pub(crate) async fn validate(&self) -> Result<Option<String>> {
+    pub(crate) fn expiry(&self) -> Option<DateTime<Utc>> {
+        match self.auth {
+            Auth::App { expiry, .. } => Some(expiry),
+            _ => None,
+        }
+    }
+
+    /// Create a new object with the updated repositories list
+    ///
+    /// This is a separate step from refreshing the repo list to avoid
+    /// async locking
+    pub fn update_repositories(self, repos: Vec<RemoteRepo>) -> Self {
+        Self {
+            auth: self.auth,
+            repositories: repos.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for State {
+    fn backend(&self) -> Backend {
+        Backend::Github
+    }
+
+    fn repositories(&self) -> &[RemoteRepo] {
+        &self.repositories
+    }
+
+    /// Get a representative list of repositories currently accessible
+    async fn current_repo_list(&self) -> Result<Vec<RemoteRepo>> {
+        Ok(self
+            .auth
+            .list_repos()
+            .await?
+            .into_iter()
+            .filter_map(|origin| {
+                Some(RemoteRepo {
+                    name: origin.full_name?,
+                    clone_url: origin.clone_url?.to_string(),
+                    ssh_url: origin.ssh_url,
+                    pushed_at: origin.pushed_at.unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    async fn validate(&self) -> Result<Option<String>> {
         let client = self.client()?;
 
         let username = match client.current().user().await {
@@ -51,27 +101,12 @@ impl State {
         Ok(username)
     }
 
-    pub(crate) fn expiry(&self) -> Option<DateTime<Utc>> {
-        match self.auth {
-            Auth::App { expiry, .. } => Some(expiry),
-            _ => None,
-        }
+    async fn clone_repo(&self, repo: &Repository) -> Result<()> {
+        self.auth.clone_repo(repo).await
     }
 
-    /// Get a representative list of repositories currently accessible
-    pub async fn current_repo_list(&self) -> Result<Vec<octocrab::models::Repository>> {
-        self.auth.list_repos().await
-    }
-
-    /// Create a new object with the updated repositories list
-    ///
-    /// This is a separate step from refreshing the repo list to avoid
-    /// async locking
-    pub fn update_repositories(self, repos: Vec<octocrab::models::Repository>) -> Self {
-        Self {
-            auth: self.auth,
-            repositories: repos.into(),
-        }
+    async fn pull_repo(&self, repo: &Repository) -> Result<()> {
+        self.auth.pull_repo(repo).await
     }
 }
 
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+
+use super::{
+    provider::{Api, Instances, RemoteRepo},
+    *,
+};
+
+/// GitLab, or a self-hosted GitLab instance, accessed through personal access tokens.
+#[derive(Clone, Debug)]
+pub(crate) enum Gitlab {}
+
+#[derive(Deserialize)]
+pub(crate) struct Project {
+    path_with_namespace: String,
+    http_url_to_repo: String,
+    ssh_url_to_repo: Option<String>,
+    last_activity_at: DateTime<Utc>,
+}
+
+impl From<Project> for RemoteRepo {
+    fn from(p: Project) -> Self {
+        RemoteRepo {
+            name: p.path_with_namespace,
+            clone_url: p.http_url_to_repo,
+            ssh_url: p.ssh_url_to_repo,
+            pushed_at: p.last_activity_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct User {
+    username: String,
+}
+
+impl From<User> for String {
+    fn from(user: User) -> Self {
+        user.username
+    }
+}
+
+impl Api for Gitlab {
+    const BACKEND: Backend = Backend::Gitlab;
+    const NAME: &'static str = "GitLab";
+    const DEFAULT_URL: &'static str = "https://gitlab.com";
+    const API_PATH: &'static str = "/api/v4";
+    const REPO_LIST: &'static str = "/projects";
+
+    type Repo = Project;
+    type User = User;
+
+    fn repo_list_query(page: usize) -> Vec<(&'static str, String)> {
+        vec![
+            ("membership", "true".into()),
+            ("simple", "true".into()),
+            ("per_page", "100".into()),
+            ("page", page.to_string()),
+        ]
+    }
+
+    fn authorize(request: RequestBuilder, token: &str) -> RequestBuilder {
+        request.header("PRIVATE-TOKEN", token)
+    }
+
+    fn git_cred(token: &str) -> GitCreds {
+        GitCreds {
+            username: "oauth2".into(),
+            password: token.into(),
+        }
+    }
+
+    fn credential(instances: Instances<Self>) -> BackendCredential {
+        BackendCredential::Gitlab(instances)
+    }
+
+    fn instances(creds: BackendCredential) -> Option<Instances<Self>> {
+        match creds {
+            BackendCredential::Gitlab(instances) => Some(instances),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::{collections::BTreeMap, fmt::Debug, marker::PhantomData};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::*;
+use crate::repo::{url_location, Backend, GitRemote, RepoRef, RepoRemote, Repository};
+
+/// A repository that a [`Provider`] gives access to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct RemoteRepo {
+    /// Path of the repository on its host, e.g. `group/subgroup/project`
+    pub name: String,
+    pub clone_url: String,
+    pub ssh_url: Option<String>,
+    pub pushed_at: DateTime<Utc>,
+}
+
+/// A git hosting service that lists the repositories its user can access, such as GitHub or
+/// GitLab.
+#[async_trait]
+pub(crate) trait Provider: Send + Sync {
+    fn backend(&self) -> Backend;
+
+    /// Repositories fetched by the last refresh of the repository list.
+    fn repositories(&self) -> &[RemoteRepo];
+
+    /// Fetch the repositories currently accessible with these credentials.
+    async fn current_repo_list(&self) -> Result<Vec<RemoteRepo>>;
+
+    /// Check that these credentials are still valid, returning the user they belong to.
+    ///
+    /// Errors are only returned if the credentials were rejected. Other failures return
+    /// `Ok(None)`, to be retried later.
+    async fn validate(&self) -> Result<Option<String>>;
+
+    async fn clone_repo(&self, repo: &Repository) -> Result<()>;
+
+    async fn pull_repo(&self, repo: &Repository) -> Result<()>;
+
+    fn repo_ref(&self, repo: &RemoteRepo) -> RepoRef {
+        RepoRef::new(self.backend(), &repo.name).expect("remote repo names are valid")
+    }
+}
+
+/// The parts of a self-hostable provider's REST API that differ between providers, such as GitLab
+/// and Gitea.
+///
+/// Everything else is shared by [`Instance`].
+pub(crate) trait Api: Clone + Debug + Send + Sync + 'static {
+    const BACKEND: Backend;
+
+    /// Name of the provider, for logs
+    const NAME: &'static str;
+
+    /// The public instance, used when no URL is given
+    const DEFAULT_URL: &'static str;
+
+    /// Path of the REST API, relative to the URL of the instance
+    const API_PATH: &'static str;
+
+    /// Endpoint that lists the repositories accessible to the authenticated user
+    const REPO_LIST: &'static str;
+
+    type Repo: DeserializeOwned + Into<RemoteRepo>;
+    type User: DeserializeOwned + Into<String>;
+
+    /// Query parameters that select a page of the repository list, starting at 1.
+    fn repo_list_query(page: usize) -> Vec<(&'static str, String)>;
+
+    fn authorize(request: RequestBuilder, token: &str) -> RequestBuilder;
+
+    fn git_cred(token: &str) -> GitCreds;
+
+    fn credential(instances: Instances<Self>) -> BackendCredential;
+
+    fn instances(creds: BackendCredential) -> Option<Instances<Self>>;
+}
+
+/// Access to a single instance of a provider, through an access token.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "")]
+pub(crate) struct Instance<A> {
+    /// Base URL of the instance, e.g. `https://gitlab.com`
+    pub url: String,
+    #[serde(serialize_with = "crate::config::serialize_secret_str")]
+    pub token: SecretString,
+    #[serde(skip)]
+    pub repositories: Arc<Vec<RemoteRepo>>,
+    #[serde(skip)]
+    api: PhantomData<A>,
+}
+
+impl<A: Api> Instance<A> {
+    pub(crate) fn new(url: Option<String>, token: SecretString) -> Self {
+        Self {
+            url: url
+                .as_deref()
+                .unwrap_or(A::DEFAULT_URL)
+                .trim_end_matches('/')
+                .to_owned(),
+            token,
+            repositories: Arc::default(),
+            api: PhantomData,
+        }
+    }
+
+    /// Host name of the instance. Repo refs of its repositories start with it, so that
+    /// repositories of different instances don't collide.
+    pub(crate) fn host(&self) -> &str {
+        url_location(&self.url)[0]
+    }
+
+    /// Create a new object with the updated repositories list
+    pub(crate) fn update_repositories(self, repos: Vec<RemoteRepo>) -> Self {
+        Self {
+            repositories: repos.into(),
+            ..self
+        }
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        let request = reqwest::Client::new().get(format!("{}{}{path}", self.url, A::API_PATH));
+        A::authorize(request, self.token.expose_secret())
+    }
+
+    /// The HTTPS clone URL of a repository on this instance.
+    fn clone_url(&self, repo: &Repository) -> Result<String> {
+        let RepoRemote::Git(GitRemote { ref address, .. }) = repo.remote else {
+            return Err(RemoteError::NotSupported("provider without git backend"));
+        };
+
+        Ok(format!("{}/{address}.git", self.url))
+    }
+}
+
+#[async_trait]
+impl<A: Api> Provider for Instance<A> {
+    fn backend(&self) -> Backend {
+        A::BACKEND
+    }
+
+    fn repositories(&self) -> &[RemoteRepo] {
+        &self.repositories
+    }
+
+    async fn current_repo_list(&self) -> Result<Vec<RemoteRepo>> {
+        let mut results = vec![];
+        for page in 1.. {
+            let repos = self
+                .get(A::REPO_LIST)
+                .query(&A::repo_list_query(page))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .with_context(|| format!("failed to list {} repositories", A::NAME))?
+                .json::<Vec<A::Repo>>()
+                .await
+                .with_context(|| format!("invalid {} repository list", A::NAME))?;
+
+            if repos.is_empty() {
+                break;
+            }
+
+            results.extend(repos.into_iter().map(Into::into));
+        }
+
+        Ok(results)
+    }
+
+    async fn validate(&self) -> Result<Option<String>> {
+        match self.get("/user").send().await {
+            Ok(response) if response.status() == StatusCode::UNAUTHORIZED => {
+                warn!(
+                    provider = A::NAME,
+                    url = %self.url,
+                    "failed to validate token"
+                );
+                Err(RemoteError::PermissionDenied)
+            }
+            Ok(response) => Ok(response.json::<A::User>().await.ok().map(Into::into)),
+            Err(e) => {
+                // swallow the error to try again on the next poll
+                error!(
+                    ?e,
+                    provider = A::NAME,
+                    url = %self.url,
+                    "failed to make user request"
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    async fn clone_repo(&self, repo: &Repository) -> Result<()> {
+        let cred = A::git_cred(self.token.expose_secret());
+        git_clone(cred, &self.clone_url(repo)?, &repo.disk_path).await
+    }
+
+    async fn pull_repo(&self, repo: &Repository) -> Result<()> {
+        git_pull(A::git_cred(self.token.expose_secret()), repo).await
+    }
+
+    fn repo_ref(&self, repo: &RemoteRepo) -> RepoRef {
+        RepoRef::new(A::BACKEND, &format!("{}/{}", self.host(), repo.name))
+            .expect("remote repo names are valid")
+    }
+}
+
+/// Every configured instance of a provider, by host name.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "", transparent)]
+pub(crate) struct Instances<A>(BTreeMap<String, Instance<A>>);
+
+impl<A> Default for Instances<A> {
+    fn default() -> Self {
+        Self(BTreeMap::default())
+    }
+}
+
+impl<A: Api> Instances<A> {
+    /// Add an instance, replacing the credentials of an existing instance on the same host.
+    pub(crate) fn insert(&mut self, instance: Instance<A>) {
+        self.0.insert(instance.host().to_owned(), instance);
+    }
+
+    pub(crate) fn remove(&mut self, host: &str) -> Option<Instance<A>> {
+        self.0.remove(host)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Instance<A>> {
+        self.0.values()
+    }
+
+    /// Validate the credentials of every instance, and refresh its repository list.
+    ///
+    /// Instances whose credentials are rejected are left out.
+    pub(crate) async fn refresh(&self) -> Self {
+        let mut refreshed = Self::default();
+
+        for (host, instance) in &self.0 {
+            match instance.validate().await {
+                Ok(user) => debug!(provider = A::NAME, %host, ?user, "credentials validated"),
+                Err(err) => {
+                    warn!(
+                        provider = A::NAME,
+                        %host,
+                        ?err,
+                        "credentials are invalid; removing"
+                    );
+                    continue;
+                }
+            }
+
+            let instance = match instance.current_repo_list().await {
+                Ok(repos) => instance.clone().update_repositories(repos),
+                Err(err) => {
+                    warn!(
+                        provider = A::NAME,
+                        %host,
+                        ?err,
+                        "failed to list repositories"
+                    );
+                    instance.clone()
+                }
+            };
+
+            refreshed.0.insert(host.clone(), instance);
+        }
+
+        refreshed
+    }
+
+    /// The instance that hosts `repo`.
+    fn for_repo(&self, repo: &Repository) -> Result<&Instance<A>> {
+        let RepoRemote::Git(GitRemote { ref host, .. }) = repo.remote else {
+            return Err(RemoteError::NotSupported("provider without git backend"));
+        };
+
+        self.0.get(host).ok_or(RemoteError::RemoteNotFound)
+    }
+
+    pub(crate) async fn clone_repo(&self, repo: &Repository) -> Result<()> {
+        self.for_repo(repo)?.clone_repo(repo).await
+    }
+
+    pub(crate) async fn pull_repo(&self, repo: &Repository) -> Result<()> {
+        self.for_repo(repo)?.pull_repo(repo).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remotes::gitlab::Gitlab;
+    use pretty_assertions::assert_eq;
+
+    fn remote_repo(name: &str) -> RemoteRepo {
+        RemoteRepo {
+            name: name.into(),
+            clone_url: String::new(),
+            ssh_url: None,
+            pushed_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn instances_by_host() {
+        let public = Instance::<Gitlab>::new(None, "token".to_owned().into());
+        let hosted = Instance::<Gitlab>::new(
+            Some("https://gitlab.example.com/".into()),
+            "token".to_owned().into(),
+        );
+
+        let public_ref = public.repo_ref(&remote_repo("group/project"));
+        let hosted_ref = hosted.repo_ref(&remote_repo("group/project"));
+        assert_eq!(public_ref.to_string(), "gitlab.com/group/project");
+        assert_eq!(
+            hosted_ref.to_string(),
+            "gitlab/gitlab.example.com/group/project"
+        );
+        assert_eq!(
+            RepoRemote::from(&hosted_ref).to_string(),
+            "https://gitlab.example.com/group/project.git"
+        );
+
+        let mut instances = Instances::default();
+        instances.insert(public);
+        instances.insert(hosted);
+        assert_eq!(instances.len(), 2);
+        assert!(instances.remove("gitlab.example.com").is_some());
+        assert_eq!(
+            instances.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(),
+            ["https://gitlab.com"]
+        );
+    }
+}
@@ -1,5 +1,4 @@
 use anyhow::Context;
-use regex::RegexSet;
 use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     collections::BTreeSet,
@@ -13,6 +12,7 @@ use tracing::debug;
 
 use crate::state::get_relative_path;
 
+pub(crate) mod blame;
 pub(crate) mod iterator;
 use iterator::language;
 
@@ -22,6 +22,14 @@ use iterator::language;
 pub enum Backend {
     Local,
     Github,
+    /// GitLab, or a self-hosted GitLab instance. Repo names start with the host of the instance.
+    /// Repo refs are `gitlab.com/group/project`, or `gitlab/<host>/group/project` for other hosts
+    Gitlab,
+    /// Gitea, or a self-hosted Gitea instance. Repo names start with the host of the instance.
+    /// Repo refs are `gitea.com/org/repo`, or `gitea/<host>/org/repo` for other hosts
+    Gitea,
+    /// Any git remote, by its URL
+    Git,
 }
 
 // Repository identifier
@@ -40,6 +48,17 @@ impl RepoRef {
                 backend,
                 name: name.as_ref().to_owned(),
             }),
+            Gitlab | Gitea => match name.as_ref().split_once('/') {
+                Some((host, path)) if !host.is_empty() && !path.is_empty() => Ok(RepoRef {
+                    backend,
+                    name: name.as_ref().to_owned(),
+                }),
+                _ => Err(RepoError::InvalidPath),
+            },
+            Git => match name.as_ref().parse::<RepoRemote>() {
+                Ok(RepoRemote::Url(url)) => Ok(RepoRef { backend, name: url }),
+                _ => Err(RepoError::UnsupportedRemote),
+            },
             Local => {
                 let path = Path::new(name.as_ref());
 
@@ -67,6 +86,8 @@ impl RepoRef {
         let refstr = components.join("/");
         let pathstr = match refstr.trim_start_matches('/').split_once('/') {
             Some(("github.com", name)) => return RepoRef::new(Backend::Github, name),
+            Some(("gitlab.com" | "gitea.com" | "gitlab" | "gitea", _)) => return refstr.parse(),
+            Some(("git", name)) => return RepoRef::new(Backend::Git, name),
             Some(("local", name)) => name,
             _ => &refstr,
         };
@@ -94,6 +115,8 @@ impl RepoRef {
     pub fn indexed_name(&self) -> String {
         // Local repos indexed as: dirname
         // Github repos indexed as: github.com/org/repo
+        // Gitlab & Gitea repos indexed as: host/group/project
+        // Git repos indexed as: host/path/to/repo
         match self.backend {
             Backend::Local => Path::new(&self.name)
                 .file_name()
@@ -101,6 +124,8 @@ impl RepoRef {
                 .to_string_lossy()
                 .into(),
             Backend::Github => format!("{}", self),
+            Backend::Gitlab | Backend::Gitea => self.name.to_owned(),
+            Backend::Git => url_location(&self.name).join("/"),
         }
     }
 
@@ -108,11 +133,31 @@ impl RepoRef {
         match self.backend {
             // org_name/repo_name
             Backend::Github => self.name.to_owned(),
+            // group/project, without the host
+            Backend::Gitlab | Backend::Gitea => self.remote_path().to_owned(),
+            // path/to/repo
+            Backend::Git => url_location(&self.name)[1..].join("/"),
             // repo_name
             Backend::Local => self.indexed_name(),
         }
     }
 
+    /// The host of a GitLab or Gitea repository.
+    fn remote_host(&self) -> &str {
+        self.name
+            .split_once('/')
+            .map(|(host, _)| host)
+            .unwrap_or_default()
+    }
+
+    /// The path of a GitLab or Gitea repository on its host.
+    fn remote_path(&self) -> &str {
+        self.name
+            .split_once('/')
+            .map(|(_, path)| path)
+            .unwrap_or(&self.name)
+    }
+
     pub fn local_path(&self) -> Option<PathBuf> {
         match self.backend {
             Backend::Local => Some(PathBuf::from(&self.name)),
@@ -150,6 +195,16 @@ impl FromStr for RepoRef {
         match refstr.trim_start_matches('/').split_once('/') {
             // github.com/...
             Some(("github.com", name)) => RepoRef::new(Backend::Github, name),
+            // gitlab.com/...
+            Some(("gitlab.com", _)) => RepoRef::new(Backend::Gitlab, refstr),
+            // gitlab/gitlab.example.com/...
+            Some(("gitlab", name)) => RepoRef::new(Backend::Gitlab, name),
+            // gitea.com/...
+            Some(("gitea.com", _)) => RepoRef::new(Backend::Gitea, refstr),
+            // gitea/gitea.example.com/...
+            Some(("gitea", name)) => RepoRef::new(Backend::Gitea, name),
+            // git/https://...
+            Some(("git", name)) => RepoRef::new(Backend::Git, name),
             // local/...
             Some(("local", name)) => RepoRef::new(Backend::Local, name),
             _ => Err(RepoError::InvalidBackend),
@@ -161,6 +216,11 @@ impl Display for RepoRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.backend() {
             Backend::Github => write!(f, "github.com/{}", self.name()),
+            Backend::Gitlab if self.remote_host() == "gitlab.com" => write!(f, "{}", self.name()),
+            Backend::Gitlab => write!(f, "gitlab/{}", self.name()),
+            Backend::Gitea if self.remote_host() == "gitea.com" => write!(f, "{}", self.name()),
+            Backend::Gitea => write!(f, "gitea/{}", self.name()),
+            Backend::Git => write!(f, "git/{}", self.name()),
             Backend::Local => write!(f, "local/{}", self.name()),
         }
     }
@@ -191,6 +251,8 @@ impl<'de> Deserialize<'de> for RepoRef {
 pub enum BranchFilter {
     All,
     Head,
+    /// Patterns of branches and tags to index, or IDs of commits. Tags and commits are only
+    /// indexed when selected here.
     Select(Vec<String>),
 }
 
@@ -218,11 +280,34 @@ impl From<&BranchFilter> for iterator::BranchFilter {
         match value {
             BranchFilter::All => iterator::BranchFilter::All,
             BranchFilter::Head => iterator::BranchFilter::Head,
-            BranchFilter::Select(regexes) => {
-                let mut regexes = regexes.clone();
-                regexes.push("HEAD".into());
-                iterator::BranchFilter::Select(RegexSet::new(regexes).unwrap())
-            }
+            BranchFilter::Select(patterns) => iterator::BranchFilter::select(patterns).unwrap(),
+        }
+    }
+}
+
+/// Per-repository settings that decide which files are indexed, on top of the `.gitignore` and
+/// `.bloopignore` files in the repository.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct IndexSettings {
+    /// Globs of paths to index. If empty, every path is indexed.
+    pub include: Vec<String>,
+    /// Globs of paths to skip, using `.gitignore` syntax
+    pub exclude: Vec<String>,
+    /// Files larger than this many bytes are skipped
+    pub max_file_size: u64,
+    /// Whether to index the files inside zip, jar and tar archives, as if the archives were
+    /// directories
+    pub expand_archives: bool,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec![],
+            max_file_size: iterator::MAX_FILE_LEN,
+            expand_archives: false,
         }
     }
 }
@@ -236,6 +321,24 @@ pub struct Repository {
     pub last_index_unix_secs: u64,
     pub most_common_lang: Option<String>,
     pub branch_filter: Option<BranchFilter>,
+    #[serde(default)]
+    pub index_settings: IndexSettings,
+    /// The commit the repository was last indexed at, if only `HEAD` is indexed. Later syncs
+    /// only reindex the paths that changed since.
+    #[serde(default)]
+    pub last_indexed_commit: Option<IndexedCommit>,
+    /// The commit `HEAD` was indexed at when the commit history was last indexed. Later syncs
+    /// only index the commits made since.
+    #[serde(default)]
+    pub last_history_commit: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct IndexedCommit {
+    /// The branch `HEAD` was indexed as, or `None` if it was detached
+    pub branch: Option<String>,
+    /// Full hex ID of the commit
+    pub id: String,
 }
 
 impl Repository {
@@ -274,21 +377,43 @@ impl Repository {
             remote,
             most_common_lang: None,
             branch_filter: None,
+            index_settings: IndexSettings::default(),
+            last_indexed_commit: None,
+            last_history_commit: None,
         }
     }
 
     /// Pre-scan the repository to provide supporting metadata for a
     /// new indexing operation
-    pub async fn get_repo_metadata(&self) -> Arc<RepoMetadata> {
-        let last_commit_unix_secs = gix::open(&self.disk_path)
-            .context("failed to open git repo")
-            .and_then(|repo| Ok(repo.head()?.peel_to_commit_in_place()?.time()?.seconds))
+    pub async fn get_repo_metadata(&self, reporef: &RepoRef) -> Arc<RepoMetadata> {
+        // Remote repositories are only fetched, so their local `HEAD` never moves, unlike the
+        // remote branch it is indexed as.
+        let indexed_head = iterator::indexed_head(reporef, &self.disk_path)
+            .map_err(|err| debug!(?err, %reporef, "failed to find indexed HEAD"))
             .ok();
+        let head_commit = indexed_head.as_ref().and_then(|head| {
+            gix::open(&self.disk_path)
+                .context("failed to open git repo")
+                .and_then(|repo| {
+                    let id = gix::ObjectId::from_hex(head.id.as_bytes())?;
+                    let commit = repo.find_object(id)?.try_into_commit()?;
+                    Ok((commit.time()?.seconds, head.id.clone()))
+                })
+                .ok()
+        });
+        let last_commit_unix_secs = head_commit.as_ref().map(|(unix_secs, _)| *unix_secs);
+
+        let head = match self.branch_filter {
+            None | Some(BranchFilter::Head) if last_commit_unix_secs.is_some() => indexed_head,
+            _ => None,
+        };
 
         let langs = Default::default();
 
         RepoMetadata {
             last_commit_unix_secs,
+            head,
+            head_commit: head_commit.map(|(_, id)| id),
             langs,
         }
         .into()
@@ -313,6 +438,8 @@ impl Repository {
     ) {
         self.last_index_unix_secs = get_unix_time(SystemTime::now());
         self.last_commit_unix_secs = metadata.last_commit_unix_secs.unwrap_or(0);
+        self.last_indexed_commit = metadata.head.clone();
+        self.last_history_commit = metadata.head_commit.clone();
         self.most_common_lang = metadata
             .langs
             .most_common_lang()
@@ -336,6 +463,10 @@ fn get_unix_time(time: SystemTime) -> u64 {
 #[derive(Debug)]
 pub struct RepoMetadata {
     pub last_commit_unix_secs: Option<u64>,
+    /// The commit `HEAD` is indexed at, if only `HEAD` is indexed
+    pub head: Option<IndexedCommit>,
+    /// The commit `HEAD` is indexed at, whose history is indexed
+    pub head_commit: Option<String>,
     pub langs: language::LanguageInfo,
 }
 
@@ -402,6 +533,8 @@ pub enum GitProtocol {
 #[serde(rename_all = "snake_case")]
 pub enum RepoRemote {
     Git(GitRemote),
+    /// A git remote on any host, as a URL that can be passed to `git clone`
+    Url(String),
     None,
 }
 
@@ -416,6 +549,18 @@ impl<T: AsRef<RepoRef>> From<T> for RepoRemote {
                 host: "github.com".to_owned(),
                 address: name.to_owned(),
             }),
+            reporef @ RepoRef {
+                backend: Backend::Gitlab | Backend::Gitea,
+                ..
+            } => RepoRemote::Git(GitRemote {
+                protocol: GitProtocol::Https,
+                host: reporef.remote_host().to_owned(),
+                address: reporef.remote_path().to_owned(),
+            }),
+            RepoRef {
+                backend: Backend::Git,
+                name,
+            } => RepoRemote::Url(name.to_owned()),
             RepoRef {
                 backend: Backend::Local,
                 name: _name,
@@ -435,6 +580,7 @@ impl Display for RepoRemote {
                 GitProtocol::Https => write!(f, "https://{host}/{address}.git"),
                 GitProtocol::Ssh => write!(f, "git@{host}:{address}.git"),
             },
+            RepoRemote::Url(url) => write!(f, "{url}"),
             RepoRemote::None => write!(f, "none"),
         }
     }
@@ -467,10 +613,36 @@ impl FromStr for RepoRemote {
             }));
         }
 
+        // any other git server, as a URL with a scheme supported by `gix`
+        const SCHEMES: &[&str] = &["https://", "http://", "ssh://", "git://", "file://"];
+        if SCHEMES.iter().any(|scheme| value.starts_with(scheme)) {
+            return Ok(RepoRemote::Url(value.trim_end_matches('/').to_owned()));
+        }
+
         Err(())
     }
 }
 
+/// The host and path segments of a git remote URL, without the scheme, user, port or `.git`
+/// suffix.
+///
+/// For example, `ssh://git@git.example.com:2222/team/project.git` is
+/// `["git.example.com", "team", "project"]`. A `file://` URL has an empty host.
+pub(crate) fn url_location(url: &str) -> Vec<&str> {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+
+    std::iter::once(host)
+        .chain(
+            path.trim_end_matches(".git")
+                .split('/')
+                .filter(|segment| !segment.is_empty()),
+        )
+        .collect()
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RepoError {
     #[error("no source configured")]
@@ -481,6 +653,8 @@ pub enum RepoError {
     InvalidPath,
     #[error("backend not recognized")]
     InvalidBackend,
+    #[error("git remote must be an https, http, ssh, git or file URL")]
+    UnsupportedRemote,
     #[error("IO error: {error}")]
     IO {
         #[from]
@@ -501,6 +675,93 @@ pub enum RepoError {
 #[cfg(test)]
 mod test {
     use super::*;
+    use tempdir::TempDir;
+
+    /// Run git in `dir` as a fixed user, outside of any global configuration, dating commits at
+    /// `unix_secs`. Returns the trimmed output.
+    fn git(dir: &Path, unix_secs: u64, args: &[&str]) -> String {
+        let date = format!("@{unix_secs} +0000");
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "Ferris")
+            .env("GIT_AUTHOR_EMAIL", "ferris@example.com")
+            .env("GIT_COMMITTER_NAME", "Ferris")
+            .env("GIT_COMMITTER_EMAIL", "ferris@example.com")
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {args:?} failed: {output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    #[tokio::test]
+    async fn fetched_history_is_indexed() {
+        let tmpdir = TempDir::new("test-fetched-history").unwrap();
+        let origin = tmpdir.path().join("origin");
+        let clone = tmpdir.path().join("clone");
+        std::fs::create_dir(&origin).unwrap();
+
+        let commit = |unix_secs: u64, contents: &str| {
+            std::fs::write(origin.join("a.txt"), contents).unwrap();
+            git(&origin, unix_secs, &["add", "-A"]);
+            git(&origin, unix_secs, &["commit", "-q", "-m", contents]);
+            git(&origin, unix_secs, &["rev-parse", "HEAD"])
+        };
+
+        git(&origin, 0, &["init", "-q", "-b", "main"]);
+        let first = commit(1, "first");
+        git(
+            tmpdir.path(),
+            1,
+            &["clone", "-q", "--no-checkout", "origin", "clone"],
+        );
+
+        // remote repositories are only fetched, so the local `main` stays at `first`
+        let second = commit(2, "second");
+        git(&clone, 2, &["fetch", "-q"]);
+
+        let reporef = "github.com/bloopai/bloop".parse::<RepoRef>().unwrap();
+        let repo = Repository {
+            disk_path: clone.clone(),
+            remote: RepoRemote::from(&reporef),
+            sync_status: SyncStatus::Done,
+            last_commit_unix_secs: 1,
+            last_index_unix_secs: 1,
+            most_common_lang: None,
+            branch_filter: None,
+            index_settings: IndexSettings::default(),
+            last_indexed_commit: None,
+            last_history_commit: Some(first),
+        };
+
+        let metadata = repo.get_repo_metadata(&reporef).await;
+        assert_eq!(metadata.head_commit.as_ref(), Some(&second));
+        assert_eq!(metadata.last_commit_unix_secs, Some(2));
+        assert_eq!(
+            metadata.head,
+            Some(IndexedCommit {
+                branch: Some("origin/main".into()),
+                id: second.clone(),
+            })
+        );
+
+        let history =
+            iterator::commit_history(&clone, &second, repo.last_history_commit.as_deref(), 10);
+        assert!(history.incremental);
+        assert_eq!(
+            history
+                .commits
+                .iter()
+                .map(|commit| commit.id.as_str())
+                .collect::<Vec<_>>(),
+            [second.as_str()]
+        );
+    }
 
     #[test]
     fn parse_reporef() {
@@ -516,6 +777,23 @@ mod test {
             "local//tmp/repository".parse::<RepoRef>().unwrap(),
             RepoRef::new(Backend::Local, "/tmp/repository").unwrap()
         );
+        assert_eq!(
+            "gitlab.com/group/subgroup/project"
+                .parse::<RepoRef>()
+                .unwrap(),
+            RepoRef::new(Backend::Gitlab, "gitlab.com/group/subgroup/project").unwrap()
+        );
+        assert_eq!(
+            "gitlab/gitlab.example.com/group/project"
+                .parse::<RepoRef>()
+                .unwrap(),
+            RepoRef::new(Backend::Gitlab, "gitlab.example.com/group/project").unwrap()
+        );
+        assert_eq!(
+            "gitea.com/org/repo".parse::<RepoRef>().unwrap(),
+            RepoRef::new(Backend::Gitea, "gitea.com/org/repo").unwrap()
+        );
+        assert!("gitea/gitea.example.com".parse::<RepoRef>().is_err());
         if "repository".parse::<RepoRef>().is_ok() {
             panic!("non-absolute local allowed")
         }
@@ -556,4 +834,34 @@ mod test {
         assert_eq!(ssh, "git@github.com:org/repo.git/".parse().unwrap());
         assert_eq!(ssh, "git@github.com:/org/repo.git/".parse().unwrap());
     }
+
+    #[test]
+    fn parse_git_reporef() {
+        let reporef = "git/ssh://git@git.example.com:2222/team/project.git"
+            .parse::<RepoRef>()
+            .unwrap();
+
+        assert_eq!(reporef.backend(), Backend::Git);
+        assert_eq!(
+            reporef.name(),
+            "ssh://git@git.example.com:2222/team/project.git"
+        );
+        assert_eq!(reporef.indexed_name(), "git.example.com/team/project");
+        assert_eq!(reporef.display_name(), "team/project");
+        assert_eq!(
+            reporef.to_string(),
+            "git/ssh://git@git.example.com:2222/team/project.git"
+        );
+        assert_eq!(
+            RepoRemote::from(&reporef),
+            RepoRemote::Url("ssh://git@git.example.com:2222/team/project.git".into())
+        );
+
+        let local = RepoRef::new(Backend::Git, "file:///srv/git/project.git/").unwrap();
+        assert_eq!(local.name(), "file:///srv/git/project.git");
+        assert_eq!(local.display_name(), "srv/git/project");
+
+        assert!(RepoRef::new(Backend::Git, "/srv/git/project.git").is_err());
+        assert!(RepoRef::new(Backend::Git, "ftp://example.com/project.git").is_err());
+    }
 }
@@ -0,0 +1,267 @@
+use std::{ops::Range, path::Path};
+
+use anyhow::{Context, Result};
+use gix::{
+    diff::blob::{diff, intern::InternedInput, sources::lines, Algorithm},
+    ObjectId,
+};
+use serde::{Deserialize, Serialize};
+
+/// The most commits visited while attributing lines. Lines that are older than that are
+/// attributed to the oldest commit visited.
+const MAX_COMMITS: usize = 10_000;
+
+/// A range of lines that were last changed by the same commit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlameRange {
+    /// 1-indexed line number at which the range starts
+    pub line_start: usize,
+    /// 1-indexed line number at which the range ends, inclusive
+    pub line_end: usize,
+    /// Full hex ID of the commit
+    pub commit_id: String,
+    pub author: String,
+    pub unix_secs: u64,
+}
+
+/// The ID of the commit `rev` points to, and of the blob at `path` in that commit, or `None` if
+/// there is no such file.
+pub fn blob_id(dir: &Path, rev: &str, path: &str) -> Result<Option<(ObjectId, ObjectId)>> {
+    let git = open(dir)?;
+    let commit = git.rev_parse_single(rev)?.object()?.try_into_commit()?;
+    Ok(blob_at(&commit, path)?.map(|blob| (commit.id, blob)))
+}
+
+/// Attribute each line of the file at `path` to the last commit that changed it, walking the
+/// history from `rev`.
+///
+/// Commits are compared against their first parent only, so lines brought in by a merge are
+/// attributed to the merge commit. Renames aren't followed: lines of a renamed file are
+/// attributed to the commit that renamed it.
+pub fn blame(dir: &Path, rev: &str, path: &str) -> Result<Vec<BlameRange>> {
+    let git = open(dir)?;
+    let mut commit = git.rev_parse_single(rev)?.object()?.try_into_commit()?;
+    let mut blob = blob_at(&commit, path)?.context("file not found")?;
+    let mut content = read_blob(&git, blob)?;
+
+    // for each line of the file, its line number in the version being compared, until the line
+    // is attributed to a commit
+    let mut origins = (0..lines(&content).count()).map(Some).collect::<Vec<_>>();
+    let mut owners = vec![None; origins.len()];
+    let mut commits = vec![];
+
+    for _ in 0..MAX_COMMITS {
+        let parent = match commit.parent_ids().next() {
+            Some(id) => Some(id.object()?.try_into_commit()?),
+            None => None,
+        };
+        let parent_blob = match &parent {
+            Some(parent) => blob_at(parent, path)?,
+            None => None,
+        };
+
+        if parent_blob != Some(blob) {
+            let parent_content = match parent_blob {
+                Some(id) => read_blob(&git, id)?,
+                None => String::new(),
+            };
+
+            let unchanged = unchanged_lines(&parent_content, &content);
+            commits.push(commit.id);
+
+            for (origin, owner) in origins.iter_mut().zip(&mut owners) {
+                let Some(line) = *origin else {
+                    continue;
+                };
+
+                *origin = unchanged[line];
+                if origin.is_none() {
+                    *owner = Some(commits.len() - 1);
+                }
+            }
+
+            content = parent_content;
+        }
+
+        match (parent, parent_blob) {
+            (Some(parent), Some(id)) if origins.iter().any(Option::is_some) => {
+                commit = parent;
+                blob = id;
+            }
+            _ => break,
+        }
+    }
+
+    // lines older than the commits visited belong to the oldest commit visited
+    if origins.iter().any(Option::is_some) {
+        if commits.last() != Some(&commit.id) {
+            commits.push(commit.id);
+        }
+
+        for (origin, owner) in origins.iter().zip(&mut owners) {
+            if origin.is_some() {
+                *owner = Some(commits.len() - 1);
+            }
+        }
+    }
+
+    let mut ranges = Vec::<BlameRange>::new();
+    for (line, owner) in owners.into_iter().enumerate() {
+        let id = commits[owner.expect("every line is attributed")];
+
+        match ranges.last_mut() {
+            Some(last) if last.commit_id == id.to_string() => last.line_end = line + 1,
+            _ => {
+                let commit = git.find_object(id)?.try_into_commit()?;
+                let author = commit.author()?;
+                ranges.push(BlameRange {
+                    line_start: line + 1,
+                    line_end: line + 1,
+                    commit_id: id.to_string(),
+                    author: author.name.to_string(),
+                    unix_secs: author.time.seconds,
+                });
+            }
+        }
+    }
+
+    Ok(ranges)
+}
+
+fn open(dir: &Path) -> Result<gix::Repository> {
+    Ok(gix::open::Options::isolated()
+        .filter_config_section(|_| false)
+        .open(dir)?
+        .to_thread_local())
+}
+
+fn blob_at(commit: &gix::Commit<'_>, path: &str) -> Result<Option<ObjectId>> {
+    Ok(commit
+        .tree()?
+        .peel_to_entry_by_path(path)?
+        .filter(|entry| entry.mode().is_blob())
+        .map(|entry| entry.object_id()))
+}
+
+fn read_blob(git: &gix::Repository, id: ObjectId) -> Result<String> {
+    Ok(String::from_utf8_lossy(&git.find_object(id)?.data).into_owned())
+}
+
+/// Map each line of `after` to the line it was unchanged from in `before`, if any.
+fn unchanged_lines(before: &str, after: &str) -> Vec<Option<usize>> {
+    let input = InternedInput::new(before, after);
+    let mut unchanged = vec![None; input.after.len()];
+    let (mut old, mut new) = (0, 0);
+
+    let mut skip_to = |before: Range<u32>, after: Range<u32>| {
+        while new < after.start as usize {
+            unchanged[new] = Some(old);
+            old += 1;
+            new += 1;
+        }
+
+        old = before.end as usize;
+        new = after.end as usize;
+    };
+
+    diff(Algorithm::Histogram, &input, &mut skip_to);
+
+    // lines after the last change are unchanged
+    let (old_len, new_len) = (input.before.len() as u32, input.after.len() as u32);
+    skip_to(old_len..old_len, new_len..new_len);
+
+    unchanged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    /// Run git in `dir` outside of any global configuration, authored by `author` at `unix_secs`,
+    /// and committed a second later. Returns the trimmed output.
+    fn git(dir: &Path, author: &str, unix_secs: u64, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", author)
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_COMMITTER_NAME", "Ferris")
+            .env("GIT_COMMITTER_EMAIL", "ferris@example.com")
+            .env("GIT_AUTHOR_DATE", format!("@{unix_secs} +0000"))
+            .env("GIT_COMMITTER_DATE", format!("@{} +0000", unix_secs + 1))
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {args:?} failed: {output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Write `files` to the work tree of `dir`, and commit everything. Returns the commit ID.
+    fn commit(dir: &Path, author: &str, unix_secs: u64, files: &[(&str, &str)]) -> String {
+        for (path, contents) in files {
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+
+        git(dir, author, unix_secs, &["add", "-A"]);
+        git(dir, author, unix_secs, &["commit", "-q", "-m", "commit"]);
+        git(dir, author, unix_secs, &["rev-parse", "HEAD"])
+    }
+
+    #[test]
+    fn blame_by_path() {
+        let tmpdir = TempDir::new("test-blame").unwrap();
+        let dir = tmpdir.path();
+        git(dir, "Ferris", 0, &["init", "-q", "-b", "main"]);
+
+        let first = commit(dir, "Ferris", 10, &[("a.txt", "a\nb\nc\n")]);
+        let second = commit(dir, "Crab", 20, &[("a.txt", "a\nx\nc\n")]);
+        let third = commit(dir, "Ferris", 30, &[("b.txt", "a\nx\nc\n")]);
+
+        let range = |line: usize, commit: &str, author: &str, unix_secs: u64| BlameRange {
+            line_start: line,
+            line_end: line,
+            commit_id: commit.to_owned(),
+            author: author.to_owned(),
+            unix_secs,
+        };
+
+        assert_eq!(
+            blame(dir, "HEAD", "a.txt").unwrap(),
+            [
+                range(1, &first, "Ferris", 10),
+                range(2, &second, "Crab", 20),
+                range(3, &first, "Ferris", 10),
+            ]
+        );
+
+        // the same blob at another path has its own history
+        let (head, a) = blob_id(dir, "HEAD", "a.txt").unwrap().unwrap();
+        let (_, b) = blob_id(dir, "main", "b.txt").unwrap().unwrap();
+        assert_eq!(head.to_string(), third);
+        assert_eq!(a, b);
+        assert_eq!(
+            blame(dir, "HEAD", "b.txt").unwrap(),
+            [BlameRange {
+                line_end: 3,
+                ..range(1, &third, "Ferris", 30)
+            }]
+        );
+
+        assert!(blob_id(dir, "HEAD", "c.txt").unwrap().is_none());
+        assert!(blame(dir, "HEAD", "c.txt").is_err());
+    }
+
+    #[test]
+    fn test_unchanged_lines() {
+        assert_eq!(
+            unchanged_lines("a\nb\nc\n", "a\nx\nb\nc\ny\n"),
+            [Some(0), None, Some(1), Some(2), None]
+        );
+        assert_eq!(unchanged_lines("", "a\nb\n"), [None, None]);
+        assert_eq!(unchanged_lines("a\nb\n", "b\n"), [Some(1)]);
+    }
+}
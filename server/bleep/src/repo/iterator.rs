@@ -1,18 +1,28 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+use anyhow::Result;
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    overrides::{Override, OverrideBuilder},
+};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use smallvec::SmallVec;
 use tracing::warn;
 
+mod archive;
 mod fs;
 mod git;
 pub(super) mod language;
 
+pub use archive::ARCHIVE_SEPARATOR;
 pub use fs::FileWalker;
-pub use git::{BranchFilter, GitWalker};
+pub use git::{commit_history, indexed_blob, indexed_head, ref_commits, BranchFilter, GitWalker};
 
-use crate::background::SyncPipes;
+use crate::{background::SyncPipes, repo::IndexSettings};
 
 // Empirically calculated using:
 //     cat **/*.rs | awk '{SUM+=length;N+=1}END{print SUM/N}'
@@ -54,17 +64,45 @@ impl RepoDirEntry {
             RepoDirEntry::Other => None,
         }
     }
+
+    pub fn last_commit(&self) -> Option<&LastCommit> {
+        match self {
+            RepoDirEntry::Dir(d) => d.last_commit.as_ref(),
+            RepoDirEntry::File(f) => f.last_commit.as_ref(),
+            RepoDirEntry::Other => None,
+        }
+    }
 }
 
 pub struct RepoDir {
     pub path: String,
     pub branches: Vec<String>,
+    pub last_commit: Option<LastCommit>,
 }
 
 pub struct RepoFile {
     pub path: String,
     pub buffer: String,
     pub branches: Vec<String>,
+    pub last_commit: Option<LastCommit>,
+}
+
+/// The last commit that touched an entry, if it could be found in the git history.
+#[derive(Clone, Debug)]
+pub struct LastCommit {
+    pub unix_secs: u64,
+    pub author: String,
+}
+
+/// A commit in the history of a repository.
+#[derive(Clone, Debug)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author: String,
+    pub unix_secs: u64,
+    pub message: String,
+    /// Paths changed by the commit, relative to the repository root
+    pub paths: Vec<String>,
 }
 
 #[derive(Hash, Eq, PartialEq)]
@@ -74,8 +112,117 @@ pub enum FileType {
     Other,
 }
 
-fn should_index_entry(de: &ignore::DirEntry) -> bool {
-    should_index(&de.path())
+/// Name of the file at the root of a repository that lists paths to skip, using `.gitignore`
+/// syntax.
+pub const BLOOPIGNORE: &str = ".bloopignore";
+
+/// Decides which files of a repository are indexed, combining the built-in rules of
+/// [`should_index`] with the `.bloopignore` file and the [`IndexSettings`] of the repository.
+#[derive(Clone)]
+pub struct FileFilter {
+    root: PathBuf,
+    bloopignore: Gitignore,
+    include: Override,
+    exclude: Gitignore,
+    pub max_file_size: u64,
+    pub expand_archives: bool,
+}
+
+impl FileFilter {
+    /// Errors are only returned for invalid globs in the settings. Invalid lines in
+    /// `.bloopignore` are skipped.
+    pub fn new(root: impl AsRef<Path>, settings: &IndexSettings) -> Result<Self> {
+        let root = root.as_ref();
+
+        let mut bloopignore = GitignoreBuilder::new(root);
+        if let Some(err) = bloopignore.add(root.join(BLOOPIGNORE)) {
+            if !matches!(err.io_error(), Some(io) if io.kind() == std::io::ErrorKind::NotFound) {
+                warn!(%err, ?root, "failed to read {BLOOPIGNORE}");
+            }
+        }
+
+        let mut include = OverrideBuilder::new(root);
+        for glob in &settings.include {
+            include.add(glob)?;
+        }
+
+        let mut exclude = GitignoreBuilder::new(root);
+        for glob in &settings.exclude {
+            exclude.add_line(None, glob)?;
+        }
+
+        Ok(Self {
+            root: root.to_owned(),
+            bloopignore: bloopignore.build()?,
+            include: include.build()?,
+            exclude: exclude.build()?,
+            max_file_size: settings.max_file_size,
+            expand_archives: settings.expand_archives,
+        })
+    }
+
+    /// Replace the rules read from `.bloopignore` on disk with `contents`, as committed in git.
+    ///
+    /// Bare clones of remote repositories have no working tree to read the file from.
+    pub fn with_bloopignore(self, contents: &str) -> Self {
+        let from = self.root.join(BLOOPIGNORE);
+        let mut bloopignore = GitignoreBuilder::new(&self.root);
+        for line in contents.lines() {
+            if let Err(err) = bloopignore.add_line(Some(from.clone()), line) {
+                warn!(%err, root = ?self.root, "invalid line in {BLOOPIGNORE}");
+            }
+        }
+
+        match bloopignore.build() {
+            Ok(bloopignore) => Self {
+                bloopignore,
+                ..self
+            },
+            Err(err) => {
+                warn!(%err, root = ?self.root, "failed to read {BLOOPIGNORE}");
+                self
+            }
+        }
+    }
+
+    pub fn is_indexed(&self, path: impl AsRef<Path>, is_dir: bool) -> bool {
+        let path = path.as_ref();
+        if !should_index(&path) && !(!is_dir && self.is_expanded(path)) {
+            return false;
+        }
+
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+
+        !(self
+            .bloopignore
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+            || self
+                .exclude
+                .matched_path_or_any_parents(relative, is_dir)
+                .is_ignore()
+            || self.include.matched(relative, is_dir).is_ignore())
+    }
+
+    /// Whether the file at `path` is an archive whose contents are indexed instead of the file
+    /// itself.
+    pub fn is_expanded(&self, path: impl AsRef<Path>) -> bool {
+        self.expand_archives && archive::is_archive(path)
+    }
+
+    /// Whether the file at `path` is small enough to be indexed, at `len` bytes. Files of exactly
+    /// `max_file_size` bytes are indexed. Expanded archives may be larger, as the size of each of
+    /// their files is checked instead.
+    pub fn is_within_size_limit(&self, path: impl AsRef<Path>, len: u64) -> bool {
+        let max_len = match self.is_expanded(path) {
+            true => archive::MAX_ARCHIVE_LEN,
+            false => self.max_file_size,
+        };
+
+        len <= max_len
+    }
 }
 
 fn should_index<P: AsRef<Path>>(p: &P) -> bool {
@@ -196,4 +343,83 @@ mod test {
             assert_eq!(should_index(&Path::new(path)), index);
         }
     }
+
+    #[test]
+    fn test_file_filter() {
+        let tmpdir = tempdir::TempDir::new("test-file-filter").unwrap();
+        let root = tmpdir.path();
+        std::fs::write(root.join(BLOOPIGNORE), "fixtures/\n*.generated.ts\n").unwrap();
+
+        let settings = IndexSettings {
+            include: vec!["src/**".into(), "fixtures/**".into()],
+            exclude: vec!["src/legacy/".into()],
+            ..Default::default()
+        };
+        let filter = FileFilter::new(root, &settings).unwrap();
+
+        let tests = [
+            ("src", true, true),
+            ("src/main.rs", false, true),
+            ("src/image.png", false, false),
+            ("src/api.generated.ts", false, false),
+            ("src/legacy", true, false),
+            ("src/legacy/main.rs", false, false),
+            ("fixtures/big.json", false, false),
+            ("docs/README.md", false, false),
+        ];
+
+        for (path, is_dir, index) in tests {
+            assert_eq!(filter.is_indexed(root.join(path), is_dir), index, "{path}");
+        }
+
+        let expanded = FileFilter::new(
+            root,
+            &IndexSettings {
+                expand_archives: true,
+                ..settings
+            },
+        )
+        .unwrap();
+
+        assert!(!filter.is_indexed(root.join("src/lib.jar"), false));
+        assert!(expanded.is_indexed(root.join("src/lib.jar"), false));
+        assert!(expanded.is_indexed(root.join("src/vendor.tar.gz"), false));
+        assert!(!expanded.is_indexed(root.join("src/legacy/lib.jar"), false));
+
+        // the committed file replaces the one on disk
+        let committed = filter.clone().with_bloopignore("src/main.rs\n[\n");
+        assert!(committed.is_indexed(root.join("fixtures/big.json"), false));
+        assert!(committed.is_indexed(root.join("src/api.generated.ts"), false));
+        assert!(!committed.is_indexed(root.join("src/main.rs"), false));
+
+        let invalid = IndexSettings {
+            exclude: vec!["src/[".into()],
+            ..Default::default()
+        };
+        assert!(FileFilter::new(root, &invalid).is_err());
+    }
+
+    #[test]
+    fn files_of_the_max_size_are_indexed() {
+        let tmpdir = tempdir::TempDir::new("test-max-file-size").unwrap();
+        let root = tmpdir.path();
+        std::fs::write(root.join("exact.rs"), "a".repeat(10_000)).unwrap();
+        std::fs::write(root.join("over.rs"), "a".repeat(10_001)).unwrap();
+
+        let filter = |max_file_size| {
+            let settings = IndexSettings {
+                max_file_size,
+                ..Default::default()
+            };
+            FileFilter::new(root, &settings).unwrap()
+        };
+
+        assert!(filter(10_000).is_within_size_limit(root.join("exact.rs"), 10_000));
+        assert!(!filter(10_000).is_within_size_limit(root.join("over.rs"), 10_001));
+
+        // only the files differ between walks, the root directory fits every limit
+        let walked = |max_file_size| FileWalker::index_directory(root, filter(max_file_size)).len();
+        assert_eq!(walked(10_000), walked(9_999) + 1);
+        assert_eq!(walked(10_001), walked(10_000) + 1);
+    }
 }
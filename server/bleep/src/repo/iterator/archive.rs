@@ -0,0 +1,365 @@
+//! Expand archives into virtual directories, so that the text files inside them are indexed as
+//! if they were part of the repository.
+
+use super::*;
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use tracing::warn;
+
+use std::{
+    collections::BTreeSet,
+    io::{Cursor, Read},
+    path::{Component, Path},
+};
+
+/// Separates the path of an archive from the path of a file inside it, as in
+/// `path/to/lib.jar!/com/foo/Bar.java`.
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+/// Archives larger than this are not expanded.
+pub const MAX_ARCHIVE_LEN: u64 = 100 * 1024 * 1024;
+
+/// Reading an archive stops after this many entries, whether they're indexed or not.
+pub const MAX_ARCHIVE_ENTRIES: usize = 10_000;
+
+/// Reading an archive stops once this many bytes have been decompressed from it, so that a small
+/// archive can't expand into more than the index can hold.
+pub const MAX_EXPANDED_LEN: u64 = 256 * 1024 * 1024;
+
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+        if [".zip", ".jar", ".war", ".ear"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+        {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether the file at `path` is an archive that can be expanded.
+pub fn is_archive(path: impl AsRef<Path>) -> bool {
+    Format::of(path.as_ref()).is_some()
+}
+
+/// Expand the archive at `path`, whose contents are `data`.
+///
+/// Produces a directory entry for the archive itself and for each directory inside it, and a
+/// file entry for each UTF-8 file inside it that would be indexed in a repository. All entries
+/// share the branches and last commit of the archive. Archives inside archives aren't expanded.
+///
+/// Archives that can't be read are skipped, or cut short at the first corrupt entry, or once
+/// [`MAX_ARCHIVE_ENTRIES`] or [`MAX_EXPANDED_LEN`] is reached.
+pub fn expand(
+    path: &str,
+    data: &[u8],
+    branches: &[String],
+    last_commit: Option<&LastCommit>,
+    max_file_size: u64,
+) -> Vec<RepoDirEntry> {
+    let mut files = vec![];
+    let mut limits = Limits::default();
+    let read = match Format::of(Path::new(path)) {
+        Some(Format::Zip) => read_zip(data, max_file_size, &mut limits, &mut files),
+        Some(Format::Tar) => read_tar(data, max_file_size, &mut limits, &mut files),
+        Some(Format::TarGz) => {
+            read_tar(GzDecoder::new(data), max_file_size, &mut limits, &mut files)
+        }
+        None => return vec![],
+    };
+
+    if let Err(err) = read {
+        warn!(?err, path, "failed to read archive; skipping the rest");
+    }
+
+    let root = format!("{path}!");
+    let dirs = files
+        .iter()
+        .flat_map(|(inner, _)| {
+            inner
+                .match_indices('/')
+                .map(|(end, _)| inner[..end].to_owned())
+        })
+        .collect::<BTreeSet<_>>();
+
+    let dir = |path| {
+        RepoDirEntry::Dir(RepoDir {
+            path,
+            branches: branches.to_vec(),
+            last_commit: last_commit.cloned(),
+        })
+    };
+
+    std::iter::once(dir(root.clone()))
+        .chain(dirs.into_iter().map(|inner| dir(format!("{root}/{inner}"))))
+        .chain(files.into_iter().map(|(inner, buffer)| {
+            RepoDirEntry::File(RepoFile {
+                path: format!("{root}/{inner}"),
+                buffer,
+                branches: branches.to_vec(),
+                last_commit: last_commit.cloned(),
+            })
+        }))
+        .collect()
+}
+
+/// What's left to read of an archive before expanding it stops.
+struct Limits {
+    entries: usize,
+    bytes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            entries: MAX_ARCHIVE_ENTRIES,
+            bytes: MAX_EXPANDED_LEN,
+        }
+    }
+}
+
+impl Limits {
+    fn entry(&mut self) -> Result<()> {
+        self.entries = self
+            .entries
+            .checked_sub(1)
+            .with_context(|| format!("archive has more than {MAX_ARCHIVE_ENTRIES} entries"))?;
+        Ok(())
+    }
+
+    fn decompress(&mut self, len: u64) -> Result<()> {
+        self.bytes = self
+            .bytes
+            .checked_sub(len)
+            .with_context(|| format!("archive expands to more than {MAX_EXPANDED_LEN} bytes"))?;
+        Ok(())
+    }
+}
+
+fn read_zip(
+    data: &[u8],
+    max_file_size: u64,
+    limits: &mut Limits,
+    files: &mut Vec<(String, String)>,
+) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+    for i in 0..archive.len() {
+        limits.entry()?;
+
+        // entries are only decompressed when they're read
+        let file = archive.by_index(i)?;
+        if !file.is_file() || file.size() > max_file_size {
+            continue;
+        }
+
+        let inner = file.enclosed_name().and_then(inner_path);
+        if let Some(inner) = inner {
+            let len = read_file(inner, file, max_file_size, files)?;
+            limits.decompress(len)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_tar(
+    data: impl Read,
+    max_file_size: u64,
+    limits: &mut Limits,
+    files: &mut Vec<(String, String)>,
+) -> Result<()> {
+    let mut archive = tar::Archive::new(data);
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        limits.entry()?;
+
+        // every entry is decompressed to get to the next one, even when it's skipped
+        limits.decompress(entry.size())?;
+        if !entry.header().entry_type().is_file() || entry.size() > max_file_size {
+            continue;
+        }
+
+        let inner = inner_path(&entry.path()?);
+        if let Some(inner) = inner {
+            read_file(inner, entry, max_file_size, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a file from an archive if it should be indexed, returning the number of bytes read.
+fn read_file(
+    inner: String,
+    file: impl Read,
+    max_file_size: u64,
+    files: &mut Vec<(String, String)>,
+) -> Result<u64> {
+    if !should_index(&inner) {
+        return Ok(0);
+    }
+
+    // the size in the header can't be trusted
+    let mut buffer = vec![];
+    file.take(max_file_size + 1).read_to_end(&mut buffer)?;
+    let len = buffer.len() as u64;
+
+    if len <= max_file_size {
+        if let Ok(buffer) = String::from_utf8(buffer) {
+            files.push((inner, buffer));
+        }
+    }
+
+    Ok(len)
+}
+
+/// Normalize the path of a file inside an archive, rejecting paths that would point outside it.
+fn inner_path(path: &Path) -> Option<String> {
+    let mut parts = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    #[test]
+    fn expand_zip() {
+        let mut data = Cursor::new(vec![]);
+        {
+            let mut zip = zip::ZipWriter::new(&mut data);
+            let options = zip::write::FileOptions::default();
+
+            zip.add_directory("com/foo/", options).unwrap();
+            zip.start_file("com/foo/Bar.java", options).unwrap();
+            zip.write_all(b"class Bar {}\n").unwrap();
+            zip.start_file("com/foo/Bar.class", options).unwrap();
+            zip.write_all(&[0xca, 0xfe, 0xba, 0xbe]).unwrap();
+            zip.start_file("README", options).unwrap();
+            zip.write_all(&[0xff, 0xfe]).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let entries = expand(
+            "/repo/lib.jar",
+            data.get_ref(),
+            &["HEAD".into()],
+            None,
+            1000,
+        );
+        let paths = entries
+            .iter()
+            .map(|entry| match entry {
+                RepoDirEntry::Dir(dir) => format!("{}/", dir.path),
+                RepoDirEntry::File(file) => file.path.clone(),
+                RepoDirEntry::Other => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            [
+                "/repo/lib.jar!/",
+                "/repo/lib.jar!/com/",
+                "/repo/lib.jar!/com/foo/",
+                "/repo/lib.jar!/com/foo/Bar.java",
+            ]
+        );
+        assert_eq!(entries[3].buffer(), Some("class Bar {}\n"));
+    }
+
+    fn tar_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(vec![]);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, *contents).unwrap();
+        }
+
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn tar_limits() {
+        let data = tar_of(&[
+            ("a.rs", b"fn a() {}\n"),
+            ("b.rs", b"fn b() {}\n"),
+            ("c.rs", b"fn c() {}\n"),
+        ]);
+        let read = |entries, bytes| {
+            let mut files = vec![];
+            let mut limits = Limits { entries, bytes };
+            let result = read_tar(data.as_slice(), 1000, &mut limits, &mut files);
+            let paths = files.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+            (result.is_ok(), paths)
+        };
+
+        assert_eq!(
+            read(3, 30),
+            (true, vec!["a.rs".into(), "b.rs".into(), "c.rs".into()])
+        );
+        assert_eq!(read(2, 30), (false, vec!["a.rs".into(), "b.rs".into()]));
+        assert_eq!(read(3, 25), (false, vec!["a.rs".into(), "b.rs".into()]));
+    }
+
+    #[test]
+    fn zip_limits() {
+        let mut data = Cursor::new(vec![]);
+        {
+            let mut zip = zip::ZipWriter::new(&mut data);
+            let options = zip::write::FileOptions::default();
+            for name in ["a.rs", "b.rs", "c.rs"] {
+                zip.start_file(name, options).unwrap();
+                zip.write_all(b"fn a() {}\n").unwrap();
+            }
+            zip.finish().unwrap();
+        }
+
+        let read = |entries, bytes| {
+            let mut files = vec![];
+            let mut limits = Limits { entries, bytes };
+            let result = read_zip(data.get_ref(), 1000, &mut limits, &mut files);
+            (result.is_ok(), files.len())
+        };
+
+        assert_eq!(read(3, 30), (true, 3));
+        assert_eq!(read(1, 30), (false, 1));
+        assert_eq!(read(3, 15), (false, 2));
+    }
+
+    #[test]
+    fn inner_paths() {
+        assert_eq!(inner_path(Path::new("./a/b.rs")), Some("a/b.rs".into()));
+        assert_eq!(inner_path(Path::new("a/../../b.rs")), None);
+        assert_eq!(inner_path(Path::new("/etc/passwd")), None);
+        assert_eq!(inner_path(Path::new(".")), None);
+    }
+}
@@ -6,15 +6,21 @@ use std::path::{Path, PathBuf};
 
 pub struct FileWalker {
     file_list: Vec<PathBuf>,
+    files: FileFilter,
 }
 
 impl FileWalker {
-    pub fn index_directory(dir: impl AsRef<Path>) -> impl FileSource {
+    pub fn index_directory(dir: impl AsRef<Path>, files: FileFilter) -> impl FileSource {
+        let filter = files.clone();
+
         // note: this WILL observe .gitignore files for the respective repos.
         let walker = ignore::WalkBuilder::new(&dir)
             .standard_filters(true)
             .hidden(false)
-            .filter_entry(should_index_entry)
+            .filter_entry(move |de| {
+                let is_dir = de.file_type().map(|t| t.is_dir()).unwrap_or_default();
+                filter.is_indexed(de.path(), is_dir)
+            })
             .build();
 
         let file_list = walker
@@ -26,11 +32,13 @@ impl FileWalker {
                 }
             })
             // Preliminarily ignore files that are very large, without reading the contents.
-            .filter(|de| matches!(de.metadata(), Ok(meta) if meta.len() < MAX_FILE_LEN))
+            .filter(|de| {
+                matches!(de.metadata(), Ok(meta) if files.is_within_size_limit(de.path(), meta.len()))
+            })
             .filter_map(|de| crate::canonicalize(de.into_path()).ok())
             .collect();
 
-        Self { file_list }
+        Self { file_list, files }
     }
 }
 
@@ -43,10 +51,26 @@ impl FileSource for FileWalker {
 
     fn for_each(self, pipes: &SyncPipes, iterator: impl Fn(RepoDirEntry) + Sync + Send) {
         use rayon::prelude::*;
+        let files = &self.files;
         self.file_list
             .into_par_iter()
             .filter_map(|entry_disk_path| {
-                if entry_disk_path.is_file() {
+                if entry_disk_path.is_file() && files.is_expanded(&entry_disk_path) {
+                    let data = match std::fs::read(&entry_disk_path) {
+                        Err(err) => {
+                            warn!(%err, ?entry_disk_path, "read failed; skipping");
+                            return None;
+                        }
+                        Ok(data) => data,
+                    };
+                    Some(archive::expand(
+                        &entry_disk_path.to_string_lossy(),
+                        &data,
+                        &[HEAD.into()],
+                        None,
+                        files.max_file_size,
+                    ))
+                } else if entry_disk_path.is_file() {
                     let buffer = match std::fs::read_to_string(&entry_disk_path) {
                         Err(err) => {
                             warn!(%err, ?entry_disk_path, "read failed; skipping");
@@ -54,20 +78,23 @@ impl FileSource for FileWalker {
                         }
                         Ok(buffer) => buffer,
                     };
-                    Some(RepoDirEntry::File(RepoFile {
+                    Some(vec![RepoDirEntry::File(RepoFile {
                         buffer,
                         path: entry_disk_path.to_string_lossy().to_string(),
                         branches: vec![HEAD.into()],
-                    }))
+                        last_commit: None,
+                    })])
                 } else if entry_disk_path.is_dir() {
-                    Some(RepoDirEntry::Dir(RepoDir {
+                    Some(vec![RepoDirEntry::Dir(RepoDir {
                         path: entry_disk_path.to_string_lossy().to_string(),
                         branches: vec![HEAD.into()],
-                    }))
+                        last_commit: None,
+                    })])
                 } else {
-                    Some(RepoDirEntry::Other)
+                    Some(vec![RepoDirEntry::Other])
                 }
             })
+            .flatten_iter()
             .take_any_while(|_| !pipes.is_cancelled())
             .for_each(iterator);
     }
@@ -1,31 +1,85 @@
-use crate::repo::RepoRef;
+use crate::repo::{IndexedCommit, RepoRef};
 
 use super::*;
 
 use anyhow::Result;
-use gix::ThreadSafeRepository;
+use gix::{
+    object::tree::diff::{change::Event, Action},
+    objs::tree::EntryMode,
+    ObjectId, ThreadSafeRepository,
+};
 use regex::RegexSet;
-use tracing::{error, trace};
+use tracing::{debug, error, trace, warn};
 
 use std::{
-    collections::{BTreeSet, HashMap},
-    path::Path,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    convert::Infallible,
+    path::{Path, PathBuf},
 };
 
 pub enum BranchFilter {
     All,
     Head,
-    Select(RegexSet),
+    Select {
+        /// Branches matching any of these patterns are indexed, along with HEAD
+        branches: RegexSet,
+        /// Tags named exactly as one of these are indexed, as are the commits they resolve to
+        /// when they look like a commit ID
+        revisions: Vec<String>,
+    },
 }
 
 impl BranchFilter {
+    /// Select branches by pattern, and tags and commits by their exact name or ID.
+    ///
+    /// Patterns that look like commit IDs only select commits, and are not matched against
+    /// branch names.
+    pub fn select(patterns: &[String]) -> Result<Self, regex::Error> {
+        let branches = patterns
+            .iter()
+            .map(String::as_str)
+            .filter(|p| !is_commit_id(p))
+            .chain(["HEAD"]);
+
+        Ok(BranchFilter::Select {
+            branches: RegexSet::new(branches)?,
+            revisions: patterns.to_vec(),
+        })
+    }
+
     fn filter(&self, is_head: bool, branch: &str) -> bool {
         match self {
             BranchFilter::All => true,
-            BranchFilter::Select(patterns) => is_head || patterns.is_match(branch),
+            BranchFilter::Select { branches, .. } => is_head || branches.is_match(branch),
             BranchFilter::Head => is_head,
         }
     }
+
+    /// Tags are only indexed when they're selected explicitly, as there are usually many more
+    /// tags than branches.
+    fn filter_tag(&self, tag: &str) -> bool {
+        match self {
+            BranchFilter::Select { revisions, .. } => revisions.iter().any(|r| r == tag),
+            BranchFilter::All | BranchFilter::Head => false,
+        }
+    }
+
+    /// Revisions that look like full or abbreviated commit IDs.
+    fn commits(&self) -> impl Iterator<Item = &str> {
+        let revisions = match self {
+            BranchFilter::Select { revisions, .. } => revisions.as_slice(),
+            BranchFilter::All | BranchFilter::Head => &[],
+        };
+
+        revisions
+            .iter()
+            .map(String::as_str)
+            .filter(|r| is_commit_id(r))
+    }
+}
+
+fn is_commit_id(rev: &str) -> bool {
+    (7..=40).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit())
 }
 
 impl Default for BranchFilter {
@@ -34,14 +88,44 @@ impl Default for BranchFilter {
     }
 }
 
+/// Use the `.bloopignore` file committed in `tree`, if there is one, over the one on disk.
+fn committed_bloopignore(tree: &gix::Tree<'_>, files: FileFilter) -> FileFilter {
+    let contents = tree
+        .clone()
+        .peel_to_entry_by_path(BLOOPIGNORE)
+        .map_err(anyhow::Error::from)
+        .and_then(|entry| match entry.filter(|entry| entry.mode().is_blob()) {
+            Some(entry) => Ok(Some(entry.object()?.data.clone())),
+            None => Ok(None),
+        });
+
+    match contents {
+        Ok(Some(contents)) => files.with_bloopignore(&String::from_utf8_lossy(&contents)),
+        Ok(None) => files,
+        Err(err) => {
+            warn!(?err, "failed to read the committed {BLOOPIGNORE}");
+            files
+        }
+    }
+}
+
 fn human_readable_branch_name(r: &gix::Reference<'_>) -> String {
     use gix::bstr::ByteSlice;
     r.name().shorten().to_str_lossy().to_string()
 }
 
+fn is_tag(r: &gix::Reference<'_>) -> bool {
+    use gix::bstr::ByteSlice;
+    r.name().as_bstr().starts_with(b"refs/tags/")
+}
+
 pub struct GitWalker {
     git: ThreadSafeRepository,
+    /// Submodules whose contents are indexed as part of the repository
+    submodules: Vec<ThreadSafeRepository>,
     entries: HashMap<(String, FileType, gix::ObjectId), BTreeSet<String>>,
+    last_commits: HashMap<(String, gix::ObjectId), LastCommit>,
+    files: FileFilter,
 }
 
 impl GitWalker {
@@ -49,6 +133,7 @@ impl GitWalker {
         reporef: &RepoRef,
         dir: impl AsRef<Path>,
         filter: impl Into<Option<BranchFilter>>,
+        files: FileFilter,
     ) -> Result<Self> {
         let root_dir = dir.as_ref();
         let branches = filter.into().unwrap_or_default();
@@ -58,34 +143,17 @@ impl GitWalker {
 
         let local_git = git.to_thread_local();
         let mut head = local_git.head()?;
-
-        // HEAD name needs to be pinned to the remote pointer
-        //
-        // Otherwise the local branch will never advance to the
-        // remote's branch ref
-        //
-        // The easiest here is to check by name, and assume the
-        // default remote is `origin`, since we don't configure it
-        // otherwise.
-        let head_name = head.clone().try_into_referent().map(|r| {
-            if reporef.is_local() {
-                human_readable_branch_name(&r)
-            } else {
-                format!("origin/{}", human_readable_branch_name(&r))
-            }
-        });
+        let head_name = head_name(reporef, &head);
 
         let refs = local_git.references()?;
         let trees = if head_name.is_none() && matches!(branches, BranchFilter::Head) {
             // the current checkout is not a branch, so HEAD will not
             // point to a real reference.
-            vec![(
-                true,
-                "HEAD".to_string(),
-                head.peel_to_commit_in_place()?.tree()?,
-            )]
+            let commit = head.peel_to_commit_in_place()?;
+            vec![(true, "HEAD".to_string(), commit.id, commit.tree()?)]
         } else {
-            refs.all()?
+            let mut trees = refs
+                .all()?
                 .filter_map(Result::ok)
                 // Check if it's HEAD
                 // Normalize the name of the branch for further steps
@@ -101,65 +169,73 @@ impl GitWalker {
                         r,
                     )
                 })
-                .filter(|(_, name, _)| {
-                    if reporef.is_local() {
-                        true
+                .filter(|(is_head, name, r)| {
+                    if is_tag(r) {
+                        branches.filter_tag(name)
+                    } else if reporef.is_local() || name.starts_with("origin/") {
+                        // Only consider remote branches, and apply branch filters along
+                        // whether it's HEAD
+                        branches.filter(*is_head, name)
                     } else {
-                        // Only consider remote branches
-                        //
-                        name.starts_with("origin/")
+                        false
                     }
                 })
-                // Apply branch filters, along whether it's HEAD
-                //
-                .filter(|(is_head, name, _)| branches.filter(*is_head, name))
                 .filter_map(|(is_head, branch, r)| -> Option<_> {
-                    Some((
-                        is_head,
-                        branch,
-                        r.into_fully_peeled_id()
-                            .ok()?
-                            .object()
-                            .ok()?
-                            .peel_to_tree()
-                            .ok()?,
-                    ))
+                    let id = r.into_fully_peeled_id().ok()?;
+                    let tree = id.object().ok()?.peel_to_tree().ok()?;
+                    Some((is_head, branch, id.detach(), tree))
                 })
-                .collect()
+                .collect::<Vec<_>>();
+
+            // selected commits are named by their full ID, as no reference points to them
+            for rev in branches.commits() {
+                let commit = local_git
+                    .rev_parse_single(rev)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|id| Ok(id.object()?.try_into_commit()?));
+
+                match commit.and_then(|commit| Ok((commit.id, commit.tree()?))) {
+                    Ok((id, tree)) => trees.push((false, id.to_string(), id, tree)),
+                    Err(err) => warn!(?err, rev, "can't find selected commit; skipping"),
+                }
+            }
+
+            trees
+        };
+
+        let tips = trees.iter().map(|(_, _, id, _)| *id).collect::<Vec<_>>();
+        let files = match trees.iter().find(|(is_head, ..)| *is_head) {
+            Some((.., tree)) => committed_bloopignore(tree, files),
+            None => files,
         };
+        let files = &files;
+        let mut submodules = Submodules::default();
 
         let entries = trees
             .into_iter()
-            .flat_map(|(is_head, branch, tree)| {
-                let files = tree.traverse().breadthfirst.files().unwrap().into_iter();
+            .flat_map(|(is_head, branch, _, tree)| {
+                let entries = tree_files(&local_git, tree, "", &mut submodules).into_iter();
 
-                files
-                    .map(move |entry| {
-                        let strpath = String::from_utf8_lossy(entry.filepath.as_ref());
-                        let full_path = root_dir.join(strpath.as_ref());
+                entries
+                    .map(move |(strpath, mode, oid)| {
+                        let full_path = root_dir.join(&strpath);
                         trace!(?strpath, ?full_path, "got path from gix");
                         (
                             is_head,
                             branch.clone(),
                             full_path.to_string_lossy().to_string(),
-                            entry.mode,
-                            entry.oid,
+                            mode,
+                            oid,
                         )
                     })
-                    .filter(|(_, _, path, _, _)| should_index(path))
+                    .filter(move |(_, _, path, mode, _)| files.is_indexed(path, mode.is_tree()))
             })
             .fold(
                 HashMap::new(),
                 |mut acc, (is_head, branch, file, mode, oid)| {
-                    let kind = if mode.is_tree() {
-                        FileType::Dir
-                    } else if mode.is_blob() {
-                        FileType::File
-                    } else {
-                        FileType::Other
-                    };
-
-                    let branches = acc.entry((file, kind, oid)).or_insert_with(BTreeSet::new);
+                    let branches = acc
+                        .entry((file, file_type(mode), oid))
+                        .or_insert_with(BTreeSet::new);
                     if is_head {
                         branches.insert("HEAD".to_string());
                     }
@@ -169,8 +245,642 @@ impl GitWalker {
                 },
             );
 
-        Ok(Self { git, entries })
+        Ok(Self::with_entries(
+            git, root_dir, submodules, entries, tips, None, files,
+        ))
+    }
+
+    /// Walk only the entries of the indexed `HEAD` that changed since the commit `since`, for
+    /// repositories where only `HEAD` is indexed.
+    ///
+    /// Along with the walker, returns the paths whose indexed entries are now outdated, relative
+    /// to the repository root. Directories are listed with a trailing `/`.
+    ///
+    /// Returns `None` if the changes can't be walked on their own, and the repository needs a
+    /// full walk instead: when `HEAD` moved to another branch, when `since` is no longer an
+    /// ancestor of `head` because history was rewritten, or when submodules, renamed entries or
+    /// the `.bloopignore` file are involved.
+    pub fn open_changes(
+        dir: impl AsRef<Path>,
+        since: &IndexedCommit,
+        head: &IndexedCommit,
+        files: &FileFilter,
+    ) -> Result<Option<(Self, HashSet<String>)>> {
+        if since.branch != head.branch {
+            return Ok(None);
+        }
+
+        let root_dir = dir.as_ref();
+        let git = gix::open::Options::isolated()
+            .filter_config_section(|_| false)
+            .open(root_dir)?;
+
+        let local_git = git.to_thread_local();
+        let tip = ObjectId::from_hex(head.id.as_bytes())?;
+        let Ok(since) = ObjectId::from_hex(since.id.as_bytes()) else {
+            return Ok(None);
+        };
+
+        if !is_ancestor(&local_git, since, tip).unwrap_or_default() {
+            debug!(%since, %tip, "history was rewritten since the last index");
+            return Ok(None);
+        }
+
+        let mut changed = vec![];
+        let mut outdated = HashSet::new();
+        let mut full_walk = false;
+
+        let old_tree = local_git.find_object(since)?.try_into_commit()?.tree()?;
+        let new_tree = local_git.find_object(tip)?.try_into_commit()?.tree()?;
+        let files = &committed_bloopignore(&new_tree, files.clone());
+        old_tree
+            .changes()?
+            .track_path()
+            .for_each_to_obtain_tree(&new_tree, |change| {
+                let location = change.location.to_string();
+                full_walk |= location == BLOOPIGNORE;
+
+                let (previous_mode, current) =
+                    if let Event::Addition { entry_mode, id } = change.event {
+                        (None, Some((entry_mode, id.detach())))
+                    } else if let Event::Deletion { entry_mode, .. } = change.event {
+                        (Some(entry_mode), None)
+                    } else if let Event::Modification {
+                        previous_entry_mode,
+                        entry_mode,
+                        id,
+                        ..
+                    } = change.event
+                    {
+                        (Some(previous_entry_mode), Some((entry_mode, id.detach())))
+                    } else {
+                        // renamed entries aren't tracked on their own
+                        full_walk = true;
+                        (None, None)
+                    };
+
+                full_walk |= previous_mode == Some(EntryMode::Commit);
+                if let Some((mode, oid)) = current {
+                    full_walk |= mode == EntryMode::Commit;
+                    changed.push((location.clone(), mode, oid));
+                }
+
+                // an entry may have turned from a file into a directory, or the other way round
+                outdated.insert(format!("{location}/"));
+                outdated.insert(location);
+
+                Ok::<_, Infallible>(Action::Continue)
+            })?;
+
+        if full_walk {
+            return Ok(None);
+        }
+
+        let mut branches = BTreeSet::from(["HEAD".to_string()]);
+        branches.extend(head.branch.clone());
+
+        let entries = changed
+            .into_iter()
+            .map(|(path, mode, oid)| (root_dir.join(path), mode, oid))
+            .filter(|(path, mode, _)| files.is_indexed(path, mode.is_tree()))
+            .map(|(path, mode, oid)| {
+                let path = path.to_string_lossy().to_string();
+                ((path, file_type(mode), oid), branches.clone())
+            })
+            .collect();
+
+        let walker = Self::with_entries(
+            git,
+            root_dir,
+            Submodules::default(),
+            entries,
+            vec![tip],
+            Some(since),
+            files,
+        );
+
+        Ok(Some((walker, outdated)))
+    }
+
+    fn with_entries(
+        git: ThreadSafeRepository,
+        root_dir: &Path,
+        submodules: Submodules,
+        entries: HashMap<(String, FileType, ObjectId), BTreeSet<String>>,
+        tips: Vec<ObjectId>,
+        since: Option<ObjectId>,
+        files: &FileFilter,
+    ) -> Self {
+        let local_git = git.to_thread_local();
+
+        // the history of submodules is not walked, so their last commits are unknown
+        let wanted = entries
+            .keys()
+            .filter_map(|(path, _, oid)| {
+                let relative = Path::new(path).strip_prefix(root_dir).ok()?;
+                let relative = relative.to_string_lossy().to_string();
+                (!submodules.contains(&relative)).then_some((relative, *oid))
+            })
+            .collect();
+
+        let last_commits = find_last_commits(&local_git, tips, since, wanted, MAX_LAST_COMMITS)
+            .into_iter()
+            .map(|((relative, oid), commit)| {
+                let full_path = root_dir.join(relative).to_string_lossy().to_string();
+                ((full_path, oid), commit)
+            })
+            .collect();
+
+        Self {
+            git,
+            submodules: submodules.into_repos(),
+            entries,
+            last_commits,
+            files,
+        }
+    }
+}
+
+/// The commit `HEAD` is indexed at in the repository at `dir`, as it would be walked by
+/// [`GitWalker::open_repository`] when only `HEAD` is indexed.
+pub fn indexed_head(reporef: &RepoRef, dir: &Path) -> Result<IndexedCommit> {
+    let git = gix::open::Options::isolated()
+        .filter_config_section(|_| false)
+        .open(dir)?
+        .to_thread_local();
+
+    let mut head = git.head()?;
+    let branch = head_name(reporef, &head);
+    let id = match branch {
+        Some(ref name) => git
+            .find_reference(name.as_str())?
+            .into_fully_peeled_id()?
+            .detach(),
+        None => head.peel_to_commit_in_place()?.id,
+    };
+
+    Ok(IndexedCommit {
+        branch,
+        id: id.to_string(),
+    })
+}
+
+/// The commits that `HEAD` and the references of the repository at `dir` point to.
+pub fn ref_commits(dir: &Path) -> Result<HashSet<String>> {
+    let git = gix::open::Options::isolated()
+        .filter_config_section(|_| false)
+        .open(dir)?
+        .to_thread_local();
+
+    let mut commits = HashSet::new();
+    if let Ok(commit) = git.head()?.peel_to_commit_in_place() {
+        commits.insert(commit.id.to_string());
+    }
+
+    let refs = git.references()?;
+    for r in refs.all()?.filter_map(Result::ok) {
+        if let Ok(id) = r.into_fully_peeled_id() {
+            commits.insert(id.to_string());
+        }
+    }
+
+    Ok(commits)
+}
+
+/// The ID and contents of the blob at `path` in the commit returned by [`indexed_head`], or `None`
+/// if there is no such file.
+pub fn indexed_blob(
+    reporef: &RepoRef,
+    dir: &Path,
+    path: &str,
+) -> Result<Option<(String, Vec<u8>)>> {
+    let commit = indexed_head(reporef, dir)?;
+    let git = gix::open::Options::isolated()
+        .filter_config_section(|_| false)
+        .open(dir)?
+        .to_thread_local();
+
+    let id = ObjectId::from_hex(commit.id.as_bytes())?;
+    let Some(entry) = git
+        .find_object(id)?
+        .try_into_commit()?
+        .tree()?
+        .peel_to_entry_by_path(path)?
+        .filter(|entry| entry.mode().is_blob())
+    else {
+        return Ok(None);
+    };
+
+    let data = entry.object()?.data.clone();
+    Ok(Some((entry.object_id().to_string(), data)))
+}
+
+/// The name of the branch `HEAD` points to, or `None` if it's detached.
+fn head_name(reporef: &RepoRef, head: &gix::Head<'_>) -> Option<String> {
+    // HEAD name needs to be pinned to the remote pointer
+    //
+    // Otherwise the local branch will never advance to the
+    // remote's branch ref
+    //
+    // The easiest here is to check by name, and assume the
+    // default remote is `origin`, since we don't configure it
+    // otherwise.
+    head.clone().try_into_referent().map(|r| {
+        if reporef.is_local() {
+            human_readable_branch_name(&r)
+        } else {
+            format!("origin/{}", human_readable_branch_name(&r))
+        }
+    })
+}
+
+fn file_type(mode: EntryMode) -> FileType {
+    if mode.is_tree() {
+        FileType::Dir
+    } else if mode.is_blob() {
+        FileType::File
+    } else {
+        FileType::Other
+    }
+}
+
+/// Whether `ancestor` can be reached from `tip`. The walk goes newest commits first, and stops
+/// at commits older than `ancestor`.
+fn is_ancestor(git: &gix::Repository, ancestor: ObjectId, tip: ObjectId) -> Result<bool> {
+    let time =
+        |id: ObjectId| -> Result<_> { Ok(git.find_object(id)?.try_into_commit()?.time()?.seconds) };
+
+    let ancestor_time = time(ancestor)?;
+    let mut seen = HashSet::from([tip]);
+    let mut queue = BinaryHeap::from([(time(tip)?, tip)]);
+
+    while let Some((unix_secs, id)) = queue.pop() {
+        if id == ancestor {
+            return Ok(true);
+        }
+
+        if unix_secs < ancestor_time {
+            break;
+        }
+
+        for parent in git.find_object(id)?.try_into_commit()?.parent_ids() {
+            if seen.insert(parent.detach()) {
+                queue.push((time(parent.detach())?, parent.detach()));
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Paths and objects in a tree, including the contents of submodules at their pinned commits.
+/// Submodules are only traversed if they can be found on disk, which they never are in the bare
+/// clones of remote repositories.
+///
+/// Paths are relative to the root of the outermost tree, and start with `prefix`.
+fn tree_files(
+    git: &gix::Repository,
+    tree: gix::Tree<'_>,
+    prefix: &str,
+    submodules: &mut Submodules,
+) -> Vec<(String, EntryMode, ObjectId)> {
+    let entries = match tree.traverse().breadthfirst.files() {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!(?err, prefix, "failed to traverse tree");
+            return vec![];
+        }
+    };
+
+    let mut output = vec![];
+    for entry in entries {
+        let relative = String::from_utf8_lossy(entry.filepath.as_ref()).to_string();
+        let path = format!("{prefix}{relative}");
+
+        if entry.mode != EntryMode::Commit {
+            output.push((path, entry.mode, entry.oid));
+            continue;
+        }
+
+        let Some(submodule) = submodules.open(git, &relative) else {
+            if prefix.is_empty() && git.work_dir().is_none() {
+                // remote repositories are bare clones, whose submodules are never fetched
+                warn!(
+                    path,
+                    "remote repository submodules are not indexed; skipping"
+                );
+            } else {
+                debug!(path, "submodule is not on disk; skipping");
+            }
+            continue;
+        };
+
+        let Some(tree) = submodule
+            .find_object(entry.oid)
+            .ok()
+            .and_then(|commit| commit.peel_to_tree().ok())
+        else {
+            warn!(path, commit=%entry.oid, "can't find submodule commit; skipping");
+            continue;
+        };
+
+        submodules.roots.insert(path.clone());
+        output.push((path.clone(), EntryMode::Tree, tree.id));
+        output.extend(tree_files(
+            &submodule,
+            tree,
+            &format!("{path}/"),
+            submodules,
+        ));
+    }
+
+    output
+}
+
+/// Submodule repositories opened while traversing trees.
+#[derive(Default)]
+struct Submodules {
+    /// Repositories by the directory where their parent would keep them, or `None` if they
+    /// couldn't be opened
+    repos: HashMap<PathBuf, Option<ThreadSafeRepository>>,
+    /// Paths of traversed submodules, relative to the repository root
+    roots: BTreeSet<String>,
+}
+
+impl Submodules {
+    /// Submodules are either absorbed into the `modules` directory of their parent's git
+    /// directory, or checked out with their own git directory.
+    fn open(&mut self, parent: &gix::Repository, path: &str) -> Option<gix::Repository> {
+        let absorbed = parent.git_dir().join("modules").join(path);
+        self.repos
+            .entry(absorbed.clone())
+            .or_insert_with(|| {
+                let checkout = parent.work_dir().map(|dir| dir.join(path));
+                std::iter::once(absorbed).chain(checkout).find_map(|dir| {
+                    gix::open::Options::isolated()
+                        .filter_config_section(|_| false)
+                        .open(dir)
+                        .ok()
+                })
+            })
+            .as_ref()
+            .map(|git| git.to_thread_local())
+    }
+
+    /// Whether a path is inside a submodule.
+    fn contains(&self, path: &str) -> bool {
+        self.roots.iter().any(|root| {
+            path.strip_prefix(root.as_str())
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or_default()
+        })
+    }
+
+    fn into_repos(self) -> Vec<ThreadSafeRepository> {
+        self.repos.into_values().flatten().collect()
+    }
+}
+
+/// Find an object in the first repository that has it.
+fn find_object(repos: &[&ThreadSafeRepository], id: ObjectId) -> Option<gix::ObjectDetached> {
+    repos
+        .iter()
+        .find_map(|git| Some(git.to_thread_local().try_find_object(id).ok()??.detach()))
+}
+
+/// The most commits compared while looking for the last commits of entries. Entries last changed
+/// before then are dated by the last commit of the repository instead.
+const MAX_LAST_COMMITS: usize = 10_000;
+
+/// Walk the history reachable from `tips`, newest commits first, to find the last commit that
+/// introduced each of the `wanted` objects at its path, relative to the repository root.
+///
+/// Each commit is compared against its first parent only, so changes brought in by a merge are
+/// attributed to the merge commit. The walk stops as soon as every object is accounted for, after
+/// comparing `max_commits` commits, or when only commits reachable from `since` are left: objects
+/// that changed since the last indexed commit were introduced after it. Commits are attributed to
+/// their author, at the time they were authored.
+///
+/// Errors, such as missing objects in shallow clones, stop the walk early and return the commits
+/// found so far.
+fn find_last_commits(
+    git: &gix::Repository,
+    tips: Vec<ObjectId>,
+    since: Option<ObjectId>,
+    mut wanted: HashSet<(String, ObjectId)>,
+    max_commits: usize,
+) -> HashMap<(String, ObjectId), LastCommit> {
+    let mut found = HashMap::new();
+    let mut compared = 0;
+    let mut hidden = HashSet::<ObjectId>::from_iter(since);
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new();
+
+    let walk = || -> Result<()> {
+        let time = |id: ObjectId| -> Result<_> {
+            Ok(git.find_object(id)?.try_into_commit()?.time()?.seconds)
+        };
+
+        for tip in tips.into_iter().chain(since) {
+            if seen.insert(tip) {
+                queue.push((time(tip)?, tip));
+            }
+        }
+
+        while let Some((_, id)) = queue.pop() {
+            if wanted.is_empty() {
+                break;
+            }
+
+            if compared == max_commits {
+                debug!(
+                    remaining = wanted.len(),
+                    "history is too long to find every last commit"
+                );
+                break;
+            }
+
+            let commit = git.find_object(id)?.try_into_commit()?;
+            let parents = commit.parent_ids().map(|p| p.detach()).collect::<Vec<_>>();
+
+            for &parent in &parents {
+                if hidden.contains(&id) {
+                    hidden.insert(parent);
+                }
+
+                if seen.insert(parent) {
+                    queue.push((time(parent)?, parent));
+                }
+            }
+
+            if hidden.contains(&id) {
+                if queue.iter().all(|(_, id)| hidden.contains(id)) {
+                    break;
+                }
+
+                continue;
+            }
+
+            compared += 1;
+            let author = commit.author()?;
+            let unix_secs = author.time.seconds;
+            let author = author.name.to_string();
+
+            let parent_tree = match parents.first() {
+                Some(parent) => git.find_object(*parent)?.try_into_commit()?.tree()?,
+                None => git.empty_tree(),
+            };
+
+            parent_tree
+                .changes()?
+                .track_path()
+                .for_each_to_obtain_tree(&commit.tree()?, |change| {
+                    let (Event::Addition { id, .. } | Event::Modification { id, .. }) =
+                        change.event
+                    else {
+                        return Ok::<_, Infallible>(Action::Continue);
+                    };
+
+                    let key = (change.location.to_string(), id.detach());
+                    if wanted.remove(&key) {
+                        found.insert(
+                            key,
+                            LastCommit {
+                                unix_secs,
+                                author: author.clone(),
+                            },
+                        );
+                    }
+
+                    Ok(Action::Continue)
+                })?;
+        }
+
+        Ok(())
+    };
+
+    if let Err(err) = walk() {
+        warn!(
+            ?err,
+            "failed to walk git history; some last commits are unknown"
+        );
     }
+
+    found
+}
+
+/// Commits found by [`commit_history`].
+pub struct CommitHistory {
+    /// Newest commits first
+    pub commits: Vec<CommitInfo>,
+
+    /// Whether only the commits made since a previous walk were collected. Otherwise, the whole
+    /// history was walked.
+    pub incremental: bool,
+}
+
+/// Walk the history of the commit `tip` in the repository at `dir`, newest commits first,
+/// collecting up to `limit` commits along with the paths each of them changed.
+///
+/// If `since` is an ancestor of `tip`, only the commits that can't be reached from `since` are
+/// collected. Otherwise, as when the history was rewritten, the whole history is walked.
+///
+/// As with [`find_last_commits`], commits are compared against their first parent only, and
+/// errors stop the walk early, returning the commits found so far.
+pub fn commit_history(dir: &Path, tip: &str, since: Option<&str>, limit: usize) -> CommitHistory {
+    let mut history = CommitHistory {
+        commits: vec![],
+        incremental: false,
+    };
+
+    let mut walk = || -> Result<()> {
+        let git = gix::open::Options::isolated()
+            .filter_config_section(|_| false)
+            .open(dir)?
+            .to_thread_local();
+
+        let time = |id: ObjectId| -> Result<_> {
+            Ok(git.find_object(id)?.try_into_commit()?.time()?.seconds)
+        };
+
+        let tip = ObjectId::from_hex(tip.as_bytes())?;
+        let since = since
+            .and_then(|since| ObjectId::from_hex(since.as_bytes()).ok())
+            .filter(|&since| {
+                is_ancestor(&git, since, tip)
+                    .map_err(|err| debug!(?err, "failed to find the last indexed commit"))
+                    .unwrap_or_default()
+            });
+        history.incremental = since.is_some();
+
+        // Commits reachable from `since` have been collected before. These are walked alongside
+        // the others, until only they are left in the queue.
+        let mut hidden = HashSet::<ObjectId>::from_iter(since);
+        let mut seen = HashSet::from([tip]);
+        let mut queue = BinaryHeap::from([(time(tip)?, tip)]);
+        if let Some(since) = since {
+            seen.insert(since);
+            queue.push((time(since)?, since));
+        }
+
+        while let Some((_, id)) = queue.pop() {
+            if history.commits.len() >= limit {
+                break;
+            }
+
+            let commit = git.find_object(id)?.try_into_commit()?;
+            let parents = commit.parent_ids().map(|p| p.detach()).collect::<Vec<_>>();
+
+            for &parent in &parents {
+                if hidden.contains(&id) {
+                    hidden.insert(parent);
+                }
+
+                if seen.insert(parent) {
+                    queue.push((time(parent)?, parent));
+                }
+            }
+
+            if hidden.contains(&id) {
+                if queue.iter().all(|(_, id)| hidden.contains(id)) {
+                    break;
+                }
+
+                continue;
+            }
+
+            let parent_tree = match parents.first() {
+                Some(parent) => git.find_object(*parent)?.try_into_commit()?.tree()?,
+                None => git.empty_tree(),
+            };
+
+            let mut paths = vec![];
+            parent_tree
+                .changes()?
+                .track_path()
+                .for_each_to_obtain_tree(&commit.tree()?, |change| {
+                    paths.push(change.location.to_string());
+                    Ok::<_, Infallible>(Action::Continue)
+                })?;
+
+            let author = commit.author()?;
+            history.commits.push(CommitInfo {
+                id: id.to_string(),
+                author: author.name.to_string(),
+                unix_secs: author.time.seconds,
+                message: commit.message_raw()?.to_string(),
+                paths,
+            });
+        }
+
+        Ok(())
+    };
+
+    if let Err(err) = walk() {
+        warn!(?err, "failed to walk git history; some commits are missing");
+    }
+
+    history
 }
 
 impl FileSource for GitWalker {
@@ -180,39 +890,352 @@ impl FileSource for GitWalker {
 
     fn for_each(self, pipes: &SyncPipes, iterator: impl Fn(RepoDirEntry) + Sync + Send) {
         use rayon::prelude::*;
+        let last_commits = &self.last_commits;
+        let files = &self.files;
+
+        // submodule objects are kept in their own repositories
+        let repos = std::iter::once(&self.git)
+            .chain(&self.submodules)
+            .collect::<Vec<_>>();
         self.entries
             .into_par_iter()
             .filter_map(|((path, kind, oid), branches)| {
                 trace!(?path, "walking over path");
-                let git = self.git.to_thread_local();
-                let Ok(Some(object)) = git.try_find_object(oid) else {
+
+                let Some(object) = find_object(&repos, oid) else {
                     error!(?path, ?branches, "can't find object for file");
                     return None;
                 };
 
-                if object.data.len() as u64 > MAX_FILE_LEN {
+                let expand = kind == FileType::File && files.is_expanded(&path);
+                if !files.is_within_size_limit(&path, object.data.len() as u64) {
                     return None;
                 }
 
+                let last_commit = last_commits.get(&(path.clone(), oid)).cloned();
                 let entry = match kind {
+                    FileType::File if expand => {
+                        let branches = branches.into_iter().collect::<Vec<_>>();
+                        return Some(archive::expand(
+                            &path,
+                            &object.data,
+                            &branches,
+                            last_commit.as_ref(),
+                            files.max_file_size,
+                        ));
+                    }
                     FileType::File => {
                         let buffer = String::from_utf8_lossy(&object.data).to_string();
                         RepoDirEntry::File(RepoFile {
                             path,
                             branches: branches.into_iter().collect(),
                             buffer,
+                            last_commit,
                         })
                     }
                     FileType::Dir => RepoDirEntry::Dir(RepoDir {
                         path,
                         branches: branches.into_iter().collect(),
+                        last_commit,
                     }),
                     FileType::Other => return None,
                 };
 
-                Some(entry)
+                Some(vec![entry])
             })
+            .flatten_iter()
             .take_any_while(|_| !pipes.is_cancelled())
             .for_each(iterator)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    /// Run git in `dir` as a fixed user, outside of any global configuration, dating commits at
+    /// `unix_secs`. Returns the trimmed output.
+    fn git(dir: &Path, unix_secs: u64, args: &[&str]) -> String {
+        let date = format!("@{unix_secs} +0000");
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "Ferris")
+            .env("GIT_AUTHOR_EMAIL", "ferris@example.com")
+            .env("GIT_COMMITTER_NAME", "Ferris")
+            .env("GIT_COMMITTER_EMAIL", "ferris@example.com")
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {args:?} failed: {output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Write `files` to the work tree of `dir`, and commit everything. Returns the commit ID.
+    fn commit(dir: &Path, unix_secs: u64, message: &str, files: &[(&str, &str)]) -> String {
+        for (path, contents) in files {
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+
+        git(dir, unix_secs, &["add", "-A"]);
+        git(dir, unix_secs, &["commit", "-q", "-m", message]);
+        git(dir, unix_secs, &["rev-parse", "HEAD"])
+    }
+
+    fn messages(history: &CommitHistory) -> Vec<&str> {
+        history
+            .commits
+            .iter()
+            .map(|commit| commit.message.trim())
+            .collect()
+    }
+
+    #[test]
+    fn last_commits_by_author() {
+        let tmpdir = TempDir::new("test-last-commits").unwrap();
+        let dir = tmpdir.path();
+        git(dir, 0, &["init", "-q", "-b", "main"]);
+
+        let first = commit(dir, 1, "add a and b", &[("a.txt", "a"), ("b.txt", "b")]);
+        std::fs::write(dir.join("a.txt"), "a2").unwrap();
+        git(
+            dir,
+            3,
+            &[
+                "commit",
+                "-q",
+                "-a",
+                "-m",
+                "change a",
+                "--author=Crab <crab@example.com>",
+                "--date=@2 +0000",
+            ],
+        );
+
+        let id = |rev: &str| ObjectId::from_hex(git(dir, 3, &["rev-parse", rev]).as_bytes());
+        let a = ("a.txt".to_owned(), id("HEAD:a.txt").unwrap());
+        let b = ("b.txt".to_owned(), id("HEAD:b.txt").unwrap());
+        let tip = id("HEAD").unwrap();
+        let repo = gix::open::Options::isolated()
+            .filter_config_section(|_| false)
+            .open(dir)
+            .unwrap()
+            .to_thread_local();
+
+        let author = |commit: &LastCommit| (commit.author.clone(), commit.unix_secs);
+        let wanted = HashSet::from([a.clone(), b.clone()]);
+        let found = find_last_commits(&repo, vec![tip], None, wanted.clone(), 10);
+        assert_eq!(author(&found[&a]), ("Crab".to_owned(), 2));
+        assert_eq!(author(&found[&b]), ("Ferris".to_owned(), 1));
+
+        // entries older than the commits compared are left to the caller
+        let found = find_last_commits(&repo, vec![tip], None, wanted.clone(), 1);
+        assert_eq!(found.keys().collect::<Vec<_>>(), [&a]);
+
+        // commits before the last indexed commit can't have introduced changes made since
+        let since = ObjectId::from_hex(first.as_bytes()).unwrap();
+        let found = find_last_commits(&repo, vec![tip], Some(since), wanted, 10);
+        assert_eq!(found.keys().collect::<Vec<_>>(), [&a]);
+    }
+
+    #[test]
+    fn commit_history_since() {
+        let tmpdir = TempDir::new("test-commit-history").unwrap();
+        let dir = tmpdir.path();
+        git(dir, 0, &["init", "-q", "-b", "main"]);
+
+        let first = commit(dir, 1, "add a", &[("a.txt", "a")]);
+        commit(dir, 2, "add b", &[("b.txt", "b")]);
+        let third = commit(dir, 3, "change a", &[("a.txt", "a2")]);
+
+        let full = commit_history(dir, &third, None, 10);
+        assert!(!full.incremental);
+        assert_eq!(messages(&full), ["change a", "add b", "add a"]);
+        assert_eq!(full.commits[0].paths, ["a.txt"]);
+        assert_eq!(full.commits[0].author, "Ferris");
+        assert_eq!(full.commits[0].unix_secs, 3);
+
+        let limited = commit_history(dir, &third, None, 2);
+        assert_eq!(messages(&limited), ["change a", "add b"]);
+
+        let since = commit_history(dir, &third, Some(&first), 10);
+        assert!(since.incremental);
+        assert_eq!(messages(&since), ["change a", "add b"]);
+
+        let unchanged = commit_history(dir, &third, Some(&third), 10);
+        assert!(unchanged.incremental);
+        assert!(unchanged.commits.is_empty());
+
+        // commits merged in are new, but not the history they share with `since`
+        git(dir, 4, &["checkout", "-q", "-b", "side", &first]);
+        commit(dir, 4, "add c", &[("c.txt", "c")]);
+        git(dir, 5, &["checkout", "-q", "main"]);
+        git(dir, 5, &["merge", "-q", "--no-ff", "-m", "merge", "side"]);
+        let merge = git(dir, 5, &["rev-parse", "HEAD"]);
+
+        let merged = commit_history(dir, &merge, Some(&third), 10);
+        assert!(merged.incremental);
+        assert_eq!(messages(&merged), ["merge", "add c"]);
+        assert_eq!(merged.commits[0].paths, ["c.txt"]);
+
+        // a rewritten history is walked whole
+        git(dir, 6, &["reset", "-q", "--hard", &first]);
+        let rewritten = commit(dir, 6, "add d", &[("d.txt", "d")]);
+
+        let history = commit_history(dir, &rewritten, Some(&third), 10);
+        assert!(!history.incremental);
+        assert_eq!(messages(&history), ["add d", "add a"]);
+    }
+
+    #[test]
+    fn select_tags_and_commits() {
+        let filter =
+            BranchFilter::select(&["origin/main".into(), "v1.4.2".into(), "3f2a9c1".into()])
+                .unwrap();
+
+        assert!(filter.filter_tag("v1.4.2"));
+        assert!(!filter.filter_tag("v1.5.0"));
+        assert!(!filter.filter_tag("v1x4y2"));
+        assert!(!filter.filter_tag("v1.4.20"));
+        assert!(!BranchFilter::All.filter_tag("v1.4.2"));
+
+        assert!(filter.filter(false, "origin/main"));
+        assert!(filter.filter(false, "origin/HEAD"));
+        assert!(!filter.filter(false, "origin/fix-3f2a9c1"));
+
+        assert_eq!(filter.commits().collect::<Vec<_>>(), ["3f2a9c1"]);
+        assert_eq!(BranchFilter::Head.commits().count(), 0);
+    }
+
+    /// The entries walked by [`GitWalker::open_changes`] between two commits on `main`, and the
+    /// outdated paths, or `None` if a full walk is needed. Directories end with a `/`.
+    fn changes(dir: &Path, since: &str, head: &str) -> Option<(Vec<String>, Vec<String>)> {
+        let indexed = |id: &str| IndexedCommit {
+            branch: Some("main".into()),
+            id: id.into(),
+        };
+        let files = FileFilter::new(dir, &crate::repo::IndexSettings::default()).unwrap();
+        let (walker, outdated) =
+            GitWalker::open_changes(dir, &indexed(since), &indexed(head), &files).unwrap()?;
+
+        let mut entries = walker
+            .entries
+            .into_keys()
+            .map(|(path, kind, _)| {
+                let path = Path::new(&path)
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy();
+                match kind {
+                    FileType::Dir => format!("{path}/"),
+                    _ => path.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        let mut outdated = outdated.into_iter().collect::<Vec<_>>();
+        outdated.sort();
+
+        Some((entries, outdated))
+    }
+
+    #[test]
+    fn changes_since_last_index() {
+        let tmpdir = TempDir::new("test-open-changes").unwrap();
+        let dir = tmpdir.path();
+        git(dir, 0, &["init", "-q", "-b", "main"]);
+
+        std::fs::create_dir(dir.join("d")).unwrap();
+        let first = commit(
+            dir,
+            1,
+            "first",
+            &[("a.txt", "a"), ("b.txt", "b"), ("x", "x"), ("d/e.txt", "e")],
+        );
+
+        // modified, added and deleted files
+        std::fs::remove_file(dir.join("b.txt")).unwrap();
+        let second = commit(dir, 2, "second", &[("a.txt", "a2"), ("c.txt", "c")]);
+        assert_eq!(
+            changes(dir, &first, &second),
+            Some((
+                vec!["a.txt".into(), "c.txt".into()],
+                ["a.txt", "a.txt/", "b.txt", "b.txt/", "c.txt", "c.txt/"]
+                    .map(String::from)
+                    .to_vec(),
+            ))
+        );
+
+        // a file turned into a directory, and a directory into a file
+        std::fs::remove_file(dir.join("x")).unwrap();
+        std::fs::create_dir(dir.join("x")).unwrap();
+        std::fs::remove_dir_all(dir.join("d")).unwrap();
+        let third = commit(dir, 3, "third", &[("x/y.txt", "y"), ("d", "d")]);
+        assert_eq!(
+            changes(dir, &second, &third),
+            Some((
+                vec!["d".into(), "x/".into(), "x/y.txt".into()],
+                ["d", "d/", "d/e.txt", "d/e.txt/", "x", "x/", "x/y.txt", "x/y.txt/"]
+                    .map(String::from)
+                    .to_vec(),
+            ))
+        );
+
+        // unchanged
+        assert_eq!(changes(dir, &third, &third), Some((vec![], vec![])));
+
+        // a changed `.bloopignore` may change which paths are indexed anywhere in the repo
+        let fourth = commit(dir, 4, "ignore", &[(BLOOPIGNORE, "*.txt")]);
+        assert_eq!(changes(dir, &third, &fourth), None);
+    }
+
+    #[test]
+    fn rewritten_history_needs_a_full_walk() {
+        let tmpdir = TempDir::new("test-rewritten-changes").unwrap();
+        let dir = tmpdir.path();
+        git(dir, 0, &["init", "-q", "-b", "main"]);
+
+        let first = commit(dir, 1, "first", &[("a.txt", "a")]);
+        let second = commit(dir, 2, "second", &[("a.txt", "a2")]);
+        git(dir, 3, &["reset", "-q", "--hard", &first]);
+        let rewritten = commit(dir, 3, "rewritten", &[("a.txt", "a3")]);
+
+        let repo = gix::open::Options::isolated()
+            .filter_config_section(|_| false)
+            .open(dir)
+            .unwrap()
+            .to_thread_local();
+        let id = |id: &str| ObjectId::from_hex(id.as_bytes()).unwrap();
+        assert!(is_ancestor(&repo, id(&first), id(&rewritten)).unwrap());
+        assert!(is_ancestor(&repo, id(&first), id(&first)).unwrap());
+        assert!(!is_ancestor(&repo, id(&second), id(&rewritten)).unwrap());
+        assert!(!is_ancestor(&repo, id(&rewritten), id(&first)).unwrap());
+
+        assert_eq!(changes(dir, &second, &rewritten), None);
+        assert_eq!(
+            changes(dir, &first, &rewritten).map(|(entries, _)| entries),
+            Some(vec!["a.txt".into()])
+        );
+
+        // `HEAD` moved to another branch
+        let other = IndexedCommit {
+            branch: Some("other".into()),
+            id: first.clone(),
+        };
+        let head = IndexedCommit {
+            branch: Some("main".into()),
+            id: rewritten,
+        };
+        let files = FileFilter::new(dir, &crate::repo::IndexSettings::default()).unwrap();
+        assert!(GitWalker::open_changes(dir, &other, &head, &files)
+            .unwrap()
+            .is_none());
+    }
+}
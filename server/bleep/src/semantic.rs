@@ -12,7 +12,7 @@ use qdrant_client::{
     qdrant::{
         point_id::PointIdOptions, r#match::MatchValue, vectors::VectorsOptions, vectors_config,
         with_payload_selector, with_vectors_selector, CollectionOperationResponse,
-        CreateCollection, Distance, FieldCondition, FieldType, Filter, Match, PointId,
+        CreateCollection, Distance, FieldCondition, FieldType, Filter, Match, PointId, Range,
         RetrievedPoint, ScoredPoint, SearchPoints, Value, VectorParams, Vectors, VectorsConfig,
         WithPayloadSelector, WithVectorsSelector,
     },
@@ -105,6 +105,11 @@ impl Payload {
             ("start_byte".into(), self.start_byte.to_string().into()),
             ("end_byte".into(), self.end_byte.to_string().into()),
             ("branches".into(), self.branches.into()),
+            // Stored as a number, unlike the other integer fields, so that it can be range-filtered.
+            (
+                "last_commit_unix_secs".into(),
+                (self.last_commit_unix_secs as i64).into(),
+            ),
         ])
     }
 }
@@ -150,6 +155,10 @@ fn parse_payload(
         end_line: val_parse_str!(converted, "end_line"),
         start_byte: val_parse_str!(converted, "start_byte"),
         end_byte: val_parse_str!(converted, "end_byte"),
+        last_commit_unix_secs: converted
+            .remove("last_commit_unix_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default(),
 
         id: Some(id),
         score: Some(score),
@@ -229,6 +238,15 @@ impl Semantic {
         qdrant
             .create_field_index(COLLECTION_NAME, "branches", FieldType::Text, None, None)
             .await?;
+        qdrant
+            .create_field_index(
+                COLLECTION_NAME,
+                "last_commit_unix_secs",
+                FieldType::Integer,
+                None,
+                None,
+            )
+            .await?;
         qdrant
             .create_field_index(
                 COLLECTION_NAME,
@@ -488,6 +506,7 @@ impl Semantic {
         buffer: &str,
         lang_str: &str,
         branches: &[String],
+        last_commit_unix_secs: u64,
         chunk_cache: crate::cache::ChunkCache<'_>,
     ) {
         let chunks = chunk::by_tokens(
@@ -515,6 +534,7 @@ impl Semantic {
                 text: chunk.data.to_owned(),
                 lang: lang_str.to_ascii_lowercase(),
                 branches: branches.to_owned(),
+                last_commit_unix_secs,
                 start_line: chunk.range.start.line as u64,
                 end_line: chunk.range.end.line as u64,
                 start_byte: chunk.range.start.byte as u64,
@@ -687,11 +707,35 @@ fn build_conditions(query: &SemanticQuery<'_>) -> Vec<qdrant_client::qdrant::Con
         }
     };
 
-    let filters: Vec<_> = [repo_filter, path_filter, lang_filter, branch_filter]
-        .into_iter()
-        .flatten()
-        .map(Into::into)
-        .collect();
+    let commit_time_filter = if query.after.is_some() || query.before.is_some() {
+        Some(Filter {
+            must: vec![FieldCondition {
+                key: "last_commit_unix_secs".into(),
+                range: Some(Range {
+                    gte: query.after.map(|t| t as f64),
+                    lt: query.before.map(|t| t as f64),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+            .into()],
+            ..Default::default()
+        })
+    } else {
+        None
+    };
+
+    let filters: Vec<_> = [
+        repo_filter,
+        path_filter,
+        lang_filter,
+        branch_filter,
+        commit_time_filter,
+    ]
+    .into_iter()
+    .flatten()
+    .map(Into::into)
+    .collect();
 
     filters
 }
@@ -13,6 +13,7 @@ pub struct Payload {
     pub start_byte: u64,
     pub end_byte: u64,
     pub branches: Vec<String>,
+    pub last_commit_unix_secs: u64,
 
     #[serde(skip)]
     pub id: Option<String>,
@@ -35,6 +36,7 @@ impl PartialEq for Payload {
             && self.start_byte == other.start_byte
             && self.end_byte == other.end_byte
             && self.branches == other.branches
+            && self.last_commit_unix_secs == other.last_commit_unix_secs
 
         // ignoring deserialized fields that will not exist on a newly
         // created payload
@@ -54,9 +54,11 @@ impl Location {
             return Err(rhs);
         }
 
+        // Highlights may be nested, e.g. captures within a structural match, so the joined
+        // location must still cover the longest one.
         let offset = rhs.byte_range.start - self.byte_range.start;
-        self.line_range.end = rhs.line_range.end;
-        self.byte_range.end = rhs.byte_range.end;
+        self.line_range.end = self.line_range.end.max(rhs.line_range.end);
+        self.byte_range.end = self.byte_range.end.max(rhs.byte_range.end);
         self.highlights
             .extend(rhs.highlights.into_iter().map(|mut h| {
                 h.start += offset;
@@ -105,25 +107,28 @@ impl SnippedFile {
 }
 
 #[derive(Copy, Clone, Debug)]
-pub struct Snipper {
+pub struct Snipper<'a> {
     pub context_before: usize,
     pub context_after: usize,
     pub find_symbols: bool,
     pub case_sensitive: bool,
+    /// Only match symbols of this kind, when finding symbols.
+    pub symbol_kind: Option<&'a str>,
 }
 
-impl Default for Snipper {
+impl Default for Snipper<'_> {
     fn default() -> Self {
         Self {
             context_before: 0,
             context_after: 0,
             find_symbols: false,
             case_sensitive: true,
+            symbol_kind: None,
         }
     }
 }
 
-impl Snipper {
+impl<'s> Snipper<'s> {
     pub fn context(mut self, before: usize, after: usize) -> Self {
         self.context_before = before;
         self.context_after = after;
@@ -140,6 +145,11 @@ impl Snipper {
         self
     }
 
+    pub fn symbol_kind(mut self, symbol_kind: Option<&'s str>) -> Self {
+        self.symbol_kind = symbol_kind;
+        self
+    }
+
     pub fn all_for_doc(
         &self,
         regex: &str,
@@ -155,6 +165,10 @@ impl Snipper {
             // search results with the symbol list present in a document.
             //
             let mut symbols = doc.symbol_locations.list();
+            if let Some(kind) = self.symbol_kind {
+                symbols.retain(|sym| sym.kind == kind);
+            }
+
             let symbol_ranges = symbols
                 .iter()
                 .map(|sym| sym.range.into())
@@ -223,6 +237,30 @@ impl Snipper {
         })
     }
 
+    /// Build snippets around a list of highlights that were already found, sorted by position.
+    pub fn for_highlights(
+        &self,
+        highlights: Vec<Range<usize>>,
+        doc: &indexes::reader::ContentDocument,
+    ) -> Option<SnippedFile> {
+        let snippets = self
+            .expand_many(highlights.into_iter(), &doc.content, &doc.line_end_indices)
+            .map(|loc| loc.reify(&doc.content, &[]))
+            .collect::<Vec<_>>();
+
+        if snippets.is_empty() {
+            return None;
+        }
+
+        Some(SnippedFile {
+            relative_path: doc.relative_path.clone(),
+            repo_name: doc.repo_name.clone(),
+            repo_ref: doc.repo_ref.clone(),
+            lang: doc.lang.clone(),
+            snippets,
+        })
+    }
+
     fn expand_many<'a>(
         &'a self,
         mut highlights: impl Iterator<Item = Range<usize>> + 'a,
@@ -596,6 +634,40 @@ mod tests {
         )
     }
 
+    #[test]
+    fn symbol_kind() {
+        let (text, line_end_indices) = with_line_ends("fn parse() {}\nstruct parse_state;\n");
+        let graph = crate::intelligence::TreeSitterFile::try_build(text.as_bytes(), "Rust")
+            .and_then(crate::intelligence::TreeSitterFile::scope_graph)
+            .unwrap();
+        let doc = indexes::reader::ContentDocument {
+            content: text.into(),
+            line_end_indices,
+            symbol_locations: crate::symbol::SymbolLocations::TreeSitter(graph),
+            ..Default::default()
+        };
+
+        let snipper = Snipper::default().find_symbols(true);
+        let all = snipper.all_for_doc("parse", &doc).unwrap().unwrap();
+        assert_eq!(all.snippets.len(), 2);
+
+        let functions = snipper
+            .symbol_kind(Some("function"))
+            .all_for_doc("parse", &doc)
+            .unwrap()
+            .unwrap();
+        assert_eq!(functions.snippets.len(), 1);
+        assert_eq!(functions.snippets[0].data, "fn parse() {}");
+
+        assert_eq!(
+            None,
+            snipper
+                .symbol_kind(Some("enum"))
+                .all_for_doc("parse", &doc)
+                .unwrap()
+        );
+    }
+
     #[test]
     fn avoids_empty_snippets() {
         let (text, line_end_indices) = with_line_ends("function foo() {}\n");
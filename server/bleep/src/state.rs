@@ -347,6 +347,27 @@ mod test {
             std::fs::create_dir_all(path.join(repo).join(".git")).unwrap();
         }
 
+        // linked worktrees and submodules use a `.git` file instead of a directory
+        let dot_git_files = [
+            (
+                "worktrees/query-parser-feature",
+                "bloopai/query-parser/.git/worktrees/query-parser-feature",
+            ),
+            (
+                "bloopai/enterprise-search/vendored",
+                "bloopai/enterprise-search/.git/modules/vendored",
+            ),
+        ];
+
+        for (dir, gitdir) in dot_git_files {
+            std::fs::create_dir_all(path.join(dir)).unwrap();
+            std::fs::write(
+                path.join(dir).join(".git"),
+                format!("gitdir: {}\n", path.join(gitdir).display()),
+            )
+            .unwrap();
+        }
+
         for file in files {
             let path = path.join(file);
             std::fs::create_dir_all(path.parent().unwrap()).unwrap();
@@ -379,6 +400,7 @@ mod test {
             repo("bloopai/query-parser"),
             repo("bloopai/foo-repo"),
             repo("bloopai/foo-repo/bar-submodule"),
+            repo("worktrees/query-parser-feature"),
         ];
         expected_repos.sort();
 
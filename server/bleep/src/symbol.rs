@@ -35,3 +35,190 @@ impl SymbolLocations {
         }
     }
 }
+
+/// A symbol definition matched by a fuzzy symbol search.
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolMatch {
+    pub name: String,
+    pub kind: String,
+    pub range: TextRange,
+    pub repo_name: String,
+    pub repo_ref: String,
+    pub relative_path: String,
+    pub lang: Option<String>,
+    pub score: u32,
+}
+
+/// Scores symbol names against a "go to symbol" query.
+///
+/// In order of preference, a name can match the query exactly, by prefix, by the initials or
+/// prefixes of its words (`gfm` or `getfm` for `get_file_metadata` or `getFileMetadata`), as a
+/// substring, as a plain subsequence, or with up to 2 typos. Matching is case-insensitive.
+///
+/// Typos are only tolerated in queries of at least [`Self::MIN_TYPO_QUERY_LEN`] characters, as
+/// shorter queries would match nearly everything.
+pub struct FuzzySymbolMatcher {
+    query: Vec<char>,
+    typos: Option<regex::RegexSet>,
+}
+
+impl FuzzySymbolMatcher {
+    pub const MIN_TYPO_QUERY_LEN: usize = 5;
+
+    pub fn new(query: &str) -> Self {
+        let typos = (query.chars().count() >= Self::MIN_TYPO_QUERY_LEN)
+            .then(|| crate::indexes::file::build_fuzzy_regex_filter(query))
+            .flatten();
+
+        Self {
+            query: query.to_lowercase().chars().collect(),
+            typos,
+        }
+    }
+
+    /// A score for `name`, higher is better, or `None` if it doesn't match at all.
+    pub fn score(&self, name: &str) -> Option<u32> {
+        if self.query.is_empty() {
+            return None;
+        }
+
+        let lower = name.to_lowercase().chars().collect::<Vec<_>>();
+        let tier = if lower == self.query {
+            6
+        } else if lower.starts_with(&self.query) {
+            5
+        } else if matches_words(&self.query, &words(name)) {
+            4
+        } else if lower.windows(self.query.len()).any(|w| w == self.query) {
+            3
+        } else if is_subsequence(&self.query, &lower) {
+            2
+        } else if self.typos.as_ref().map_or(false, |t| t.is_match(name)) {
+            1
+        } else {
+            return None;
+        };
+
+        // Prefer shorter names within a tier, as more of the name is matched.
+        Some(tier * 1000 + 999u32.saturating_sub(lower.len() as u32))
+    }
+}
+
+/// Split an identifier into lowercase words, on `snake_case`, `kebab-case` and `camelCase`
+/// boundaries. Runs of capitals are one word, so `HTTPServer` is `http` and `server`.
+fn words(name: &str) -> Vec<Vec<char>> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut current = vec![];
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && prev.map_or(false, |p| {
+                p.is_lowercase()
+                    || p.is_numeric()
+                    || (p.is_uppercase() && next.map_or(false, |n| n.is_lowercase()))
+            });
+
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+
+        current.extend(c.to_lowercase());
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+/// Whether `query` is a concatenation of prefixes of `words`, in order, starting from the first
+/// character of some word. Words may be skipped.
+fn matches_words(query: &[char], words: &[Vec<char>]) -> bool {
+    fn go(query: &[char], words: &[Vec<char>], word: usize, offset: usize) -> bool {
+        let Some((&c, rest)) = query.split_first() else {
+            return true;
+        };
+
+        // continue the current word...
+        let continues =
+            offset > 0 && words[word].get(offset) == Some(&c) && go(rest, words, word, offset + 1);
+
+        // ...or start a later one
+        continues
+            || (word + usize::from(offset > 0)..words.len())
+                .any(|next| words[next].first() == Some(&c) && go(rest, words, next, 1))
+    }
+
+    go(query, words, 0, 0)
+}
+
+fn is_subsequence(query: &[char], name: &[char]) -> bool {
+    let mut name = name.iter();
+    query.iter().all(|c| name.any(|n| n == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn split_words() {
+        let words = |s| {
+            words(s)
+                .into_iter()
+                .map(String::from_iter)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(words("get_file_metadata"), ["get", "file", "metadata"]);
+        assert_eq!(words("getFileMetadata"), ["get", "file", "metadata"]);
+        assert_eq!(words("HTTPServer2Config"), ["http", "server2", "config"]);
+        assert_eq!(words("--kebab-case"), ["kebab", "case"]);
+    }
+
+    #[test]
+    fn fuzzy_symbol_scores() {
+        let matcher = FuzzySymbolMatcher::new("gfm");
+        assert!(matcher.score("get_file_metadata").is_some());
+        assert!(matcher.score("GetFileMetadata").is_some());
+        assert!(matcher.score("set_file_mode").is_none());
+
+        let matcher = FuzzySymbolMatcher::new("getfm");
+        assert!(matcher.score("getFileMetadata").is_some());
+
+        let matcher = FuzzySymbolMatcher::new("metadata");
+        let mut names = [
+            "parse_metadata_header",
+            "metdata",
+            "metadata",
+            "file_metadata",
+            "FileMetadata",
+            "metadata_for_path",
+        ];
+        names.sort_by_key(|n| std::cmp::Reverse(matcher.score(n)));
+
+        assert_eq!(
+            names,
+            [
+                "metadata",
+                "metadata_for_path",
+                "FileMetadata",
+                "file_metadata",
+                "parse_metadata_header",
+                "metdata",
+            ]
+        );
+    }
+}
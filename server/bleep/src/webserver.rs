@@ -1,9 +1,13 @@
-use crate::{env::Feature, Application};
+use crate::{
+    env::Feature,
+    remotes::{gitea::Gitea, gitlab::Gitlab},
+    Application,
+};
 
 use axum::{
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, post, put},
     Extension, Json,
 };
 use std::{borrow::Cow, net::SocketAddr};
@@ -15,16 +19,22 @@ use tracing::info;
 mod aaa;
 pub mod answer;
 mod autocomplete;
+mod call_hierarchy;
 mod config;
 mod file;
+mod git;
 mod github;
 mod hoverable;
 mod index;
 mod intelligence;
 pub mod middleware;
+mod outline;
+mod providers;
 mod query;
 pub mod repos;
+mod saved_searches;
 mod semantic;
+mod symbols;
 
 pub type Router<S = Application> = axum::Router<S>;
 
@@ -44,6 +54,20 @@ pub async fn start(app: Application) -> anyhow::Result<()> {
         .route("/config", get(config::get).put(config::put))
         // querying
         .route("/q", get(query::handle))
+        .route("/q/export", get(query::export))
+        .route("/q/stream", get(query::stream))
+        // saved searches
+        .route(
+            "/saved-searches",
+            get(saved_searches::list).post(saved_searches::create),
+        )
+        .route(
+            "/saved-searches/:id",
+            get(saved_searches::get)
+                .put(saved_searches::update)
+                .delete(saved_searches::delete),
+        )
+        .route("/saved-searches/:id/diff", get(saved_searches::diff))
         // autocomplete
         .route("/autocomplete", get(autocomplete::handle))
         // indexing
@@ -53,6 +77,9 @@ pub async fn start(app: Application) -> anyhow::Result<()> {
         // intelligence
         .route("/hoverable", get(hoverable::handle))
         .route("/token-info", get(intelligence::handle))
+        .route("/outline", get(outline::handle))
+        .route("/call-hierarchy", get(call_hierarchy::handle))
+        .route("/symbols", get(symbols::handle))
         // misc
         .route("/search", get(semantic::complex_search))
         .route("/file", get(file::handle))
@@ -71,6 +98,25 @@ pub async fn start(app: Application) -> anyhow::Result<()> {
         api = api.route("/repos/scan", get(repos::scan_local));
     }
 
+    if app.env.allow(Feature::GitRemotes) {
+        api = api.route(
+            "/remotes/git",
+            get(git::list).put(git::set).delete(git::delete),
+        );
+    }
+
+    if app.env.allow(Feature::ProviderLogin) {
+        api = api
+            .route(
+                "/remotes/gitlab",
+                put(providers::login::<Gitlab>).delete(providers::logout::<Gitlab>),
+            )
+            .route(
+                "/remotes/gitea",
+                put(providers::login::<Gitea>).delete(providers::logout::<Gitea>),
+            );
+    }
+
     if app.env.allow(Feature::GithubDeviceFlow) {
         api = api
             .route("/remotes/github/login", get(github::login))
@@ -25,11 +25,12 @@ use tiktoken_rs::CoreBPE;
 use tokio::sync::mpsc::Sender;
 use tracing::{debug, info, warn};
 
-use super::middleware::User;
+use super::{call_hierarchy::CallGraph, middleware::User};
 use crate::{
     analytics::{EventData, QueryEvent},
     db::QueryLog,
     indexes::reader::{ContentDocument, FileDocument},
+    intelligence::call_hierarchy::{callables, CallDirection, CallEdge},
     query::parser::{self, Literal, SemanticQuery},
     repo::RepoRef,
     semantic, Application,
@@ -470,6 +471,12 @@ impl Agent {
             Action::Path { query } => self.path_search(query).await?,
             Action::Code { query } => self.code_search(query).await?,
             Action::Proc { query, paths } => self.process_files(query, paths).await?,
+            Action::Diff { base, head } => self.branch_diff(base, head).await?,
+            Action::Calls {
+                path,
+                symbol,
+                direction,
+            } => self.call_hierarchy(*path, symbol, *direction).await?,
         };
 
         let functions = serde_json::from_value::<Vec<llm_gateway::api::Function>>(
@@ -632,6 +639,193 @@ impl Agent {
         Ok(response)
     }
 
+    async fn branch_diff(&mut self, base: &str, head: &str) -> Result<String> {
+        const MAX_DIFFS: usize = 20;
+        const MAX_DIFF_TOKENS: usize = 500;
+
+        self.update(Update::StartStep(SearchStep::Diff {
+            base: base.to_owned(),
+            head: head.to_owned(),
+            response: String::new(),
+        }))
+        .await?;
+
+        let changes = self
+            .app
+            .indexes
+            .file
+            .diff_branches(&self.repo_ref, base, head)
+            .await?;
+
+        let bpe = tiktoken_rs::get_bpe_from_model("gpt-3.5-turbo")?;
+        let added = changes
+            .added
+            .iter()
+            .map(|p| (p.clone(), self.get_path_alias(p)))
+            .collect::<Vec<_>>();
+        let modified = changes
+            .modified
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                // Only the first few diffs are shown, to leave room for the rest of the
+                // conversation.
+                let diff = match &file.diff {
+                    Some(diff) if i < MAX_DIFFS => limit_tokens(diff, bpe.clone(), MAX_DIFF_TOKENS),
+                    _ => "",
+                };
+
+                json!({
+                    "path": file.path,
+                    "alias": self.get_path_alias(&file.path),
+                    "diff": diff,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let response = serde_json::to_string(&json!({
+            "added": added,
+            "removed": changes.removed,
+            "modified": modified,
+        }))
+        .unwrap();
+
+        self.update(Update::ReplaceStep(SearchStep::Diff {
+            base: base.to_owned(),
+            head: head.to_owned(),
+            response: response.clone(),
+        }))
+        .await?;
+
+        self.track_query(
+            EventData::input_stage("branch diff")
+                .with_payload("base", base)
+                .with_payload("head", head)
+                .with_payload("added", &changes.added)
+                .with_payload("removed", &changes.removed)
+                .with_payload("raw_prompt", &response),
+        );
+
+        Ok(response)
+    }
+
+    async fn call_hierarchy(
+        &mut self,
+        path_alias: usize,
+        symbol: &str,
+        direction: CallDirection,
+    ) -> Result<String> {
+        const DEPTH: usize = 2;
+
+        let path = self
+            .paths()
+            .get(path_alias)
+            .cloned()
+            .ok_or_else(|| anyhow!("invalid path alias {path_alias}"))?;
+
+        self.update(Update::StartStep(SearchStep::Calls {
+            path: path.clone(),
+            symbol: symbol.to_owned(),
+            direction,
+            response: String::new(),
+        }))
+        .await?;
+
+        let doc = self
+            .get_file_content(&path)
+            .await?
+            .with_context(|| format!("path does not exist in the index: {path}"))?;
+
+        let calls = match callables(&doc).into_iter().find(|item| item.name == symbol) {
+            Some(item) => {
+                let branch = self.last_exchange().query.first_branch();
+                CallGraph::new(
+                    &self.app,
+                    &self.app.indexes,
+                    &self.repo_ref,
+                    branch.as_deref(),
+                )
+                .tree(doc, &item, direction, DEPTH)
+                .await?
+            }
+            None => vec![],
+        };
+
+        let calls = self.calls_json(&calls, &path, direction)?;
+        let response = serde_json::to_string(&json!({
+            "path": path,
+            "symbol": symbol,
+            "direction": direction,
+            "calls": calls,
+        }))
+        .unwrap();
+
+        self.update(Update::ReplaceStep(SearchStep::Calls {
+            path: path.clone(),
+            symbol: symbol.to_owned(),
+            direction,
+            response: response.clone(),
+        }))
+        .await?;
+
+        self.track_query(
+            EventData::input_stage("call hierarchy")
+                .with_payload("path", &path)
+                .with_payload("symbol", symbol)
+                .with_payload("direction", direction)
+                .with_payload("raw_prompt", &response),
+        );
+
+        Ok(response)
+    }
+
+    /// Describe a call tree to the model, with the first few call sites of each call.
+    ///
+    /// `from` is the file of the function that the calls in `edges` are to or from.
+    fn calls_json(
+        &mut self,
+        edges: &[CallEdge],
+        from: &str,
+        direction: CallDirection,
+    ) -> Result<Vec<serde_json::Value>> {
+        const MAX_SITES: usize = 3;
+        const MAX_SITE_TOKENS: usize = 100;
+
+        let bpe = tiktoken_rs::get_bpe_from_model("gpt-3.5-turbo")?;
+        let mut calls = vec![];
+
+        for edge in edges {
+            // Call sites are in the file of the calling function.
+            let site_path = match direction {
+                CallDirection::Incoming => &edge.item.file,
+                CallDirection::Outgoing => from,
+            };
+
+            let sites = edge
+                .sites
+                .iter()
+                .take(MAX_SITES)
+                .map(|site| {
+                    json!({
+                        "path": site_path,
+                        "line": site.range.start.line + 1,
+                        "snippet": limit_tokens(&site.snippet.data, bpe.clone(), MAX_SITE_TOKENS),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            calls.push(json!({
+                "function": edge.item.name,
+                "path": edge.item.file,
+                "alias": self.get_path_alias(&edge.item.file),
+                "sites": sites,
+                "calls": self.calls_json(&edge.children, &edge.item.file, direction)?,
+            }));
+        }
+
+        Ok(calls)
+    }
+
     async fn process_files(&mut self, query: &str, path_aliases: &[usize]) -> Result<String> {
         const MAX_CHUNK_LINE_LENGTH: usize = 20;
         const CHUNK_MERGE_DISTANCE: usize = 10;
@@ -1050,6 +1244,23 @@ impl Agent {
                                     .join(", ")
                             ),
                         ),
+                        SearchStep::Diff { base, head, .. } => (
+                            "diff".to_owned(),
+                            format!("{{\n \"base\": \"{base}\",\n \"head\": \"{head}\"\n}}"),
+                        ),
+                        SearchStep::Calls {
+                            path,
+                            symbol,
+                            direction,
+                            ..
+                        } => (
+                            "calls".to_owned(),
+                            format!(
+                                "{{\n \"path\": {},\n \"symbol\": \"{symbol}\",\n \"direction\": {}\n}}",
+                                self.paths().iter().position(|p| p == path).unwrap(),
+                                json!(direction),
+                            ),
+                        ),
                     };
 
                     vec![
@@ -1547,6 +1758,15 @@ enum Action {
         query: String,
         paths: Vec<usize>,
     },
+    Diff {
+        base: String,
+        head: String,
+    },
+    Calls {
+        path: usize,
+        symbol: String,
+        direction: CallDirection,
+    },
 }
 
 impl Action {
@@ -1,4 +1,4 @@
-use crate::query::parser::SemanticQuery;
+use crate::{intelligence::call_hierarchy::CallDirection, query::parser::SemanticQuery};
 use std::{borrow::Cow, mem};
 
 use anyhow::{Context, Result};
@@ -49,6 +49,8 @@ impl Exchange {
                 (Some(l @ SearchStep::Path { .. }), r @ SearchStep::Path { .. }) => *l = r,
                 (Some(l @ SearchStep::Code { .. }), r @ SearchStep::Code { .. }) => *l = r,
                 (Some(l @ SearchStep::Proc { .. }), r @ SearchStep::Proc { .. }) => *l = r,
+                (Some(l @ SearchStep::Diff { .. }), r @ SearchStep::Diff { .. }) => *l = r,
+                (Some(l @ SearchStep::Calls { .. }), r @ SearchStep::Calls { .. }) => *l = r,
                 _ => panic!("Tried to replace a step that was not found"),
             },
             Update::Article(full_text) => {
@@ -155,6 +157,17 @@ pub enum SearchStep {
         paths: Vec<String>,
         response: String,
     },
+    Diff {
+        base: String,
+        head: String,
+        response: String,
+    },
+    Calls {
+        path: String,
+        symbol: String,
+        direction: CallDirection,
+        response: String,
+    },
 }
 
 impl SearchStep {
@@ -176,6 +189,22 @@ impl SearchStep {
                 paths: paths.clone(),
                 response: "[hidden, compressed]".into(),
             },
+            Self::Diff { base, head, .. } => Self::Diff {
+                base: base.clone(),
+                head: head.clone(),
+                response: "[hidden, compressed]".into(),
+            },
+            Self::Calls {
+                path,
+                symbol,
+                direction,
+                ..
+            } => Self::Calls {
+                path: path.clone(),
+                symbol: symbol.clone(),
+                direction: *direction,
+                response: "[hidden, compressed]".into(),
+            },
         }
     }
 
@@ -184,6 +213,8 @@ impl SearchStep {
             Self::Path { response, .. } => response.clone(),
             Self::Code { response, .. } => response.clone(),
             Self::Proc { response, .. } => response.clone(),
+            Self::Diff { response, .. } => response.clone(),
+            Self::Calls { response, .. } => response.clone(),
         }
     }
 }
@@ -29,6 +29,24 @@ pub fn functions(add_proc: bool) -> serde_json::Value {
                     "required": ["query"]
                 }
             },
+            {
+                "name": "diff",
+                "description": "List the files added, removed and modified between two branches of the codebase, with diffs of the modified files. Use when the user asks what changed between branches.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "base": {
+                            "type": "string",
+                            "description": "The branch to compare from, e.g. 'main'."
+                        },
+                        "head": {
+                            "type": "string",
+                            "description": "The branch to compare to, e.g. 'release'."
+                        }
+                    },
+                    "required": ["base", "head"]
+                }
+            },
             {
                 "name": "none",
                 "description": "You have enough information to answer the user's query. This is the final step, and signals that you have enough information to respond to the user's query. Use this if the user has instructed you to modify some code.",
@@ -75,6 +93,33 @@ pub fn functions(add_proc: bool) -> serde_json::Value {
             }
             )
         );
+        funcs.as_array_mut().unwrap().push(
+            serde_json::json!(
+            {
+                "name": "calls",
+                "description": "Trace the call hierarchy of a function defined in a file: the functions that call it, or the functions it calls, two levels deep. Use to follow execution paths through the codebase.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "integer",
+                            "description": "The index of the path the function is defined in."
+                        },
+                        "symbol": {
+                            "type": "string",
+                            "description": "The name of the function, e.g. 'handle'."
+                        },
+                        "direction": {
+                            "type": "string",
+                            "enum": ["incoming", "outgoing"],
+                            "description": "'incoming' for the functions that call it, 'outgoing' for the functions it calls."
+                        }
+                    },
+                    "required": ["path", "symbol", "direction"]
+                }
+            }
+            )
+        );
     }
     funcs
 }
@@ -103,6 +148,8 @@ pub fn system(paths: &Vec<String>) -> String {
 - Respond with functions to find information related to the query, until all relevant information has been found
 - Only call functions.none with paths that contain code that might help answer the user's query, or which answer it directly
 - If you have already called functions.code or functions.path but they did not return any relevant information, try again with a substantively different query. The terms in your new query should not overlap with terms in previous queries
+- If the user asks what changed between two branches, e.g. 'What changed between main and release?', respond with functions.diff
+- If the user asks what calls a function, or how execution reaches some code, respond with functions.calls on the path that defines the function
 - Use functions.proc on paths that you suspect might contain relevant information, or to expand on code that's already been returned by a code search. Do not pass more than 10 paths to functions.proc at a time
 - If after attempting to gather information you are still unsure how to answer the query, respond with the functions.none function
 - If the query is a greeting, or not a question or an instruction use functions.none
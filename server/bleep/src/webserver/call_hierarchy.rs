@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::{intelligence::repo_sites, prelude::*};
+use crate::{
+    db::SiteMatch,
+    indexes::reader::ContentDocument,
+    intelligence::{
+        call_hierarchy::{
+            call_site, call_sites, callables, enclosing_callable, push_site, CallDirection,
+            CallEdge, CallItem, CallSite,
+        },
+        code_navigation::{CodeNavigationContext, Token},
+    },
+    repo::RepoRef,
+    Application,
+};
+
+use axum::extract::State;
+
+/// The deepest call tree the endpoint returns.
+const MAX_DEPTH: usize = 5;
+
+/// The most calls found in a tree, after which no more functions are expanded.
+const MAX_CALLS: usize = 250;
+
+fn default_depth() -> usize {
+    2
+}
+
+/// The request made to the `call-hierarchy` endpoint.
+#[derive(Debug, Deserialize)]
+pub(super) struct CallHierarchyRequest {
+    /// The repo_ref of the file of interest
+    repo_ref: String,
+
+    /// The path to the file of interest, relative to the repo root
+    relative_path: String,
+
+    /// Branch name to use for the lookup,
+    branch: Option<String>,
+
+    /// The byte range of the name of a function definition
+    start: usize,
+    end: usize,
+
+    /// Whether to list the callers or the callees of the function
+    direction: CallDirection,
+
+    /// How many levels of calls to follow, at most `MAX_DEPTH`
+    #[serde(default = "default_depth")]
+    depth: usize,
+}
+
+/// The response from the `call-hierarchy` endpoint.
+#[derive(Serialize)]
+pub(super) struct CallHierarchyResponse {
+    item: CallItem,
+    calls: Vec<CallEdge>,
+}
+
+impl super::ApiResponse for CallHierarchyResponse {}
+
+/// The callers or callees of a function, and theirs, up to a bounded depth.
+pub(super) async fn handle(
+    Query(payload): Query<CallHierarchyRequest>,
+    State(app): State<Application>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+) -> Result<impl IntoResponse> {
+    let repo_ref = payload.repo_ref.parse::<RepoRef>().map_err(Error::user)?;
+
+    let document = indexes
+        .file
+        .by_path(&repo_ref, &payload.relative_path, payload.branch.as_deref())
+        .await
+        .map_err(Error::user)?
+        .ok_or_else(|| Error::user("file not found").with_status(StatusCode::NOT_FOUND))?;
+
+    let item = callables(&document)
+        .into_iter()
+        .find(|item| item.range.start.byte <= payload.start && payload.end <= item.range.end.byte)
+        .ok_or_else(|| Error::user("no function is defined at this range"))?;
+
+    let calls = CallGraph::new(&app, &indexes, &repo_ref, payload.branch.as_deref())
+        .tree(
+            document,
+            &item,
+            payload.direction,
+            payload.depth.min(MAX_DEPTH),
+        )
+        .await?;
+
+    Ok(json(CallHierarchyResponse { item, calls }))
+}
+
+/// Finds calls between functions across the files of a repo.
+///
+/// Calls within a file are resolved through its scope graph. Calls to and from other files are
+/// found like repo-wide definitions and references, in the symbol table.
+pub(super) struct CallGraph<'a> {
+    app: &'a Application,
+    indexes: &'a Indexes,
+    repo_ref: &'a RepoRef,
+    branch: Option<&'a str>,
+
+    /// Documents loaded so far, by path
+    docs: HashMap<String, ContentDocument>,
+
+    /// The functions defined in each loaded document, by path
+    callables: HashMap<String, Vec<CallItem>>,
+}
+
+impl<'a> CallGraph<'a> {
+    pub(super) fn new(
+        app: &'a Application,
+        indexes: &'a Indexes,
+        repo_ref: &'a RepoRef,
+        branch: Option<&'a str>,
+    ) -> Self {
+        Self {
+            app,
+            indexes,
+            repo_ref,
+            branch,
+            docs: HashMap::new(),
+            callables: HashMap::new(),
+        }
+    }
+
+    /// The calls to or from `item`, a function defined in `doc`, followed `depth` levels deep.
+    ///
+    /// Each function is only expanded the first time it is reached, which also ends recursive
+    /// calls.
+    pub(super) async fn tree(
+        &mut self,
+        doc: ContentDocument,
+        item: &CallItem,
+        direction: CallDirection,
+        depth: usize,
+    ) -> anyhow::Result<Vec<CallEdge>> {
+        self.docs.insert(doc.relative_path.clone(), doc);
+
+        let key = |item: &CallItem| (item.file.clone(), item.range.start.byte);
+        let mut expanded = HashSet::from([key(item)]);
+
+        // Edges in breadth-first order, along with the index of the edge they were found from.
+        let mut found: Vec<(Option<usize>, CallEdge)> = vec![];
+        let mut frontier = vec![(None, item.clone())];
+
+        for _ in 0..depth {
+            let mut next = vec![];
+            for (parent, item) in frontier {
+                if found.len() >= MAX_CALLS {
+                    break;
+                }
+
+                for edge in self.edges(&item, direction).await? {
+                    if expanded.insert(key(&edge.item)) {
+                        next.push((Some(found.len()), edge.item.clone()));
+                    }
+                    found.push((parent, edge));
+                }
+            }
+            frontier = next;
+        }
+
+        // Children always come after their parent, so attaching them from the back builds the
+        // tree bottom-up.
+        let mut roots = vec![];
+        while let Some((parent, edge)) = found.pop() {
+            match parent {
+                Some(parent) => found[parent].1.children.insert(0, edge),
+                None => roots.insert(0, edge),
+            }
+        }
+
+        Ok(roots)
+    }
+
+    async fn edges(
+        &mut self,
+        item: &CallItem,
+        direction: CallDirection,
+    ) -> anyhow::Result<Vec<CallEdge>> {
+        match direction {
+            CallDirection::Incoming => self.callers(item).await,
+            CallDirection::Outgoing => self.callees(item).await,
+        }
+    }
+
+    /// The functions that contain references to `item`.
+    async fn callers(&mut self, item: &CallItem) -> anyhow::Result<Vec<CallEdge>> {
+        let Some(doc) = self.docs.get(&item.file).cloned() else {
+            return Ok(vec![]);
+        };
+
+        let repo_sites = self.repo_sites(&item.name, &doc).await?;
+        let ctx = CodeNavigationContext {
+            repo_ref: self.repo_ref.clone(),
+            token: Token {
+                relative_path: &item.file,
+                start_byte: item.range.start.byte,
+                end_byte: item.range.end.byte,
+            },
+            source_document: doc,
+            repo_sites,
+        };
+
+        let references = ctx.token_info();
+        self.add_docs(ctx.repo_sites);
+
+        let mut edges = vec![];
+        for file in references {
+            let callables = self.callables_in(&file.file);
+            for occurrence in file.data.into_iter().filter(|o| !o.is_definition()) {
+                if let Some(caller) = enclosing_callable(callables, occurrence.range) {
+                    let site = CallSite {
+                        range: occurrence.range,
+                        snippet: occurrence.snippet,
+                    };
+                    push_site(&mut edges, caller, occurrence.precision, site);
+                }
+            }
+        }
+
+        Ok(edges)
+    }
+
+    /// The functions that the references made by `item` resolve to.
+    async fn callees(&mut self, item: &CallItem) -> anyhow::Result<Vec<CallEdge>> {
+        let Some(doc) = self.docs.get(&item.file).cloned() else {
+            return Ok(vec![]);
+        };
+
+        // Sites are grouped by name, to look each name up in the symbol table once.
+        let mut by_name = BTreeMap::<String, Vec<_>>::new();
+        for range in call_sites(&doc, item, self.callables_in(&item.file)) {
+            let name = doc.content[range.start.byte..range.end.byte].to_owned();
+            by_name.entry(name).or_default().push(range);
+        }
+
+        let mut edges = vec![];
+        for (name, ranges) in by_name {
+            let repo_sites = self.repo_sites(&name, &doc).await?;
+            let mut ctx = CodeNavigationContext {
+                repo_ref: self.repo_ref.clone(),
+                token: Token {
+                    relative_path: &item.file,
+                    start_byte: 0,
+                    end_byte: 0,
+                },
+                source_document: doc.clone(),
+                repo_sites,
+            };
+
+            let mut definitions = vec![];
+            for range in ranges {
+                ctx.token = Token {
+                    relative_path: &item.file,
+                    start_byte: range.start.byte,
+                    end_byte: range.end.byte,
+                };
+                definitions.push((range, ctx.token_info()));
+            }
+            self.add_docs(ctx.repo_sites);
+
+            for (range, files) in definitions {
+                for file in files {
+                    let callables = self.callables_in(&file.file);
+                    for definition in file.data.iter().filter(|o| o.is_definition()) {
+                        // imports are listed as definitions too, but aren't functions
+                        if let Some(callee) = callables.iter().find(|c| c.range == definition.range)
+                        {
+                            let site = call_site(&doc, range);
+                            push_site(&mut edges, callee, definition.precision, site);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(edges)
+    }
+
+    async fn repo_sites(
+        &self,
+        name: &str,
+        doc: &ContentDocument,
+    ) -> anyhow::Result<Vec<(ContentDocument, Vec<SiteMatch>)>> {
+        repo_sites(
+            self.app,
+            self.indexes,
+            self.repo_ref,
+            name,
+            doc,
+            self.branch,
+        )
+        .await
+    }
+
+    fn add_docs(&mut self, repo_sites: Vec<(ContentDocument, Vec<SiteMatch>)>) {
+        for (doc, _) in repo_sites {
+            self.docs.entry(doc.relative_path.clone()).or_insert(doc);
+        }
+    }
+
+    fn callables_in(&mut self, path: &str) -> &[CallItem] {
+        if !self.callables.contains_key(path) {
+            let items = self.docs.get(path).map(callables).unwrap_or_default();
+            self.callables.insert(path.to_owned(), items);
+        }
+
+        &self.callables[path]
+    }
+}
@@ -1,9 +1,20 @@
 use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Context;
-use axum::{extract::Query, Extension, Json};
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
 
-use crate::repo::RepoRef;
+use crate::{
+    db::BlameCache,
+    repo::{
+        blame::{self, BlameRange},
+        iterator::{self, ARCHIVE_SEPARATOR},
+        RepoRef,
+    },
+    Application,
+};
 
 use super::prelude::*;
 
@@ -18,12 +29,18 @@ pub(super) struct Params {
 
     /// 1-indexed line number at which to end the snippet
     pub line_end: Option<usize>,
+
+    /// Whether to annotate the snippet with the commits that last changed each line
+    #[serde(default)]
+    pub blame: bool,
 }
 
 #[derive(serde::Serialize)]
 pub(super) struct FileResponse {
     contents: String,
     lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blame: Option<Vec<BlameRange>>,
 }
 
 impl super::ApiResponse for FileResponse {}
@@ -31,6 +48,7 @@ impl super::ApiResponse for FileResponse {}
 pub(super) async fn handle<'a>(
     Query(params): Query<Params>,
     Extension(indexes): Extension<Arc<Indexes>>,
+    State(app): State<Application>,
 ) -> Result<Json<super::Response<'a>>, Error> {
     let doc = indexes
         .file
@@ -43,12 +61,117 @@ pub(super) async fn handle<'a>(
         .map_err(Error::internal)?
         .ok_or_else(|| Error::user("file not found").with_status(StatusCode::NOT_FOUND))?;
 
+    let contents = split_by_lines(&doc.content, &doc.line_end_indices, &params)?.to_string();
+    let blame = match params.blame {
+        true => Some(blame_lines(&app, &params).await?),
+        false => None,
+    };
+
     Ok(json(FileResponse {
-        contents: split_by_lines(&doc.content, &doc.line_end_indices, &params)?.to_string(),
+        contents,
         lang: doc.lang,
+        blame,
     }))
 }
 
+/// Blame the lines of a file requested in `params`, from the git repository on disk.
+///
+/// Blame is computed for the whole file, at the requested branch or the commit `HEAD` is indexed
+/// at, and cached by the file's path, the hash of its blob and that commit.
+async fn blame_lines(app: &Application, params: &Params) -> Result<Vec<BlameRange>, Error> {
+    let Some(disk_path) = app
+        .repo_pool
+        .read_async(&params.repo_ref, |_k, v| v.disk_path.clone())
+        .await
+    else {
+        return Err(Error::new(ErrorKind::NotFound, "Can't find repository"));
+    };
+
+    let repo_ref = params.repo_ref.to_string();
+    let path = params
+        .path
+        .to_str()
+        .context("invalid file path")?
+        .to_owned();
+
+    if path.contains(ARCHIVE_SEPARATOR) {
+        return Err(Error::user(
+            "blame is not available for files inside archives",
+        ));
+    }
+
+    let (commit, blob) = {
+        let (disk_path, path) = (disk_path.clone(), path.clone());
+        let (reporef, branch) = (params.repo_ref.clone(), params.branch.clone());
+        tokio::task::spawn_blocking(move || {
+            // The local `HEAD` of remote repositories isn't moved by fetches, so the file is
+            // blamed at the commit its contents were indexed from.
+            let rev = match branch {
+                Some(branch) => branch,
+                None => iterator::indexed_head(&reporef, &disk_path)?.id,
+            };
+
+            blame::blob_id(&disk_path, &rev, &path)
+        })
+        .await
+        .map_err(Error::internal)?
+        .map_err(git_error)?
+        .ok_or_else(|| Error::user("file not found").with_status(StatusCode::NOT_FOUND))?
+    };
+    let (commit, blob) = (commit.to_string(), blob.to_string());
+
+    let cache = BlameCache::new(&app.sql);
+    let ranges = match cache.get(&repo_ref, &blob, &path, &commit).await? {
+        Some(ranges) => ranges,
+        None => {
+            // blame the commit that was resolved, in case `rev` has moved since
+            let ranges = {
+                let (commit, path) = (commit.clone(), path.clone());
+                tokio::task::spawn_blocking(move || blame::blame(&disk_path, &commit, &path))
+                    .await
+                    .map_err(Error::internal)??
+            };
+
+            cache
+                .insert(&repo_ref, &blob, &path, &commit, &ranges)
+                .await?;
+            ranges
+        }
+    };
+
+    Ok(clip_ranges(ranges, params.line_start, params.line_end))
+}
+
+/// Only report repositories that can't be opened as a user error; other git failures are ours.
+fn git_error(err: anyhow::Error) -> Error {
+    match err.downcast_ref::<gix::open::Error>() {
+        Some(gix::open::Error::NotARepository { .. }) => {
+            Error::user("file is not in a git repository")
+        }
+        _ => Error::internal(err),
+    }
+}
+
+/// Keep only the parts of blame ranges between the 1-indexed `line_start` and `line_end`.
+fn clip_ranges(
+    ranges: Vec<BlameRange>,
+    line_start: Option<isize>,
+    line_end: Option<usize>,
+) -> Vec<BlameRange> {
+    let line_start = line_start.unwrap_or(1).max(1) as usize;
+    let line_end = line_end.unwrap_or(usize::MAX);
+
+    ranges
+        .into_iter()
+        .filter(|r| r.line_end >= line_start && r.line_start <= line_end)
+        .map(|r| BlameRange {
+            line_start: r.line_start.max(line_start),
+            line_end: r.line_end.min(line_end),
+            ..r
+        })
+        .collect()
+}
+
 fn split_by_lines<'a>(text: &'a str, indices: &[u32], params: &Params) -> Result<&'a str, Error> {
     let char_start = match params.line_start {
         Some(line_start) if line_start == 1 => 0,
@@ -98,6 +221,7 @@ cccccc
                     line_start: None,
                     line_end: None,
                     branch: None,
+                    blame: false,
                 }
             )
             .unwrap_or_else(|_| panic!("bad")),
@@ -114,6 +238,7 @@ cccccc
                     line_start: Some(1),
                     line_end: None,
                     branch: None,
+                    blame: false,
                 }
             )
             .unwrap_or_else(|_| panic!("bad")),
@@ -130,6 +255,7 @@ cccccc
                     line_start: Some(2),
                     line_end: None,
                     branch: None,
+                    blame: false,
                 }
             )
             .unwrap_or_else(|_| panic!("bad")),
@@ -146,6 +272,7 @@ cccccc
                     line_start: Some(3),
                     line_end: Some(3),
                     branch: None,
+                    blame: false,
                 }
             )
             .unwrap_or_else(|_| panic!("bad")),
@@ -162,10 +289,30 @@ cccccc
                     line_start: Some(2),
                     line_end: Some(3),
                     branch: None,
+                    blame: false,
                 }
             )
             .unwrap_or_else(|_| panic!("bad")),
             &text[7..]
         );
     }
+
+    #[test]
+    fn clip_blame() {
+        let range = |line_start, line_end, commit_id: &str| BlameRange {
+            line_start,
+            line_end,
+            commit_id: commit_id.into(),
+            author: "bloop".into(),
+            unix_secs: 0,
+        };
+
+        let ranges = vec![range(1, 3, "a"), range(4, 4, "b"), range(5, 9, "c")];
+
+        assert_eq!(clip_ranges(ranges.clone(), None, None), ranges);
+        assert_eq!(
+            clip_ranges(ranges, Some(3), Some(6)),
+            vec![range(3, 3, "a"), range(4, 4, "b"), range(5, 6, "c")]
+        );
+    }
 }
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::State, Json};
+use secrecy::SecretString;
+
+use super::prelude::*;
+use crate::{remotes::git, Application};
+
+/// Hosts that have credentials set, and the user name for each. Passwords are never returned.
+#[derive(Serialize)]
+pub(super) struct GitHostsResponse {
+    hosts: BTreeMap<String, String>,
+}
+
+impl super::ApiResponse for GitHostsResponse {}
+
+#[derive(Deserialize)]
+pub(super) struct SetCredentials {
+    host: String,
+    username: String,
+    password: SecretString,
+}
+
+#[derive(Deserialize)]
+pub(super) struct HostParams {
+    host: String,
+}
+
+pub(super) async fn list(State(app): State<Application>) -> impl IntoResponse {
+    let hosts = app
+        .credentials
+        .git()
+        .hosts
+        .into_iter()
+        .map(|(host, auth)| (host, auth.username))
+        .collect();
+
+    json(GitHostsResponse { hosts })
+}
+
+/// Set the credentials used to clone and fetch repositories from a git server.
+pub(super) async fn set(
+    State(app): State<Application>,
+    Json(params): Json<SetCredentials>,
+) -> Result<()> {
+    let mut state = app.credentials.git();
+    state.hosts.insert(
+        params.host,
+        git::Auth {
+            username: params.username,
+            password: params.password,
+        },
+    );
+
+    app.credentials.set_git(state);
+    save(&app).await
+}
+
+pub(super) async fn delete(
+    Query(HostParams { host }): Query<HostParams>,
+    State(app): State<Application>,
+) -> Result<()> {
+    let mut state = app.credentials.git();
+    if state.hosts.remove(&host).is_none() {
+        return Err(Error::new(ErrorKind::NotFound, "no credentials for host"));
+    }
+
+    app.credentials.set_git(state);
+    save(&app).await
+}
+
+async fn save(app: &Application) -> Result<()> {
+    app.config
+        .source
+        .save_credentials(&app.credentials.serialize().await)
+        .map_err(|_| Error::internal("failed to save changes"))
+}
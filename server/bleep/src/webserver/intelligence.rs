@@ -1,18 +1,30 @@
-use std::{ops::Not, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Not,
+    sync::Arc,
+};
 
 use super::prelude::*;
 use crate::{
+    db::{IndexSource, PreciseIndex, SiteMatch, SymbolOccurrence, SymbolTable},
     indexes::{reader::ContentDocument, Indexes},
     intelligence::{
-        code_navigation::{CodeNavigationContext, FileSymbols, Occurrence, OccurrenceKind, Token},
+        code_navigation::{
+            CodeNavigationContext, FileSymbols, Occurrence, OccurrenceKind, Precision, Token,
+        },
         Language, NodeKind, TSLanguage,
     },
     repo::RepoRef,
     snippet::Snipper,
     text_range::TextRange,
+    Application,
 };
 
-use axum::{extract::Query, response::IntoResponse, Extension};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Extension,
+};
 use serde::{Deserialize, Serialize};
 
 /// The request made to the `local-intel` endpoint.
@@ -48,6 +60,7 @@ impl super::ApiResponse for TokenInfoResponse {}
 
 pub(super) async fn handle(
     Query(payload): Query<TokenInfoRequest>,
+    State(app): State<Application>,
     Extension(indexes): Extension<Arc<Indexes>>,
 ) -> Result<impl IntoResponse> {
     let repo_ref = payload.repo_ref.parse::<RepoRef>().map_err(Error::user)?;
@@ -64,32 +77,39 @@ pub(super) async fn handle(
         .await
         .map_err(Error::user)?
         .ok_or_else(|| Error::user("path not found").with_status(StatusCode::NOT_FOUND))?;
-    let lang = source_document.lang.as_deref();
-    let all_docs = {
-        let associated_langs = match lang.map(TSLanguage::from_id) {
-            Some(Language::Supported(config)) => config.language_ids,
-            _ => &[],
-        };
-        indexes
-            .file
-            .by_repo(
-                &repo_ref,
-                associated_langs.iter(),
-                payload.branch.as_deref(),
-            )
-            .await
-    };
+    let token_text = source_document
+        .content
+        .get(payload.start..payload.end)
+        .ok_or_else(|| Error::user("invalid token range"))?;
 
-    let source_document_idx = all_docs
-        .iter()
-        .position(|doc| doc.relative_path == payload.relative_path)
-        .ok_or(Error::internal("invalid language"))?;
+    let precise = precise_token_info(
+        &app,
+        &indexes,
+        &repo_ref,
+        &source_document,
+        payload.branch.as_deref(),
+        payload.start,
+    )
+    .await?;
+    if let Some(data) = precise {
+        return Ok(json(TokenInfoResponse { data }));
+    }
+
+    let repo_sites = repo_sites(
+        &app,
+        &indexes,
+        &repo_ref,
+        token_text,
+        &source_document,
+        payload.branch.as_deref(),
+    )
+    .await?;
 
     let ctx = CodeNavigationContext {
         repo_ref: repo_ref.clone(),
         token,
-        all_docs,
-        source_document_idx,
+        source_document,
+        repo_sites,
     };
 
     let data = ctx.token_info();
@@ -100,7 +120,7 @@ pub(super) async fn handle(
             ctx.active_token_text(),
             ctx.active_token_range(),
             payload.branch.as_deref(),
-            &source_document,
+            &ctx.source_document,
         )
         .await
         .map(TokenInfoResponse::new)
@@ -110,6 +130,185 @@ pub(super) async fn handle(
     }
 }
 
+/// Occurrences of the symbol at `start` from a SCIP index or LSIF dump of the repo, or `None` if
+/// no index covers the token.
+///
+/// Definitions are listed before references, unless the token is a definition, in which case only
+/// references are. Global symbols are also looked up in the default index of other repos.
+async fn precise_token_info(
+    app: &Application,
+    indexes: &Indexes,
+    repo_ref: &RepoRef,
+    source_document: &ContentDocument,
+    branch: Option<&str>,
+    start: usize,
+) -> Result<Option<Vec<FileSymbols>>> {
+    let index = PreciseIndex::new(&app.sql);
+    let repo = repo_ref.to_string();
+
+    let Some(indexed_branch) = index
+        .indexed_branch(&repo, branch.unwrap_or_default())
+        .await
+        .map_err(Error::internal)?
+    else {
+        return Ok(None);
+    };
+
+    let content = &source_document.content;
+    let path = &source_document.relative_path;
+    let Some(token) = index
+        .at(&repo, &indexed_branch, path, content, start)
+        .await
+        .map_err(Error::internal)?
+    else {
+        return Ok(None);
+    };
+
+    let occurrences = index
+        .by_symbol(&token.symbol)
+        .await
+        .map_err(Error::internal)?;
+
+    // Other repos are searched in their default index, where occurrences from an uploaded index
+    // take precedence over those from a committed one.
+    let uploaded = occurrences
+        .iter()
+        .filter(|o| o.branch.is_empty() && o.source == IndexSource::Upload)
+        .map(|o| o.repo_ref.clone())
+        .collect::<HashSet<_>>();
+
+    let occurrences = occurrences
+        .into_iter()
+        .filter(|o| {
+            if o.repo_ref == repo {
+                o.branch == indexed_branch.branch && o.source == indexed_branch.source
+            } else {
+                token.global
+                    && o.branch.is_empty()
+                    && (o.source == IndexSource::Upload || !uploaded.contains(&o.repo_ref))
+            }
+        })
+        .filter(|o| {
+            !(o.repo_ref == repo && o.relative_path == *path && o.range.contains(content, start))
+        })
+        .filter(|o| !(token.definition && o.definition))
+        .collect::<Vec<_>>();
+
+    let mut docs = HashMap::new();
+    let mut data = vec![];
+
+    for definitions in [true, false] {
+        let mut by_file = BTreeMap::<_, Vec<&SymbolOccurrence>>::new();
+        for o in occurrences.iter().filter(|o| o.definition == definitions) {
+            by_file
+                .entry((o.repo_ref.as_str(), o.relative_path.as_str()))
+                .or_default()
+                .push(o);
+        }
+
+        for ((file_repo, file), file_occurrences) in by_file {
+            if !docs.contains_key(&(file_repo, file)) {
+                let file_branch = if file_repo == repo { branch } else { None };
+                let doc = match file_repo.parse::<RepoRef>() {
+                    Ok(file_repo) => indexes
+                        .file
+                        .by_path(&file_repo, file, file_branch)
+                        .await
+                        .ok()
+                        .flatten(),
+                    Err(_) => None,
+                };
+                docs.insert((file_repo, file), doc);
+            }
+
+            // files that aren't indexed can't be shown
+            let Some(doc) = &docs[&(file_repo, file)] else {
+                continue;
+            };
+
+            let mut file_data = file_occurrences
+                .into_iter()
+                .filter_map(|o| {
+                    let bytes = o.range.to_byte_range(&doc.content)?;
+                    let snippet = Snipper::default()
+                        .expand(bytes.clone(), &doc.content, &doc.line_end_indices)
+                        .reify(&doc.content, &[]);
+
+                    Some(Occurrence {
+                        kind: if o.definition {
+                            OccurrenceKind::Definition
+                        } else {
+                            OccurrenceKind::Reference
+                        },
+                        precision: Precision::Precise,
+                        range: TextRange::from_byte_range(bytes, &doc.line_end_indices),
+                        snippet,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            file_data.sort_by_key(|occurrence| occurrence.range.start.byte);
+
+            if !file_data.is_empty() {
+                data.push(FileSymbols {
+                    repo_ref: (file_repo != repo).then(|| file_repo.to_owned()),
+                    file: file.to_owned(),
+                    data: file_data,
+                });
+            }
+        }
+    }
+
+    Ok(data.is_empty().not().then_some(data))
+}
+
+/// Look up the sites of top-level symbols called `name` in the other files of the repo, and load
+/// the documents they're in.
+pub(super) async fn repo_sites(
+    app: &Application,
+    indexes: &Indexes,
+    repo_ref: &RepoRef,
+    name: &str,
+    source_document: &ContentDocument,
+    branch: Option<&str>,
+) -> anyhow::Result<Vec<(ContentDocument, Vec<SiteMatch>)>> {
+    let associated_langs = match source_document.lang.as_deref().map(TSLanguage::from_id) {
+        Some(Language::Supported(config)) => config.language_ids,
+        _ => &[],
+    };
+
+    let matches = SymbolTable::new(&app.sql)
+        .lookup(&repo_ref.to_string(), name, associated_langs, branch)
+        .await?;
+
+    let mut by_cache_key = HashMap::<_, Vec<_>>::new();
+    for site in matches {
+        if site.relative_path != source_document.relative_path {
+            by_cache_key
+                .entry(site.cache_key.clone())
+                .or_default()
+                .push(site);
+        }
+    }
+
+    if by_cache_key.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut docs = indexes
+        .file
+        .by_cache_keys(repo_ref, by_cache_key.keys().map(String::as_str))
+        .await?;
+
+    let mut sites = by_cache_key
+        .into_iter()
+        .filter_map(|(cache_key, sites)| Some((docs.remove(&cache_key)?, sites)))
+        .collect::<Vec<_>>();
+    sites.sort_by(|(a, _), (b, _)| a.relative_path.cmp(&b.relative_path));
+
+    Ok(sites)
+}
+
 async fn search_nav(
     indexes: Arc<Indexes>,
     repo_ref: &RepoRef,
@@ -249,6 +448,7 @@ async fn search_nav(
 
                     Occurrence {
                         kind: is_def,
+                        precision: Precision::Heuristic,
                         range,
                         snippet,
                     }
@@ -259,6 +459,7 @@ async fn search_nav(
             let file = doc.relative_path;
 
             data.is_empty().not().then(|| FileSymbols {
+                repo_ref: None,
                 file: file.clone(),
                 data,
             })
@@ -281,6 +482,7 @@ mod tests {
                     "file": "server/bleep/src/symbol.rs",
                     "data": [{
                         "kind": "definition",
+                        "precision": "precise",
                         "range": {
                             "start": { "byte": 2620, "line": 90, "column": 0  },
                             "end": { "byte": 2627, "line": 90, "column": 0  },
@@ -298,6 +500,7 @@ mod tests {
                     "file": "server/bleep/src/intelligence/scope_resolution.rs",
                     "data": [{
                         "kind": "reference",
+                        "precision": "heuristic",
                         "range": {
                             "start": { "byte": 2725, "line": 93, "column": 0  },
                             "end": { "byte": 2732, "line": 93, "column": 0  },
@@ -316,9 +519,11 @@ mod tests {
         let observed = serde_json::to_value(TokenInfoResponse {
             data: vec![
                 FileSymbols {
+                    repo_ref: None,
                     file: "server/bleep/src/symbol.rs".into(),
                     data: vec![Occurrence {
                     kind: OccurrenceKind::Definition,
+                    precision: Precision::Precise,
                     range: TextRange {
                         start: Point {
                             byte: 2620,
@@ -341,9 +546,11 @@ mod tests {
                 }],
                 },
                 FileSymbols {
+                    repo_ref: None,
                     file: "server/bleep/src/intelligence/scope_resolution.rs".into(),
                     data: vec![Occurrence {
                         kind: OccurrenceKind::Reference,
+                        precision: Precision::Heuristic,
                         range: TextRange {
                             start: Point {
                                 byte: 2725,
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use super::prelude::*;
+use crate::{
+    indexes::Indexes,
+    intelligence::outline::{outline, OutlineSymbol},
+    repo::RepoRef,
+};
+
+use axum::{extract::Query, response::IntoResponse, Extension};
+use serde::{Deserialize, Serialize};
+
+/// The request made to the `outline` endpoint.
+#[derive(Debug, Deserialize)]
+pub(super) struct OutlineRequest {
+    /// The repo_ref of the file of interest
+    repo_ref: String,
+
+    /// The path to the file of interest, relative to the repo root
+    relative_path: String,
+
+    /// Branch name to use for the lookup,
+    branch: Option<String>,
+}
+
+/// The response from the `outline` endpoint.
+#[derive(Serialize)]
+pub(super) struct OutlineResponse {
+    symbols: Vec<OutlineSymbol>,
+}
+
+impl super::ApiResponse for OutlineResponse {}
+
+/// The definitions of a file, nested by enclosing declaration. Files in languages without
+/// scope resolution have an empty outline.
+pub(super) async fn handle(
+    Query(payload): Query<OutlineRequest>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+) -> Result<impl IntoResponse> {
+    let repo_ref = &payload.repo_ref.parse::<RepoRef>().map_err(Error::user)?;
+
+    let document = indexes
+        .file
+        .by_path(repo_ref, &payload.relative_path, payload.branch.as_deref())
+        .await
+        .map_err(Error::user)?
+        .ok_or_else(|| Error::user("file not found").with_status(StatusCode::NOT_FOUND))?;
+
+    let symbols = match (
+        document.lang.as_deref(),
+        document.symbol_locations.scope_graph(),
+    ) {
+        (Some(lang_id), Some(graph)) => outline(&document.content, lang_id, graph),
+        _ => vec![],
+    };
+
+    Ok(json(OutlineResponse { symbols }))
+}
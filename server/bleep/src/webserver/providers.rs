@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use secrecy::SecretString;
+
+use super::prelude::*;
+use crate::{
+    remotes::provider::{Api, Instance, Provider},
+    Application,
+};
+
+#[derive(Deserialize)]
+pub(super) struct Login {
+    /// Base URL of a self-hosted instance. Defaults to the public instance of the provider.
+    url: Option<String>,
+    token: SecretString,
+}
+
+#[derive(Deserialize)]
+pub(super) struct Logout {
+    /// Base URL of the instance to log out of. Defaults to the public instance of the provider.
+    url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(super) struct LoginResponse {
+    user: String,
+}
+
+impl super::ApiResponse for LoginResponse {}
+
+/// Store the credentials for an instance of a provider, once the instance has accepted them.
+///
+/// Each instance is logged in separately, so that several instances of the same provider can be
+/// used at once.
+pub(super) async fn login<A: Api>(
+    State(app): State<Application>,
+    Json(Login { url, token }): Json<Login>,
+) -> Result<impl IntoResponse> {
+    let instance = Instance::<A>::new(url, token);
+
+    let user = match instance.validate().await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Err(Error::new(
+                ErrorKind::UpstreamService,
+                "failed to reach the provider",
+            ))
+        }
+        Err(_) => return Err(Error::user("the provider rejected this token")),
+    };
+
+    let mut instances = app.credentials.instances::<A>();
+    instances.insert(instance);
+    app.credentials.set_instances(instances);
+    save(&app).await?;
+
+    Ok(json(LoginResponse { user }))
+}
+
+pub(super) async fn logout<A: Api>(
+    State(app): State<Application>,
+    Query(Logout { url }): Query<Logout>,
+) -> Result<()> {
+    let host = Instance::<A>::new(url, String::new().into())
+        .host()
+        .to_owned();
+
+    let mut instances = app.credentials.instances::<A>();
+    if instances.remove(&host).is_none() {
+        return Err(Error::new(ErrorKind::NotFound, "not logged in"));
+    }
+
+    app.credentials.set_instances(instances);
+    save(&app).await
+}
+
+async fn save(app: &Application) -> Result<()> {
+    app.config
+        .source
+        .save_credentials(&app.credentials.serialize().await)
+        .map_err(|_| Error::internal("failed to save changes"))
+}
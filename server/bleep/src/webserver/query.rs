@@ -1,7 +1,23 @@
-use axum::extract::State;
+use axum::{
+    body::StreamBody,
+    extract::State,
+    http::header,
+    response::{sse, Response as AxumResponse, Sse},
+    Json,
+};
+use futures::{StreamExt, TryStreamExt};
+use serde_json::json;
 
 use super::prelude::*;
-use crate::{db::QueryLog, query::execute::ApiQuery, Application};
+use crate::{
+    db::QueryLog,
+    query::{
+        execute::{ApiQuery, QueryResult},
+        parser,
+    },
+    snippet::SnippedFile,
+    Application,
+};
 
 pub(super) async fn handle(
     Query(api_params): Query<ApiQuery>,
@@ -16,3 +32,209 @@ pub(super) async fn handle(
         .map(json)
         .map_err(super::Error::from)
 }
+
+/// Stream results as server-sent events, as soon as each segment of the index is searched.
+///
+/// Each event is a JSON-encoded `QueryEvent`. The last event carries stats for the whole query.
+pub(super) async fn stream(
+    Query(api_params): Query<ApiQuery>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    State(app): State<Application>,
+) -> Result<impl IntoResponse> {
+    parser::parse(&api_params.q).map_err(Error::user)?;
+    QueryLog::new(&app.sql).insert(&api_params.q).await?;
+
+    let events = api_params.query_stream(indexes).map(|event| match event {
+        Ok(event) => sse::Event::default().json_data(event),
+        Err(err) => Ok(sse::Event::default().event("error").data(err.to_string())),
+    });
+
+    Ok(Sse::new(events))
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ExportFormat {
+    /// One JSON-encoded `QueryResult` per line.
+    #[default]
+    Jsonl,
+
+    /// A SARIF 2.1.0 log, with a result for every highlighted match.
+    Sarif,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ExportParams {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Export every result of a query, rather than a single page.
+///
+/// JSONL output is streamed as results are read from the index, whereas a SARIF log is a single
+/// JSON document, and is only sent once all results are collected.
+pub(super) async fn export(
+    Query(api_params): Query<ApiQuery>,
+    Query(params): Query<ExportParams>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    State(app): State<Application>,
+) -> Result<AxumResponse> {
+    // Report malformed queries up-front, as errors can't change the status code mid-stream.
+    parser::parse(&api_params.q).map_err(Error::user)?;
+    QueryLog::new(&app.sql).insert(&api_params.q).await?;
+
+    let q = api_params.q.clone();
+    let results = api_params.query_all(indexes);
+
+    Ok(match params.format {
+        ExportFormat::Jsonl => {
+            let lines = results.map(|result| -> anyhow::Result<Vec<u8>> {
+                let mut line = serde_json::to_vec(&result?)?;
+                line.push(b'\n');
+                Ok(line)
+            });
+
+            (
+                [(header::CONTENT_TYPE, "application/x-ndjson")],
+                StreamBody::new(lines),
+            )
+                .into_response()
+        }
+        ExportFormat::Sarif => {
+            let results = results.try_collect::<Vec<_>>().await.map_err(Error::from)?;
+
+            (
+                [(header::CONTENT_TYPE, "application/sarif+json")],
+                Json(sarif_log(&q, &results)),
+            )
+                .into_response()
+        }
+    })
+}
+
+/// Build a SARIF 2.1.0 log from a list of query results.
+///
+/// Only content matches point to a location in a file, so other kinds of results are left out.
+fn sarif_log(q: &str, results: &[QueryResult]) -> serde_json::Value {
+    let results = results
+        .iter()
+        .filter_map(|result| match result {
+            QueryResult::Snippets(file) => Some(file),
+            _ => None,
+        })
+        .flat_map(|file| sarif_results(q, file))
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "bloop",
+                    "informationUri": "https://bloop.ai",
+                    "rules": [{
+                        "id": "search-match",
+                        "shortDescription": { "text": "Code search match" },
+                    }],
+                },
+            },
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_results<'a>(
+    q: &'a str,
+    file: &'a SnippedFile,
+) -> impl Iterator<Item = serde_json::Value> + 'a {
+    file.snippets.iter().flat_map(move |snippet| {
+        // Snippet line ranges are zero-based and inclusive, SARIF lines start at 1.
+        let context_region = json!({
+            "startLine": snippet.line_range.start + 1,
+            "endLine": snippet.line_range.end + 1,
+            "snippet": { "text": snippet.data },
+        });
+
+        snippet.highlights.iter().map(move |highlight| {
+            let (start_line, start_column) = sarif_position(&snippet.data, highlight.start);
+            let (end_line, end_column) = sarif_position(&snippet.data, highlight.end);
+
+            json!({
+                "ruleId": "search-match",
+                "level": "note",
+                "message": { "text": format!("Match for `{q}`") },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file.relative_path },
+                        "region": {
+                            "startLine": snippet.line_range.start + start_line,
+                            "startColumn": start_column,
+                            "endLine": snippet.line_range.start + end_line,
+                            "endColumn": end_column,
+                        },
+                        "contextRegion": context_region,
+                    },
+                }],
+                "properties": {
+                    "repoName": file.repo_name,
+                    "repoRef": file.repo_ref,
+                },
+            })
+        })
+    })
+}
+
+/// Convert a byte offset into a snippet to a one-based line and UTF-16 column, relative to the
+/// start of the snippet.
+fn sarif_position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].encode_utf16().count() + 1;
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snippet::Snippet;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sarif_positions() {
+        let results = [QueryResult::Snippets(SnippedFile {
+            relative_path: "src/main.rs".into(),
+            repo_name: "bloop".into(),
+            repo_ref: "github.com/bloopai/bloop".into(),
+            lang: Some("rust".into()),
+            snippets: vec![Snippet {
+                data: "fn main() {\n    println!(\"héllo\");\n}\n".into(),
+                highlights: vec![26..32],
+                symbols: vec![],
+                line_range: 4..6,
+            }],
+        })];
+
+        let log = sarif_log("héllo", &results);
+        let result = &log["runs"][0]["results"][0];
+        let location = &result["locations"][0]["physicalLocation"];
+
+        assert_eq!(location["artifactLocation"]["uri"], "src/main.rs");
+        assert_eq!(
+            location["region"],
+            json!({
+                "startLine": 6,
+                "startColumn": 15,
+                "endLine": 6,
+                "endColumn": 20,
+            })
+        );
+        assert_eq!(location["contextRegion"]["startLine"], 5);
+        assert_eq!(location["contextRegion"]["endLine"], 7);
+        assert_eq!(result["properties"]["repoName"], "bloop");
+    }
+}
@@ -2,12 +2,19 @@ use std::{collections::HashSet, hash::Hash, time::Duration};
 
 use crate::{
     background::QueuedRepoStatus,
-    repo::{Backend, BranchFilter, RepoRef, Repository, SyncStatus},
+    db::PreciseIndex,
+    indexes::file::{BranchDiff, BranchNotIndexed},
+    intelligence::precise,
+    remotes::provider::RemoteRepo,
+    repo::{
+        iterator::FileFilter, Backend, BranchFilter, IndexSettings, RepoRef, Repository, SyncStatus,
+    },
     state::RepositoryPool,
     Application,
 };
 use axum::{
-    extract::{Query, State},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Query, State},
     http::StatusCode,
     response::{sse, IntoResponse, Sse},
     Extension, Json,
@@ -36,6 +43,7 @@ pub(crate) struct Repo {
     pub(super) most_common_lang: Option<String>,
     pub(super) branch_filter: BranchFilter,
     pub(super) branches: Vec<Branch>,
+    pub(super) index_settings: IndexSettings,
 }
 
 impl From<(&RepoRef, &Repository)> for Repo {
@@ -134,27 +142,29 @@ impl From<(&RepoRef, &Repository)> for Repo {
             most_common_lang: repo.most_common_lang.clone(),
             branch_filter,
             branches,
+            index_settings: repo.index_settings.clone(),
         }
     }
 }
 
 impl Repo {
-    pub(crate) fn from_github(
+    pub(crate) fn from_remote(
         local_duplicates: Vec<RepoRef>,
-        origin: &octocrab::models::Repository,
+        repo_ref: RepoRef,
+        origin: &RemoteRepo,
     ) -> Self {
-        let name = origin.full_name.clone().unwrap();
         Repo {
-            provider: Backend::Github,
-            repo_ref: RepoRef::new(Backend::Github, &name).unwrap(),
+            provider: repo_ref.backend(),
+            repo_ref,
             sync_status: SyncStatus::Uninitialized,
             local_duplicates,
-            name,
-            last_update: origin.pushed_at.unwrap(),
+            name: origin.name.clone(),
+            last_update: origin.pushed_at,
             last_index: None,
             most_common_lang: None,
             branch_filter: crate::repo::BranchFilter::Select(vec![]),
             branches: vec![],
+            index_settings: IndexSettings::default(),
         }
     }
 }
@@ -200,6 +210,12 @@ pub(super) fn router() -> Router {
         .route("/status", get(index_status))
         .route("/indexed", indexed)
         .route("/sync", get(sync).delete(delete_sync))
+        .route("/settings", put(set_settings))
+        .route("/diff", get(diff_branches))
+        .route(
+            "/precise-index",
+            put(upload_precise_index).layer(DefaultBodyLimit::max(MAX_PRECISE_INDEX_LEN)),
+        )
 }
 
 /// Get a stream of status notifications about the indexing of each repository
@@ -232,6 +248,36 @@ pub(crate) struct RepoParams {
     pub(crate) repo: RepoRef,
 }
 
+#[derive(Deserialize)]
+pub(super) struct DiffParams {
+    repo: RepoRef,
+    base: String,
+    head: String,
+}
+
+impl super::ApiResponse for BranchDiff {}
+
+/// The largest SCIP index or LSIF dump that can be uploaded.
+const MAX_PRECISE_INDEX_LEN: usize = 1024 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub(super) struct PreciseIndexParams {
+    repo: RepoRef,
+    /// The branch the index was produced for. Without one, the index applies to every branch
+    /// that doesn't have its own.
+    branch: Option<String>,
+    /// The directory of the repo that the indexer was run in
+    #[serde(default)]
+    root: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct PreciseIndexUploaded {
+    occurrences: usize,
+}
+
+impl super::ApiResponse for PreciseIndexUploaded {}
+
 /// Live report of the state of the sync queue
 //
 pub(super) async fn queue(State(app): State<Application>) -> impl IntoResponse {
@@ -314,6 +360,35 @@ pub(super) async fn sync(
     Ok(json(ReposResponse::SyncQueued))
 }
 
+/// Change which files of a repository are indexed, and reindex it with the new settings
+pub(super) async fn set_settings(
+    Query(RepoParams { repo }): Query<RepoParams>,
+    State(app): State<Application>,
+    Json(settings): Json<IndexSettings>,
+) -> Result<impl IntoResponse> {
+    let Some(disk_path) = app
+        .repo_pool
+        .read_async(&repo, |_k, v| v.disk_path.clone())
+        .await
+    else {
+        return Err(Error::new(ErrorKind::NotFound, "Can't find repository"));
+    };
+
+    // check the globs before they're stored, as they are only used in the next sync
+    FileFilter::new(&disk_path, &settings).map_err(Error::user)?;
+
+    app.repo_pool
+        .update_async(&repo, |_k, v| {
+            // files that are now included or excluded may not have changed
+            v.index_settings = settings;
+            v.last_indexed_commit = None;
+        })
+        .await;
+    app.write_index().enqueue_sync(vec![repo]).await;
+
+    Ok(json(ReposResponse::SyncQueued))
+}
+
 /// Synchronize a repo by its id
 pub(super) async fn delete_sync(
     Query(RepoParams { repo }): Query<RepoParams>,
@@ -326,13 +401,12 @@ pub(super) async fn delete_sync(
 /// List all repositories that are either indexed, or available for indexing
 //
 pub(super) async fn available(State(app): State<Application>) -> impl IntoResponse {
-    let unknown_github = app
+    let unknown_remote = app
         .credentials
-        .github()
-        .map(|gh| gh.repositories)
-        .unwrap_or_default()
-        .iter()
-        .map(|repo| {
+        .remote_repos()
+        .await
+        .into_iter()
+        .map(|(repo_ref, repo)| {
             let mut local_duplicates = vec![];
             app.repo_pool.scan(|k, v| {
                 // either `ssh_url` or `clone_url` should match what we generate.
@@ -345,11 +419,7 @@ pub(super) async fn available(State(app): State<Application>) -> impl IntoRespon
                 // most of the time is spent in the network.
                 if [
                     repo.ssh_url.as_deref().unwrap_or_default().to_lowercase(),
-                    repo.clone_url
-                        .as_ref()
-                        .map(|url| url.as_str())
-                        .unwrap_or_default()
-                        .to_lowercase(),
+                    repo.clone_url.to_lowercase(),
                 ]
                 .contains(&v.remote.to_string().to_lowercase())
                 {
@@ -357,11 +427,11 @@ pub(super) async fn available(State(app): State<Application>) -> impl IntoRespon
                 }
             });
 
-            Repo::from_github(local_duplicates, repo)
+            Repo::from_remote(local_duplicates, repo_ref, &repo)
         })
         .collect::<HashSet<_>>();
 
-    let repos = list_unique_repos(app.repo_pool.clone(), unknown_github).await;
+    let repos = list_unique_repos(app.repo_pool.clone(), unknown_remote).await;
     (StatusCode::OK, Json(ReposResponse::List(repos)))
 }
 
@@ -431,6 +501,63 @@ pub(super) async fn scan_local(
     }
 }
 
+/// List the files added, removed and modified between two indexed branches of a repository, with
+/// unified diffs of the modified files
+pub(super) async fn diff_branches(
+    Query(DiffParams { repo, base, head }): Query<DiffParams>,
+    State(app): State<Application>,
+) -> Result<impl IntoResponse> {
+    if app.repo_pool.read_async(&repo, |_, _| ()).await.is_none() {
+        return Err(Error::new(ErrorKind::NotFound, "Can't find repository"));
+    }
+
+    let changes = app
+        .indexes
+        .file
+        .diff_branches(&repo, &base, &head)
+        .await
+        .map_err(|err| match err.downcast::<BranchNotIndexed>() {
+            Ok(err) => Error::user(err).with_status(StatusCode::NOT_FOUND),
+            Err(err) => Error::internal(err),
+        })?;
+
+    Ok(json(changes))
+}
+
+/// Store a SCIP index or LSIF dump of a repository, replacing the previous index of the same
+/// branch and root directory
+pub(super) async fn upload_precise_index(
+    Query(PreciseIndexParams { repo, branch, root }): Query<PreciseIndexParams>,
+    State(app): State<Application>,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    if app.repo_pool.read_async(&repo, |_, _| ()).await.is_none() {
+        return Err(Error::new(ErrorKind::NotFound, "Can't find repository"));
+    }
+
+    let root = root.trim_matches('/').to_owned();
+    let occurrences = {
+        let root = root.clone();
+        tokio::task::spawn_blocking(move || precise::parse(&body, &root))
+            .await
+            .map_err(Error::internal)?
+            .map_err(|err| Error::user(format!("{err:#}")))?
+    };
+    let count = occurrences.len();
+
+    PreciseIndex::new(&app.sql)
+        .replace(
+            &repo.to_string(),
+            branch.as_deref().unwrap_or_default(),
+            &root,
+            occurrences,
+        )
+        .await
+        .map_err(Error::internal)?;
+
+    Ok(json(PreciseIndexUploaded { occurrences: count }))
+}
+
 async fn list_unique_repos(repo_pool: RepositoryPool, other: HashSet<Repo>) -> Vec<Repo> {
     let mut repos = HashSet::new();
     repo_pool
@@ -473,6 +600,9 @@ mod test {
                     last_index_unix_secs: 123456,
                     most_common_lang: None,
                     branch_filter: Default::default(),
+                    index_settings: Default::default(),
+                    last_indexed_commit: None,
+                    last_history_commit: None,
                 },
             )
             .unwrap();
@@ -491,6 +621,9 @@ mod test {
                     last_index_unix_secs: 123456,
                     most_common_lang: None,
                     branch_filter: Default::default(),
+                    index_settings: Default::default(),
+                    last_indexed_commit: None,
+                    last_history_commit: None,
                 },
             )
             .unwrap();
@@ -511,6 +644,9 @@ mod test {
                     last_index_unix_secs: 0,
                     most_common_lang: None,
                     branch_filter: Default::default(),
+                    index_settings: Default::default(),
+                    last_indexed_commit: None,
+                    last_history_commit: None,
                 },
             )
                 .into(),
@@ -530,6 +666,9 @@ mod test {
                 last_index_unix_secs: 0,
                 most_common_lang: None,
                 branch_filter: Default::default(),
+                index_settings: Default::default(),
+                last_indexed_commit: None,
+                last_history_commit: None,
             },
         )
             .into();
@@ -0,0 +1,93 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::prelude::*;
+use crate::{db::SavedSearches, periodic::run_saved_search, query::parser, Application};
+
+#[derive(Deserialize)]
+pub(super) struct SavedSearchParams {
+    name: String,
+    /// A query written in the bloop query language
+    query: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct Created {
+    id: i64,
+}
+
+pub(super) async fn list(State(app): State<Application>) -> Result<impl IntoResponse> {
+    let searches = SavedSearches::new(&app.sql).list().await?;
+    Ok(Json(searches))
+}
+
+/// Save a search, recording its current matches as a baseline for later changes.
+pub(super) async fn create(
+    State(app): State<Application>,
+    Json(params): Json<SavedSearchParams>,
+) -> Result<impl IntoResponse> {
+    parser::parse(&params.query).map_err(Error::user)?;
+
+    let id = SavedSearches::new(&app.sql)
+        .insert(&params.name, &params.query)
+        .await?;
+    run_saved_search(&app, id, &params.query, None).await?;
+
+    Ok((StatusCode::CREATED, Json(Created { id })))
+}
+
+pub(super) async fn get(
+    Path(id): Path<i64>,
+    State(app): State<Application>,
+) -> Result<impl IntoResponse> {
+    SavedSearches::new(&app.sql)
+        .get(id)
+        .await?
+        .map(Json)
+        .ok_or_else(not_found)
+}
+
+/// Change a saved search. Previous matches are discarded, and the new query sets a new baseline.
+pub(super) async fn update(
+    Path(id): Path<i64>,
+    State(app): State<Application>,
+    Json(params): Json<SavedSearchParams>,
+) -> Result<()> {
+    parser::parse(&params.query).map_err(Error::user)?;
+
+    if !SavedSearches::new(&app.sql)
+        .update(id, &params.name, &params.query)
+        .await?
+    {
+        return Err(not_found());
+    }
+
+    run_saved_search(&app, id, &params.query, None).await?;
+    Ok(())
+}
+
+pub(super) async fn delete(Path(id): Path<i64>, State(app): State<Application>) -> Result<()> {
+    if !SavedSearches::new(&app.sql).delete(id).await? {
+        return Err(not_found());
+    }
+
+    Ok(())
+}
+
+/// Matches that were added or removed by the latest run of a saved search.
+pub(super) async fn diff(
+    Path(id): Path<i64>,
+    State(app): State<Application>,
+) -> Result<impl IntoResponse> {
+    SavedSearches::new(&app.sql)
+        .last_diff(id)
+        .await?
+        .map(Json)
+        .ok_or_else(not_found)
+}
+
+fn not_found() -> Error {
+    Error::new(ErrorKind::NotFound, "saved search not found")
+}
@@ -0,0 +1,58 @@
+use axum::extract::State;
+
+use super::prelude::*;
+use crate::{repo::RepoRef, symbol::SymbolMatch, Application};
+
+/// The most definitions returned for one request.
+const MAX_LIMIT: usize = 500;
+
+fn default_limit() -> usize {
+    50
+}
+
+/// The request made to the `symbols` endpoint.
+#[derive(Debug, Deserialize)]
+pub(super) struct SymbolsRequest {
+    /// A fuzzy symbol name, e.g. `gfm` for `get_file_metadata`
+    q: String,
+
+    /// Only search the definitions of this repo, instead of every indexed repo
+    repo_ref: Option<String>,
+
+    /// Maximum number of definitions returned, at most `MAX_LIMIT`
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+/// The response from the `symbols` endpoint.
+#[derive(Serialize)]
+pub(super) struct SymbolsResponse {
+    symbols: Vec<SymbolMatch>,
+}
+
+impl super::ApiResponse for SymbolsResponse {}
+
+/// Rank symbol definitions for a "go to symbol" palette.
+pub(super) async fn handle(
+    Query(params): Query<SymbolsRequest>,
+    State(app): State<Application>,
+) -> Result<impl IntoResponse> {
+    let repo_ref = params
+        .repo_ref
+        .map(|r| r.parse::<RepoRef>())
+        .transpose()
+        .map_err(Error::user)?;
+
+    let symbols = app
+        .indexes
+        .file
+        .fuzzy_symbol_match(
+            &params.q,
+            repo_ref.as_ref(),
+            params.limit.clamp(1, MAX_LIMIT),
+        )
+        .await
+        .map_err(Error::internal)?;
+
+    Ok(json(SymbolsResponse { symbols }))
+}